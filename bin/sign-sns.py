@@ -0,0 +1,81 @@
+#! /usr/bin/python3
+
+# Wraps a FlexMatch event in a signed SNS Lambda event, for testing VERIFY_SIGNATURES locally.
+# The signing cert is self-signed, serve it as a stand-in for SNS with
+#   python3 -m http.server 8099 --directory {out dir}
+# and run the Lambda with SIGNING_CERT_URLS=http://localhost:8099/
+
+import base64
+import datetime
+import json
+import os
+import subprocess
+import sys
+import uuid
+
+if len(sys.argv) < 3:
+    print('Usage: sign-sns {event json file} {out dir} [signature version (1 or 2)] [cert url]')
+    sys.exit(1)
+
+event_file = sys.argv[1]
+out_dir = sys.argv[2]
+signature_version = sys.argv[3] if len(sys.argv) > 3 else '2'
+cert_url = sys.argv[4] if len(sys.argv) > 4 else 'http://localhost:8099/cert.pem'
+
+os.makedirs(out_dir, exist_ok=True)
+key_file = os.path.join(out_dir, 'key.pem')
+cert_file = os.path.join(out_dir, 'cert.pem')
+
+if not os.path.exists(cert_file):
+    subprocess.run([
+        'openssl', 'req', '-x509', '-newkey', 'rsa:2048', '-nodes',
+        '-keyout', key_file, '-out', cert_file,
+        '-days', '30', '-subj', '/CN=sns.localhost',
+    ], check=True, capture_output=True)
+
+with open(event_file) as f:
+    message = f.read()
+
+message_id = str(uuid.uuid4())
+timestamp = datetime.datetime.utcnow().strftime('%Y-%m-%dT%H:%M:%S.%f')[:-3] + 'Z'
+topic_arn = 'arn:aws:sns:us-west-2:123456789012:echo-matchmaking'
+
+string_to_sign = ''.join('{}\n{}\n'.format(name, value) for name, value in [
+    ('Message', message),
+    ('MessageId', message_id),
+    ('Timestamp', timestamp),
+    ('TopicArn', topic_arn),
+    ('Type', 'Notification'),
+])
+
+digest = '-sha1' if signature_version == '1' else '-sha256'
+signature = subprocess.run(
+    ['openssl', 'dgst', digest, '-sign', key_file],
+    input=string_to_sign.encode(), check=True, capture_output=True).stdout
+
+event = {
+    'Records': [{
+        'EventSource': 'aws:sns',
+        'EventVersion': '1.0',
+        'EventSubscriptionArn': topic_arn + ':' + str(uuid.uuid4()),
+        'Sns': {
+            'Type': 'Notification',
+            'MessageId': message_id,
+            'TopicArn': topic_arn,
+            'Subject': None,
+            'Message': message,
+            'Timestamp': timestamp,
+            'SignatureVersion': signature_version,
+            'Signature': base64.b64encode(signature).decode(),
+            'SigningCertUrl': cert_url,
+            'UnsubscribeUrl': 'http://localhost:8099/unsubscribe',
+            'MessageAttributes': {},
+        },
+    }],
+}
+
+event_out = os.path.join(out_dir, 'event-{}.json'.format(message_id))
+with open(event_out, 'w') as f:
+    json.dump(event, f, indent=2)
+
+print(event_out)
@@ -0,0 +1,54 @@
+#! /usr/bin/python3
+
+# Stands in for a webhook destination, for testing WEBHOOKS locally.
+# Prints each request, checks X-Echo-Signature when given the secret, and can fail the first
+# requests with a 503 to exercise retries.
+
+import hashlib
+import hmac
+import http.server
+import json
+import sys
+
+if len(sys.argv) < 2:
+    print('Usage: webhook-server {port} [secret] [requests to fail]')
+    sys.exit(1)
+
+port = int(sys.argv[1])
+secret = sys.argv[2] if len(sys.argv) > 2 and sys.argv[2] else None
+fail = int(sys.argv[3]) if len(sys.argv) > 3 else 0
+
+
+class Handler(http.server.BaseHTTPRequestHandler):
+    def do_POST(self):
+        global fail
+
+        body = self.rfile.read(int(self.headers.get('Content-Length', 0)))
+
+        if secret:
+            timestamp = self.headers.get('X-Echo-Timestamp', '')
+            expected = 'sha256=' + hmac.new(
+                secret.encode(), timestamp.encode() + b'.' + body, hashlib.sha256).hexdigest()
+            if not hmac.compare_digest(expected, self.headers.get('X-Echo-Signature', '')):
+                print('{}: bad signature'.format(self.path))
+                self.send_response(401)
+                self.end_headers()
+                return
+
+        if fail > 0:
+            fail -= 1
+            print('{}: failing, {} more to fail'.format(self.path, fail))
+            self.send_response(503)
+            self.end_headers()
+            return
+
+        print('{}: {}'.format(self.path, json.dumps(json.loads(body), indent=2)))
+        self.send_response(204)
+        self.end_headers()
+
+    def log_message(self, format, *args):
+        pass
+
+
+print('listening on {}'.format(port))
+http.server.HTTPServer(('', port), Handler).serve_forever()
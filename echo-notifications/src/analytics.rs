@@ -0,0 +1,464 @@
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use tracing::warn;
+
+use echo_protocol::events::{Acceptance, MatchmakingDetail, MatchmakingEvent, Ticket};
+
+/// CloudWatch namespace the metrics are published under
+const NAMESPACE: &str = "Echo/Matchmaking";
+
+const TIME_TO_MATCH: &str = "TimeToMatch";
+const TIME_IN_QUEUE: &str = "TimeInQueue";
+const TICKETS_SUCCEEDED: &str = "TicketsSucceeded";
+const TICKETS_TIMED_OUT: &str = "TicketsTimedOut";
+const TICKETS_CANCELLED: &str = "TicketsCancelled";
+const TICKETS_FAILED: &str = "TicketsFailed";
+const MATCHES_ACCEPTED: &str = "MatchesAccepted";
+const MATCHES_REJECTED: &str = "MatchesRejected";
+const ACCEPTANCE_TIMED_OUT: &str = "AcceptanceTimedOut";
+
+/// What one event adds to the metrics of its matchmaking configuration
+#[derive(Debug, Default, PartialEq)]
+pub struct EventMetrics {
+    pub configuration: String,
+
+    /// milliseconds, per ticket
+    pub time_to_match: Vec<i64>,
+    pub time_in_queue: Vec<i64>,
+
+    /// outcome counts, by metric name
+    pub counts: BTreeMap<&'static str, u64>,
+}
+
+impl EventMetrics {
+    fn is_empty(&self) -> bool {
+        self.time_to_match.is_empty() && self.time_in_queue.is_empty() && self.counts.is_empty()
+    }
+
+    fn count(&mut self, name: &'static str, count: usize) {
+        *self.counts.entry(name).or_default() += count as u64;
+    }
+
+    /// The CloudWatch Embedded Metric Format log line for the metrics
+    fn emf(&self, time: DateTime<Utc>) -> serde_json::Value {
+        let mut metrics = Vec::new();
+        let mut line = serde_json::Map::new();
+        line.insert(
+            "Configuration".to_owned(),
+            self.configuration.clone().into(),
+        );
+
+        for (name, values) in [
+            (TIME_TO_MATCH, &self.time_to_match),
+            (TIME_IN_QUEUE, &self.time_in_queue),
+        ] {
+            if !values.is_empty() {
+                metrics.push(serde_json::json!({ "Name": name, "Unit": "Milliseconds" }));
+                line.insert(name.to_owned(), values.clone().into());
+            }
+        }
+        for (name, count) in &self.counts {
+            metrics.push(serde_json::json!({ "Name": name, "Unit": "Count" }));
+            line.insert((*name).to_owned(), (*count).into());
+        }
+
+        line.insert(
+            "_aws".to_owned(),
+            serde_json::json!({
+                "Timestamp": time.timestamp_millis(),
+                "CloudWatchMetrics": [{
+                    "Namespace": NAMESPACE,
+                    "Dimensions": [["Configuration"]],
+                    "Metrics": metrics,
+                }],
+            }),
+        );
+
+        line.into()
+    }
+}
+
+/// The configuration or queue name from the event's ARN (...:matchmakingconfiguration/echo)
+pub fn resource_name(resources: &[String]) -> String {
+    resources
+        .first()
+        .map(|arn| arn.rsplit('/').next().unwrap_or(arn).to_owned())
+        .unwrap_or_else(|| "unknown".to_owned())
+}
+
+fn waited(tickets: &[Ticket], time: DateTime<Utc>) -> Vec<i64> {
+    tickets
+        .iter()
+        .map(|ticket| (time - ticket.start_time).num_milliseconds())
+        .collect()
+}
+
+/// Works out what a FlexMatch event adds to the metrics
+///
+/// Every finished ticket counts towards time in queue, succeeded ones towards time to match too
+pub fn event_metrics(event: &MatchmakingEvent) -> EventMetrics {
+    let mut metrics = EventMetrics {
+        configuration: resource_name(&event.resources),
+        ..EventMetrics::default()
+    };
+
+    let tickets = event.detail.tickets();
+    match &event.detail {
+        MatchmakingDetail::MatchmakingSucceeded(_) => {
+            metrics.time_to_match = waited(tickets, event.time);
+            metrics.time_in_queue = metrics.time_to_match.clone();
+            metrics.count(TICKETS_SUCCEEDED, tickets.len());
+        }
+        MatchmakingDetail::MatchmakingTimedOut(_) => {
+            metrics.time_in_queue = waited(tickets, event.time);
+            metrics.count(TICKETS_TIMED_OUT, tickets.len());
+        }
+        MatchmakingDetail::MatchmakingCancelled(_) => {
+            metrics.time_in_queue = waited(tickets, event.time);
+            metrics.count(TICKETS_CANCELLED, tickets.len());
+        }
+        MatchmakingDetail::MatchmakingFailed(_) => {
+            metrics.time_in_queue = waited(tickets, event.time);
+            metrics.count(TICKETS_FAILED, tickets.len());
+        }
+        // once per match, not per ticket
+        MatchmakingDetail::AcceptMatchCompleted(detail) => match detail.acceptance {
+            Acceptance::Accepted => metrics.count(MATCHES_ACCEPTED, 1),
+            Acceptance::Rejected => metrics.count(MATCHES_REJECTED, 1),
+            Acceptance::TimedOut => metrics.count(ACCEPTANCE_TIMED_OUT, 1),
+        },
+        MatchmakingDetail::MatchmakingSearching(_)
+        | MatchmakingDetail::PotentialMatchCreated(_)
+        | MatchmakingDetail::AcceptMatch(_) => {}
+    }
+
+    metrics
+}
+
+/// Count, total, min and max of a time in milliseconds
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TimeStats {
+    pub count: u64,
+    pub total_ms: i64,
+    pub min_ms: Option<i64>,
+    pub max_ms: Option<i64>,
+
+    /// derived from the others, kept up to date for whoever reads the file
+    #[serde(default)]
+    pub mean_ms: Option<i64>,
+}
+
+impl TimeStats {
+    fn add(&mut self, values: &[i64]) {
+        for &value in values {
+            self.count += 1;
+            self.total_ms += value;
+            self.min_ms = Some(self.min_ms.map_or(value, |min| min.min(value)));
+            self.max_ms = Some(self.max_ms.map_or(value, |max| max.max(value)));
+        }
+
+        if self.count > 0 {
+            self.mean_ms = Some(self.total_ms / self.count as i64);
+        }
+    }
+}
+
+/// Rates derived from the outcome counts, None until there's something to divide by
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rates {
+    /// of matches that needed acceptance
+    pub acceptance: Option<f64>,
+
+    /// of finished tickets
+    pub timeout: Option<f64>,
+    pub failure: Option<f64>,
+}
+
+fn rate(count: u64, total: u64) -> Option<f64> {
+    if total == 0 {
+        return None;
+    }
+
+    Some(count as f64 / total as f64)
+}
+
+/// Running totals for one matchmaking configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ConfigurationRollup {
+    pub time_to_match: TimeStats,
+    pub time_in_queue: TimeStats,
+    pub counts: BTreeMap<String, u64>,
+
+    #[serde(default)]
+    pub rates: Rates,
+}
+
+impl ConfigurationRollup {
+    fn get(&self, name: &str) -> u64 {
+        self.counts.get(name).copied().unwrap_or_default()
+    }
+
+    fn add(&mut self, metrics: &EventMetrics) {
+        self.time_to_match.add(&metrics.time_to_match);
+        self.time_in_queue.add(&metrics.time_in_queue);
+        for (name, count) in &metrics.counts {
+            *self.counts.entry((*name).to_owned()).or_default() += count;
+        }
+
+        let answered = self.get(MATCHES_ACCEPTED)
+            + self.get(MATCHES_REJECTED)
+            + self.get(ACCEPTANCE_TIMED_OUT);
+        let finished = self.get(TICKETS_SUCCEEDED)
+            + self.get(TICKETS_TIMED_OUT)
+            + self.get(TICKETS_CANCELLED)
+            + self.get(TICKETS_FAILED);
+
+        self.rates = Rates {
+            acceptance: rate(self.get(MATCHES_ACCEPTED), answered),
+            timeout: rate(self.get(TICKETS_TIMED_OUT), finished),
+            failure: rate(self.get(TICKETS_FAILED), finished),
+        };
+    }
+}
+
+/// Running totals per matchmaking configuration, as kept in the rollup file
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Rollup {
+    pub configurations: BTreeMap<String, ConfigurationRollup>,
+}
+
+impl Rollup {
+    pub fn add(&mut self, metrics: &EventMetrics) {
+        self.configurations
+            .entry(metrics.configuration.clone())
+            .or_default()
+            .add(metrics);
+    }
+}
+
+/// Turns FlexMatch events into metrics, logged in Embedded Metric Format and/or rolled up
+/// into a file
+pub struct Analytics {
+    emf: bool,
+    rollup_path: Option<PathBuf>,
+    rollup: Mutex<Rollup>,
+}
+
+impl Analytics {
+    /// Picks up the totals in an existing rollup file
+    pub async fn new(emf: bool, rollup_path: Option<PathBuf>) -> anyhow::Result<Self> {
+        let rollup = match &rollup_path {
+            Some(path) => match tokio::fs::read_to_string(path).await {
+                Ok(rollup) => serde_json::from_str(&rollup)
+                    .map_err(|err| anyhow!("Invalid rollup {}: {}", path.display(), err))?,
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Rollup::default(),
+                Err(err) => return Err(anyhow!("Failed to read {}: {}", path.display(), err)),
+            },
+            None => Rollup::default(),
+        };
+
+        Ok(Self {
+            emf,
+            rollup_path,
+            rollup: Mutex::new(rollup),
+        })
+    }
+
+    async fn write_rollup(&self, path: &Path, rollup: String) -> anyhow::Result<()> {
+        // write then rename, so readers never see a partial file
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, rollup)
+            .await
+            .map_err(|err| anyhow!("Failed to write {}: {}", tmp_path.display(), err))?;
+        tokio::fs::rename(&tmp_path, path)
+            .await
+            .map_err(|err| anyhow!("Failed to write {}: {}", path.display(), err))
+    }
+
+    /// Records the event's metrics, metrics failures don't fail the event
+    pub async fn record(&self, event: &MatchmakingEvent) {
+        let metrics = event_metrics(event);
+        if metrics.is_empty() {
+            return;
+        }
+
+        if self.emf {
+            // EMF lines have to be plain JSON, without the log prefix
+            println!("{}", metrics.emf(event.time));
+        }
+
+        let path = match &self.rollup_path {
+            Some(path) => path,
+            None => return,
+        };
+
+        let rollup = {
+            let mut rollup = self.rollup.lock().unwrap();
+            rollup.add(&metrics);
+            serde_json::to_string_pretty(&*rollup)
+        };
+
+        let res = match rollup {
+            Ok(rollup) => self.write_rollup(path, rollup).await,
+            Err(err) => Err(err.into()),
+        };
+        if let Err(err) = res {
+            warn!("failed to write the metrics rollup: {}", err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use echo_protocol::events::{self, GameLiftEvent};
+
+    use super::*;
+    use crate::tests::{fixture, temp_dir};
+
+    /// The FlexMatch events in the fixtures: a match accepted and succeeded, then tickets
+    /// timing out, being cancelled and failing
+    const MATCHMAKING_FIXTURES: [&str; 8] = [
+        "01-matchmaking-searching.json",
+        "02-potential-match-created.json",
+        "03-accept-match.json",
+        "04-accept-match-completed.json",
+        "06-matchmaking-succeeded.json",
+        "07-matchmaking-timed-out.json",
+        "08-matchmaking-cancelled.json",
+        "09-matchmaking-failed.json",
+    ];
+
+    fn matchmaking_event(name: &str) -> MatchmakingEvent {
+        match events::parse_event(&fixture(name)).unwrap() {
+            GameLiftEvent::Matchmaking(event) => event,
+            GameLiftEvent::Placement(_) => panic!("{} is a placement event", name),
+        }
+    }
+
+    fn time_stats(values: &[i64]) -> TimeStats {
+        let mut stats = TimeStats::default();
+        stats.add(values);
+        stats
+    }
+
+    /// The rollup of the fixtures, `times` over
+    fn expected_rollup(times: u64) -> ConfigurationRollup {
+        let time_to_match = [11_200, 11_200].repeat(times as usize);
+        let time_in_queue = [11_200, 11_200, 120_000, 30_000, 20_000].repeat(times as usize);
+
+        ConfigurationRollup {
+            time_to_match: time_stats(&time_to_match),
+            time_in_queue: time_stats(&time_in_queue),
+            counts: [
+                (MATCHES_ACCEPTED, 1),
+                (TICKETS_SUCCEEDED, 2),
+                (TICKETS_TIMED_OUT, 1),
+                (TICKETS_CANCELLED, 1),
+                (TICKETS_FAILED, 1),
+            ]
+            .into_iter()
+            .map(|(name, count)| (name.to_owned(), count * times))
+            .collect(),
+            rates: Rates {
+                acceptance: Some(1.0),
+                timeout: Some(0.2),
+                failure: Some(0.2),
+            },
+        }
+    }
+
+    #[test]
+    fn event_metrics_per_outcome() {
+        for name in &MATCHMAKING_FIXTURES[..3] {
+            assert!(
+                event_metrics(&matchmaking_event(name)).is_empty(),
+                "{}",
+                name
+            );
+        }
+
+        let accepted = event_metrics(&matchmaking_event("04-accept-match-completed.json"));
+        assert_eq!(
+            accepted,
+            EventMetrics {
+                configuration: "echo".to_owned(),
+                counts: [(MATCHES_ACCEPTED, 1)].into_iter().collect(),
+                ..EventMetrics::default()
+            }
+        );
+
+        let succeeded = event_metrics(&matchmaking_event("06-matchmaking-succeeded.json"));
+        assert_eq!(
+            succeeded,
+            EventMetrics {
+                configuration: "echo".to_owned(),
+                time_to_match: vec![11_200, 11_200],
+                time_in_queue: vec![11_200, 11_200],
+                counts: [(TICKETS_SUCCEEDED, 2)].into_iter().collect(),
+            }
+        );
+
+        let timed_out = event_metrics(&matchmaking_event("07-matchmaking-timed-out.json"));
+        assert_eq!(
+            timed_out,
+            EventMetrics {
+                configuration: "echo".to_owned(),
+                time_in_queue: vec![120_000],
+                counts: [(TICKETS_TIMED_OUT, 1)].into_iter().collect(),
+                ..EventMetrics::default()
+            }
+        );
+    }
+
+    #[test]
+    fn rolls_up_the_fixtures() {
+        let mut rollup = Rollup::default();
+        for name in MATCHMAKING_FIXTURES {
+            rollup.add(&event_metrics(&matchmaking_event(name)));
+        }
+
+        assert_eq!(rollup.configurations.len(), 1);
+        let echo = &rollup.configurations["echo"];
+        assert_eq!(*echo, expected_rollup(1));
+        assert_eq!(echo.time_in_queue.min_ms, Some(11_200));
+        assert_eq!(echo.time_in_queue.max_ms, Some(120_000));
+        assert_eq!(echo.time_in_queue.mean_ms, Some(38_480));
+    }
+
+    #[tokio::test]
+    async fn keeps_totals_in_the_rollup_file() {
+        let dir = temp_dir("rollup");
+        std::fs::create_dir_all(&dir).unwrap();
+        let path = dir.join("rollup.json");
+
+        let analytics = Analytics::new(false, Some(path.clone())).await.unwrap();
+        for name in MATCHMAKING_FIXTURES {
+            analytics.record(&matchmaking_event(name)).await;
+        }
+
+        let rollup: Rollup =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(rollup.configurations["echo"], expected_rollup(1));
+
+        // a cold start picks up where the last one left off
+        let analytics = Analytics::new(false, Some(path.clone())).await.unwrap();
+        for name in MATCHMAKING_FIXTURES {
+            analytics.record(&matchmaking_event(name)).await;
+        }
+
+        let rollup: Rollup =
+            serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+        assert_eq!(rollup.configurations["echo"], expected_rollup(2));
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
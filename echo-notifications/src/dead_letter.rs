@@ -0,0 +1,138 @@
+use std::path::PathBuf;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use tracing::{error, info};
+
+use crate::sources::Record;
+
+/// Where poison messages go instead of failing the invocation, log or file:<dir>
+#[derive(Default)]
+pub enum DeadLetterSink {
+    /// logs the message and why it failed
+    #[default]
+    Log,
+
+    /// writes {message id}.json with the message and why it failed
+    File(PathBuf),
+}
+
+impl FromStr for DeadLetterSink {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if s == "log" {
+            return Ok(Self::Log);
+        }
+
+        match s.split_once(':') {
+            Some(("file", dir)) if !dir.is_empty() => Ok(Self::File(dir.into())),
+            _ => Err(format!(
+                "invalid dead-letter sink '{}', expected log or file:<dir>",
+                s
+            )),
+        }
+    }
+}
+
+impl DeadLetterSink {
+    pub async fn send(&self, record: &Record, err: &anyhow::Error) -> anyhow::Result<()> {
+        let letter = serde_json::json!({
+            "error": format!("{:#}", err),
+            "message": record.raw,
+        });
+
+        match self {
+            Self::Log => {
+                error!("dead letter {}: {}", record.id, letter);
+            }
+            Self::File(dir) => {
+                let message_id = &record.id;
+                if message_id.is_empty()
+                    || message_id.contains(['/', '\\'])
+                    || message_id.starts_with('.')
+                {
+                    bail!("Invalid message id {}", message_id);
+                }
+
+                tokio::fs::create_dir_all(dir)
+                    .await
+                    .map_err(|err| anyhow!("Failed to create {}: {}", dir.display(), err))?;
+
+                let path = dir.join(format!("{}.json", message_id));
+                tokio::fs::write(&path, serde_json::to_string_pretty(&letter)?)
+                    .await
+                    .map_err(|err| anyhow!("Failed to write {}: {}", path.display(), err))?;
+
+                info!("dead letter {} written to {}", message_id, path.display());
+            }
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::tests::temp_dir;
+
+    fn record(id: &str) -> Record {
+        Record {
+            id: id.to_owned(),
+            message: "{}".to_owned(),
+            sns: None,
+            batch_item_id: None,
+            raw: json!({ "id": id, "detail": { "type": "MatchmakingRewound" } }),
+        }
+    }
+
+    #[test]
+    fn parses_sinks() {
+        assert!(matches!("log".parse(), Ok(DeadLetterSink::Log)));
+        assert!(matches!(
+            "file:/tmp/dead-letters".parse(),
+            Ok(DeadLetterSink::File(dir)) if dir == std::path::Path::new("/tmp/dead-letters")
+        ));
+
+        for sink in ["", "file:", "file", "sqs:dead-letters", "LOG"] {
+            assert!(sink.parse::<DeadLetterSink>().is_err(), "{}", sink);
+        }
+    }
+
+    #[tokio::test]
+    async fn file_sink_writes_the_raw_record() {
+        let dir = temp_dir("dead-letters");
+        let sink = DeadLetterSink::File(dir.clone());
+
+        sink.send(&record("message-1"), &anyhow!("unknown event type"))
+            .await
+            .unwrap();
+
+        let letter: Value =
+            serde_json::from_str(&std::fs::read_to_string(dir.join("message-1.json")).unwrap())
+                .unwrap();
+        assert_eq!(
+            letter,
+            json!({
+                "error": "unknown event type",
+                "message": record("message-1").raw,
+            })
+        );
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn file_sink_rejects_unsafe_ids() {
+        let dir = temp_dir("dead-letters");
+        let sink = DeadLetterSink::File(dir.clone());
+
+        for id in ["", "../message-1", ".message-1"] {
+            assert!(sink.send(&record(id), &anyhow!("poison")).await.is_err());
+        }
+        assert!(!dir.exists());
+    }
+}
@@ -0,0 +1,135 @@
+use std::collections::{HashSet, VecDeque};
+use std::sync::Mutex;
+
+use tracing::warn;
+
+use echo_protocol::store::MessageStore;
+
+/// How many message ids are remembered in memory, for redeliveries to a warm Lambda
+const RECENT_MESSAGES: usize = 10_000;
+
+#[derive(Default)]
+struct Recent {
+    ids: HashSet<String>,
+
+    /// oldest first, so the oldest id can be forgotten
+    order: VecDeque<String>,
+}
+
+/// Skips messages that have already been processed
+///
+/// Ids are remembered in memory while the Lambda stays warm, and in the message store
+/// (if there is one) across cold starts and other invocations. Checking and marking aren't
+/// atomic, so concurrent deliveries of the same message can both be processed
+#[derive(Default)]
+pub struct Dedup {
+    recent: Mutex<Recent>,
+    store: Option<Box<dyn MessageStore>>,
+}
+
+impl Dedup {
+    pub fn new(store: Box<dyn MessageStore>) -> Self {
+        Self {
+            recent: Mutex::default(),
+            store: Some(store),
+        }
+    }
+
+    pub async fn is_processed(&self, message_id: &str) -> bool {
+        if self.recent.lock().unwrap().ids.contains(message_id) {
+            return true;
+        }
+
+        match &self.store {
+            Some(store) => match store.is_processed(message_id.to_owned()).await {
+                Ok(processed) => processed,
+                // processing twice beats not processing at all
+                Err(err) => {
+                    warn!("failed to check message {}: {}", message_id, err);
+                    false
+                }
+            },
+            None => false,
+        }
+    }
+
+    pub async fn mark_processed(&self, message_id: &str) {
+        {
+            let mut recent = self.recent.lock().unwrap();
+            if recent.ids.insert(message_id.to_owned()) {
+                recent.order.push_back(message_id.to_owned());
+
+                if recent.order.len() > RECENT_MESSAGES {
+                    if let Some(oldest) = recent.order.pop_front() {
+                        recent.ids.remove(&oldest);
+                    }
+                }
+            }
+        }
+
+        if let Some(store) = &self.store {
+            if let Err(err) = store.mark_processed(message_id.to_owned()).await {
+                warn!("failed to mark message {} processed: {}", message_id, err);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use anyhow::anyhow;
+
+    use echo_protocol::store::{file::FileMessageStore, StoreFuture};
+
+    use super::*;
+    use crate::tests::temp_dir;
+
+    /// A message store that's always down
+    struct FailingStore;
+
+    impl MessageStore for FailingStore {
+        fn is_processed(&self, _message_id: String) -> StoreFuture<'_, bool> {
+            Box::pin(async { Err(anyhow!("store is down")) })
+        }
+
+        fn mark_processed(&self, _message_id: String) -> StoreFuture<'_, ()> {
+            Box::pin(async { Err(anyhow!("store is down")) })
+        }
+    }
+
+    #[tokio::test]
+    async fn skips_marked_messages() {
+        let dedup = Dedup::default();
+
+        assert!(!dedup.is_processed("message-1").await);
+        dedup.mark_processed("message-1").await;
+        assert!(dedup.is_processed("message-1").await);
+        assert!(!dedup.is_processed("message-2").await);
+    }
+
+    #[tokio::test]
+    async fn remembers_across_cold_starts() {
+        let dir = temp_dir("dedup");
+
+        let dedup = Dedup::new(Box::new(FileMessageStore::new(&dir)));
+        dedup.mark_processed("message-1").await;
+
+        let dedup = Dedup::new(Box::new(FileMessageStore::new(&dir)));
+        assert!(dedup.is_processed("message-1").await);
+        assert!(!dedup.is_processed("message-2").await);
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn store_errors_fail_open() {
+        let dedup = Dedup::new(Box::new(FailingStore));
+
+        // processing twice beats not processing at all
+        assert!(!dedup.is_processed("message-1").await);
+
+        // still remembered while warm
+        dedup.mark_processed("message-1").await;
+        assert!(dedup.is_processed("message-1").await);
+    }
+}
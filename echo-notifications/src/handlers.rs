@@ -0,0 +1,210 @@
+use tracing::{info, warn};
+
+use echo_protocol::events::{
+    AcceptMatch, AcceptMatchCompleted, Acceptance, EstimatedWait, GameSessionInfo,
+    MatchmakingDetail, MatchmakingEnded, MatchmakingSearching, MatchmakingSucceeded,
+    PlacementDetail, PlacementEnded, PlacementFulfilled, PotentialMatchCreated,
+    RuleEvaluationMetric, Ticket,
+};
+
+fn log_tickets(tickets: &[Ticket]) {
+    for ticket in tickets {
+        info!(
+            "  ticket {} (started {})",
+            ticket.ticket_id, ticket.start_time
+        );
+        for player in &ticket.players {
+            info!(
+                "    player {} team: {:?} accepted: {:?} player session: {:?}",
+                player.player_id, player.team, player.accepted, player.player_session_id
+            );
+        }
+    }
+}
+
+fn log_game_session(game_session_info: &GameSessionInfo) {
+    if let Some(game_session_arn) = &game_session_info.game_session_arn {
+        info!(
+            "  game session {} at {:?}:{:?}",
+            game_session_arn, game_session_info.ip_address, game_session_info.port
+        );
+    }
+
+    for player in &game_session_info.players {
+        info!(
+            "    player {} team: {:?} player session: {:?}",
+            player.player_id, player.team, player.player_session_id
+        );
+    }
+}
+
+fn log_rule_evaluation_metrics(metrics: &[RuleEvaluationMetric]) {
+    for metric in metrics {
+        info!(
+            "  rule {} passed {} failed {}",
+            metric.rule_name, metric.passed_count, metric.failed_count
+        );
+    }
+}
+
+fn matchmaking_searching(detail: &MatchmakingSearching) -> anyhow::Result<()> {
+    match &detail.estimated_wait_millis {
+        Some(EstimatedWait::Millis(millis)) => info!("Searching, estimated wait {}ms", millis),
+        Some(EstimatedWait::Unavailable(reason)) => {
+            info!("Searching, estimated wait unavailable ({})", reason)
+        }
+        None => info!("Searching"),
+    }
+
+    log_tickets(&detail.tickets);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn potential_match_created(detail: &PotentialMatchCreated) -> anyhow::Result<()> {
+    if detail.acceptance_required {
+        info!(
+            "Potential match {} created, players have {:?}s to accept",
+            detail.match_id, detail.acceptance_timeout
+        );
+    } else {
+        info!("Potential match {} created", detail.match_id);
+    }
+
+    log_tickets(&detail.tickets);
+    log_rule_evaluation_metrics(&detail.rule_evaluation_metrics);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn accept_match(detail: &AcceptMatch) -> anyhow::Result<()> {
+    info!("Players answered match {}", detail.match_id);
+
+    log_tickets(&detail.tickets);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn accept_match_completed(detail: &AcceptMatchCompleted) -> anyhow::Result<()> {
+    match detail.acceptance {
+        Acceptance::Accepted => info!("Match {} accepted", detail.match_id),
+        Acceptance::TimedOut => warn!("Match {} acceptance timed out", detail.match_id),
+        Acceptance::Rejected => warn!("Match {} rejected", detail.match_id),
+    }
+
+    log_tickets(&detail.tickets);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn matchmaking_succeeded(detail: &MatchmakingSucceeded) -> anyhow::Result<()> {
+    info!("Match {} succeeded", detail.match_id);
+
+    log_tickets(&detail.tickets);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn matchmaking_ended(outcome: &str, detail: &MatchmakingEnded) -> anyhow::Result<()> {
+    warn!(
+        "Matchmaking {} ({}): {}",
+        outcome,
+        detail.reason,
+        detail.message.as_deref().unwrap_or("no message")
+    );
+
+    if let Some(match_id) = &detail.match_id {
+        info!("  match {}", match_id);
+    }
+    if let Some(custom_event_data) = &detail.custom_event_data {
+        info!("  custom event data: {}", custom_event_data);
+    }
+
+    log_tickets(&detail.tickets);
+    log_rule_evaluation_metrics(&detail.rule_evaluation_metrics);
+    log_game_session(&detail.game_session_info);
+
+    Ok(())
+}
+
+fn matchmaking_timed_out(detail: &MatchmakingEnded) -> anyhow::Result<()> {
+    matchmaking_ended("timed out", detail)
+}
+
+fn matchmaking_cancelled(detail: &MatchmakingEnded) -> anyhow::Result<()> {
+    matchmaking_ended("cancelled", detail)
+}
+
+fn matchmaking_failed(detail: &MatchmakingEnded) -> anyhow::Result<()> {
+    matchmaking_ended("failed", detail)
+}
+
+fn placement_fulfilled(detail: &PlacementFulfilled) -> anyhow::Result<()> {
+    info!(
+        "Placement {} fulfilled: game session {} at {}:{} ({})",
+        detail.placement_id,
+        detail.game_session_arn,
+        detail.ip_address,
+        detail.port,
+        detail.dns_name.as_deref().unwrap_or("no dns name")
+    );
+
+    for player_session in &detail.placed_player_sessions {
+        info!(
+            "  player {} player session: {}",
+            player_session.player_id, player_session.player_session_id
+        );
+    }
+
+    Ok(())
+}
+
+fn placement_ended(outcome: &str, detail: &PlacementEnded) -> anyhow::Result<()> {
+    warn!(
+        "Placement {} {} (started {}, ended {:?})",
+        detail.placement_id, outcome, detail.start_time, detail.end_time
+    );
+
+    Ok(())
+}
+
+fn placement_cancelled(detail: &PlacementEnded) -> anyhow::Result<()> {
+    placement_ended("cancelled", detail)
+}
+
+fn placement_timed_out(detail: &PlacementEnded) -> anyhow::Result<()> {
+    placement_ended("timed out", detail)
+}
+
+fn placement_failed(detail: &PlacementEnded) -> anyhow::Result<()> {
+    placement_ended("failed", detail)
+}
+
+/// Hands the FlexMatch event to the handler for its type
+pub fn handle_matchmaking(detail: &MatchmakingDetail) -> anyhow::Result<()> {
+    match detail {
+        MatchmakingDetail::MatchmakingSearching(detail) => matchmaking_searching(detail),
+        MatchmakingDetail::PotentialMatchCreated(detail) => potential_match_created(detail),
+        MatchmakingDetail::AcceptMatch(detail) => accept_match(detail),
+        MatchmakingDetail::AcceptMatchCompleted(detail) => accept_match_completed(detail),
+        MatchmakingDetail::MatchmakingSucceeded(detail) => matchmaking_succeeded(detail),
+        MatchmakingDetail::MatchmakingTimedOut(detail) => matchmaking_timed_out(detail),
+        MatchmakingDetail::MatchmakingCancelled(detail) => matchmaking_cancelled(detail),
+        MatchmakingDetail::MatchmakingFailed(detail) => matchmaking_failed(detail),
+    }
+}
+
+/// Hands the queue placement event to the handler for its type
+pub fn handle_placement(detail: &PlacementDetail) -> anyhow::Result<()> {
+    match detail {
+        PlacementDetail::PlacementFulfilled(detail) => placement_fulfilled(detail),
+        PlacementDetail::PlacementCancelled(detail) => placement_cancelled(detail),
+        PlacementDetail::PlacementTimedOut(detail) => placement_timed_out(detail),
+        PlacementDetail::PlacementFailed(detail) => placement_failed(detail),
+    }
+}
@@ -1,49 +1,387 @@
 #![deny(warnings)]
 
-use anyhow::bail;
-use aws_lambda_events::event::sns::{SnsEvent, SnsRecord};
+mod analytics;
+mod dead_letter;
+mod dedup;
+mod handlers;
+mod relay;
+mod replay;
+mod sources;
+mod verify;
+mod webhooks;
+
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use argh::FromArgs;
 use lambda_runtime::{run, service_fn, Error, LambdaEvent};
-use tracing::{error, info};
+use tracing::{error, info, warn};
 
-async fn process_record(record: &SnsRecord) -> anyhow::Result<()> {
-    // for now just log the message we got
-    info!("read message:\n{}\n", record.sns.message);
+use echo_protocol::{
+    events,
+    store::{StoreConfig, TicketStore},
+    tickets::TicketState,
+};
 
-    Ok(())
+use sources::{Record, Source};
+
+/// Environment variable with the ticket store to keep ticket state in (file:<dir> or dynamodb:<table>)
+const TICKET_STORE_ENV: &str = "TICKET_STORE";
+
+/// Environment variable with the url of a notification relay to forward messages to
+const NOTIFY_RELAY_URL_ENV: &str = "NOTIFY_RELAY_URL";
+
+/// Environment variable with the store to remember processed messages in (file:<dir> or dynamodb:<table>)
+const MESSAGE_STORE_ENV: &str = "MESSAGE_STORE";
+
+/// Environment variable with where poison messages go (log or file:<dir>)
+const DEAD_LETTER_ENV: &str = "DEAD_LETTER";
+
+/// Environment variable that turns on SNS signature checks (true or 1)
+const VERIFY_SIGNATURES_ENV: &str = "VERIFY_SIGNATURES";
+
+/// Environment variable with extra signing cert url prefixes to trust, comma separated
+const SIGNING_CERT_URLS_ENV: &str = "SIGNING_CERT_URLS";
+
+/// Environment variable that turns on Embedded Metric Format metrics (true or 1)
+const METRICS_ENV: &str = "METRICS";
+
+/// Environment variable with a file to keep running metric totals in
+const METRICS_ROLLUP_ENV: &str = "METRICS_ROLLUP";
+
+/// Environment variable with the webhooks to send events to, a JSON list or a file containing one
+const WEBHOOKS_ENV: &str = "WEBHOOKS";
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Process GameLift notifications, as a Lambda unless given a subcommand
+struct Options {
+    #[argh(subcommand)]
+    mode: Option<Mode>,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+#[argh(subcommand)]
+enum Mode {
+    Replay(replay::ReplayCommand),
 }
 
-async fn process_records(records: impl AsRef<[SnsRecord]>) -> anyhow::Result<()> {
-    let mut error = false;
-    for record in records.as_ref().iter() {
-        if let Err(err) = process_record(record).await {
-            error!("failed to process record: {}", err);
-            error = true;
+/// Where processed events go besides the log
+#[derive(Default)]
+struct Sinks {
+    verifier: Option<verify::SignatureVerifier>,
+    store: Option<Box<dyn TicketStore>>,
+    relay: Option<relay::Relay>,
+    analytics: Option<analytics::Analytics>,
+    webhooks: Option<webhooks::Webhooks>,
+    dedup: dedup::Dedup,
+    dead_letter: dead_letter::DeadLetterSink,
+}
+
+/// Why a record couldn't be processed
+enum RecordError {
+    /// might work next time, eg. the ticket store was unavailable
+    Retryable(anyhow::Error),
+
+    /// will never work, eg. the message isn't a GameLift event
+    Poison(anyhow::Error),
+
+    /// not from SNS, so it's dropped without a trace beyond the log
+    Rejected(anyhow::Error),
+}
+
+async fn store_tickets(
+    store: &dyn TicketStore,
+    event: &events::MatchmakingEvent,
+) -> anyhow::Result<()> {
+    for state in TicketState::from_event(event) {
+        let ticket_id = state.ticket_id.clone();
+        let status = state.status;
+
+        if store.put(state).await? {
+            info!("stored ticket {} as {:?}", ticket_id, status);
+        } else {
+            warn!(
+                "ticket {} has a newer state, skipped {:?}",
+                ticket_id, status
+            );
         }
     }
 
-    if error {
-        bail!("One or more records failed");
+    Ok(())
+}
+
+async fn process_matchmaking(
+    event: &events::MatchmakingEvent,
+    sinks: &Sinks,
+) -> Result<(), RecordError> {
+    let ticket_ids = event
+        .detail
+        .tickets()
+        .iter()
+        .map(|ticket| ticket.ticket_id.as_str())
+        .collect::<Vec<_>>();
+    info!(
+        "{} event {} at {} in {} for {:?}, tickets: {:?}",
+        event.detail.event_type(),
+        event.id,
+        event.time,
+        event.region,
+        event.resources,
+        ticket_ids
+    );
+
+    handlers::handle_matchmaking(&event.detail).map_err(RecordError::Poison)?;
+
+    if let Some(store) = &sinks.store {
+        store_tickets(store.as_ref(), event)
+            .await
+            .map_err(RecordError::Retryable)?;
+    }
+
+    Ok(())
+}
+
+fn process_placement(event: &events::PlacementEvent) -> Result<(), RecordError> {
+    info!(
+        "{} event {} at {} in {} for {:?}, placement: {}",
+        event.detail.event_type(),
+        event.id,
+        event.time,
+        event.region,
+        event.resources,
+        event.detail.placement_id()
+    );
+
+    handlers::handle_placement(&event.detail).map_err(RecordError::Poison)
+}
+
+async fn process_record(record: &Record, sinks: &Sinks) -> Result<(), RecordError> {
+    // only SNS messages are signed, the other sources are authorized by IAM
+    if let (Some(verifier), Some(sns)) = (&sinks.verifier, &record.sns) {
+        verifier.verify(sns).await.map_err(|err| match err {
+            verify::VerifyError::Invalid(_) => RecordError::Rejected(anyhow!("{}", err)),
+            verify::VerifyError::Unavailable(_) => RecordError::Retryable(anyhow!("{}", err)),
+        })?;
+    }
+
+    let event = events::parse_event(&record.message).map_err(|err| {
+        RecordError::Poison(anyhow!(
+            "{} in message {}:\n{}\n",
+            err,
+            record.id,
+            record.message
+        ))
+    })?;
+
+    match &event {
+        events::GameLiftEvent::Matchmaking(event) => process_matchmaking(event, sinks).await?,
+        events::GameLiftEvent::Placement(event) => process_placement(event)?,
+    }
+
+    if let Some(relay) = &sinks.relay {
+        relay
+            .forward(&record.id, &record.message)
+            .await
+            .map_err(RecordError::Retryable)?;
+    }
+
+    // last, so retrying an earlier failure doesn't count the event or send the webhooks twice
+    if let (Some(analytics), events::GameLiftEvent::Matchmaking(event)) = (&sinks.analytics, &event)
+    {
+        analytics.record(event).await;
+    }
+    if let Some(webhooks) = &sinks.webhooks {
+        webhooks.send(&event).await;
     }
 
     Ok(())
 }
 
-async fn function_handler(event: LambdaEvent<SnsEvent>) -> Result<(), Error> {
-    if let Err(err) = process_records(&event.payload.records).await {
+/// Processes each record once, returning the ones that should be retried
+///
+/// Records already processed by an earlier delivery are skipped, poison records go to
+/// the dead-letter sink and records with bad signatures are dropped, rather than failing the batch
+async fn process_records<'a>(records: &'a [Record], sinks: &Sinks) -> Vec<&'a Record> {
+    let mut failed = Vec::new();
+    for record in records {
+        let message_id = &record.id;
+        if sinks.dedup.is_processed(message_id).await {
+            info!("skipped message {}, it was already processed", message_id);
+            continue;
+        }
+
+        match process_record(record, sinks).await {
+            Ok(()) => {}
+            Err(RecordError::Poison(err)) => {
+                error!("poison message {}: {}", message_id, err);
+
+                if let Err(err) = sinks.dead_letter.send(record, &err).await {
+                    error!("failed to dead-letter message {}: {}", message_id, err);
+                    failed.push(record);
+                    continue;
+                }
+            }
+            Err(RecordError::Retryable(err)) => {
+                error!("failed to process message {}: {}", message_id, err);
+                failed.push(record);
+                continue;
+            }
+            // forged messages could reuse a real message's id, so they aren't marked processed
+            Err(RecordError::Rejected(err)) => {
+                error!("rejected message {}: {}", message_id, err);
+                continue;
+            }
+        }
+
+        sinks.dedup.mark_processed(message_id).await;
+    }
+
+    failed
+}
+
+/// Accepts SNS and SQS batches, EventBridge events and events invoked by hand
+///
+/// Failed SQS messages are reported as batch item failures so only they are retried,
+/// the other sources fail the invocation
+async fn function_handler(
+    event: LambdaEvent<serde_json::Value>,
+    sinks: &Sinks,
+) -> Result<serde_json::Value, Error> {
+    let (source, records) = sources::records(event.payload).map_err(|err| {
+        error!("failed to read the invocation: {}", err);
+        err
+    })?;
+    info!("{} records from {:?}", records.len(), source);
+
+    let failed = process_records(&records, sinks).await;
+
+    if source == Source::Sqs {
+        let failures = failed
+            .iter()
+            .filter_map(|record| record.batch_item_id.as_deref())
+            .map(|id| serde_json::json!({ "itemIdentifier": id }))
+            .collect::<Vec<_>>();
+        return Ok(serde_json::json!({ "batchItemFailures": failures }));
+    }
+
+    if !failed.is_empty() {
+        let err = anyhow!("{} of {} records failed", failed.len(), records.len());
         error!("failed to process records: {}", err);
         return Err(err.into());
     }
 
-    Ok(())
+    Ok(serde_json::Value::Null)
+}
+
+/// Sets up the sinks from the environment variables
+async fn sinks_from_env() -> anyhow::Result<Sinks> {
+    let mut sinks = Sinks::default();
+
+    if matches!(
+        std::env::var(VERIFY_SIGNATURES_ENV).as_deref(),
+        Ok("true") | Ok("1")
+    ) {
+        let allowed_urls = std::env::var(SIGNING_CERT_URLS_ENV)
+            .unwrap_or_default()
+            .split(',')
+            .map(str::trim)
+            .filter(|url| !url.is_empty())
+            .map(str::to_owned)
+            .collect::<Vec<_>>();
+        info!(
+            "verifying message signatures, also trusting certs from {:?}",
+            allowed_urls
+        );
+
+        sinks.verifier = Some(verify::SignatureVerifier::new(allowed_urls));
+    }
+
+    if let Ok(config) = std::env::var(TICKET_STORE_ENV) {
+        let config = config.parse::<StoreConfig>().map_err(|err| anyhow!(err))?;
+        info!("storing tickets in {:?}", config);
+
+        sinks.store = Some(config.open_tickets().await?);
+    }
+
+    if let Ok(config) = std::env::var(MESSAGE_STORE_ENV) {
+        let config = config.parse::<StoreConfig>().map_err(|err| anyhow!(err))?;
+        info!("remembering processed messages in {:?}", config);
+
+        sinks.dedup = dedup::Dedup::new(config.open_messages().await?);
+    }
+
+    let emf = matches!(std::env::var(METRICS_ENV).as_deref(), Ok("true") | Ok("1"));
+    let rollup_path = std::env::var(METRICS_ROLLUP_ENV).ok().map(PathBuf::from);
+    if emf || rollup_path.is_some() {
+        info!(
+            "recording metrics, embedded metric format: {} rollup: {:?}",
+            emf, rollup_path
+        );
+
+        sinks.analytics = Some(analytics::Analytics::new(emf, rollup_path).await?);
+    }
+
+    if let Ok(config) = std::env::var(WEBHOOKS_ENV) {
+        sinks.webhooks = Some(webhooks::Webhooks::load(&config).await?);
+    }
+
+    if let Ok(sink) = std::env::var(DEAD_LETTER_ENV) {
+        sinks.dead_letter = sink
+            .parse::<dead_letter::DeadLetterSink>()
+            .map_err(|err| anyhow!(err))?;
+    }
+
+    if let Ok(url) = std::env::var(NOTIFY_RELAY_URL_ENV) {
+        info!("forwarding messages to relay {}", url);
+
+        sinks.relay = Some(relay::Relay::new(&url));
+    }
+
+    Ok(sinks)
 }
 
 #[tokio::main]
 async fn main() -> Result<(), Error> {
+    let options: Options = argh::from_env();
+
     tracing_subscriber::fmt()
         .with_max_level(tracing::Level::INFO)
         .with_target(false)
         .without_time()
         .init();
 
-    run(service_fn(function_handler)).await
+    let sinks = sinks_from_env().await?;
+
+    match options.mode {
+        Some(Mode::Replay(command)) => Ok(replay::replay(&command, &sinks).await?),
+        None => run(service_fn(|event| function_handler(event, &sinks))).await,
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::{Path, PathBuf};
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    /// A directory that doesn't exist yet, unique to the test
+    pub fn temp_dir(name: &str) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        std::env::temp_dir().join(format!(
+            "echo-notifications-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// The fixtures directory, the recorded events replay is tested with
+    pub fn fixtures() -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures")
+    }
+
+    /// A fixture's JSON
+    pub fn fixture(name: &str) -> String {
+        std::fs::read_to_string(fixtures().join(name)).unwrap()
+    }
 }
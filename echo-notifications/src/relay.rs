@@ -0,0 +1,163 @@
+use anyhow::bail;
+
+/// Forwards events to a notification relay (echo notify-relay), so clients waiting
+/// on tickets hear about updates without polling
+pub struct Relay {
+    client: reqwest::Client,
+    url: String,
+}
+
+impl Relay {
+    pub fn new(endpoint: &str) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            url: format!("{}/sns", endpoint.trim_end_matches('/')),
+        }
+    }
+
+    /// Sends the event in the SNS shape the relay accepts, whichever source it came from
+    pub async fn forward(&self, message_id: &str, message: &str) -> anyhow::Result<()> {
+        let notification = serde_json::json!({
+            "Type": "Notification",
+            "MessageId": message_id,
+            "Message": message,
+        });
+        let response = self
+            .client
+            .post(&self.url)
+            .json(&notification)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            bail!(
+                "Relay rejected message {} ({}): {}",
+                message_id,
+                status,
+                response.text().await?
+            );
+        }
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+
+    use hyper::{
+        body,
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use serde_json::Value;
+    use tokio::sync::mpsc;
+
+    use super::*;
+    use crate::{process_record, sources, tests::fixture, RecordError, Sinks};
+
+    /// Local stand-in for a relay, handing what's posted to it to a waiting receiver
+    fn start_relay(status: u16) -> (mpsc::UnboundedReceiver<(String, Value)>, String) {
+        let (sender, receiver) = mpsc::unbounded_channel();
+
+        let make_service = make_service_fn(move |_| {
+            let sender = sender.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let sender = sender.clone();
+                    async move {
+                        let path = request.uri().path().to_owned();
+                        let body = body::to_bytes(request.into_body()).await.unwrap();
+                        sender
+                            .send((path, serde_json::from_slice(&body).unwrap()))
+                            .ok();
+
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .body(Body::from("{}"))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+
+        let http = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let url = format!("http://127.0.0.1:{}/", http.local_addr().port());
+        tokio::spawn(http);
+
+        (receiver, url)
+    }
+
+    /// A url nothing is listening on
+    fn relay_down() -> String {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+        format!("http://127.0.0.1:{}", port)
+    }
+
+    fn record() -> sources::Record {
+        let payload = serde_json::from_str(&fixture("01-matchmaking-searching.json")).unwrap();
+        let (_, mut records) = sources::records(payload).unwrap();
+        records.remove(0)
+    }
+
+    #[tokio::test]
+    async fn forwards_in_the_sns_shape() {
+        let (mut waiting, url) = start_relay(200);
+        let record = record();
+
+        Relay::new(&url)
+            .forward(&record.id, &record.message)
+            .await
+            .unwrap();
+
+        let (path, notification) = waiting.recv().await.unwrap();
+        assert_eq!(path, "/sns");
+        assert_eq!(notification["Type"], "Notification");
+        assert_eq!(notification["MessageId"], record.id.as_str());
+        assert_eq!(notification["Message"], record.message.as_str());
+    }
+
+    #[tokio::test]
+    async fn fails_when_the_relay_rejects_or_is_down() {
+        let (mut waiting, url) = start_relay(400);
+        let err = Relay::new(&url)
+            .forward("message-1", "{}")
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "Relay rejected message message-1 (400 Bad Request): {}"
+        );
+        assert!(waiting.recv().await.is_some());
+
+        assert!(Relay::new(&relay_down())
+            .forward("message-1", "{}")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn relay_down_is_retryable() {
+        let sinks = Sinks {
+            relay: Some(Relay::new(&relay_down())),
+            ..Sinks::default()
+        };
+
+        match process_record(&record(), &sinks).await {
+            Err(RecordError::Retryable(err)) => assert!(err.is::<reqwest::Error>(), "{}", err),
+            Err(RecordError::Poison(err)) | Err(RecordError::Rejected(err)) => {
+                panic!("not retryable: {}", err)
+            }
+            Ok(()) => panic!("forwarded to a relay that's down"),
+        }
+    }
+}
@@ -0,0 +1,313 @@
+use std::path::PathBuf;
+
+use anyhow::{anyhow, bail};
+use argh::FromArgs;
+use chrono::{DateTime, Duration, SecondsFormat, Utc};
+use serde_json::Value;
+use tracing::info;
+
+use crate::sources::{self, Record};
+use crate::{process_records, Sinks};
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Feed recorded events through the notification pipeline, with the sinks configured in the environment
+#[argh(subcommand, name = "replay")]
+pub struct ReplayCommand {
+    /// JSON files, or directories of them: Lambda SNS or SQS events, GameLift events, lists of events or dead letters
+    #[argh(positional)]
+    pub paths: Vec<PathBuf>,
+
+    /// move event times so the latest is now, keeping the gaps between them
+    #[argh(switch)]
+    pub shift_times: bool,
+}
+
+/// The JSON files to replay, directories are read recursively in name order
+async fn json_files(paths: &[PathBuf]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    let mut pending = paths.iter().rev().cloned().collect::<Vec<_>>();
+    while let Some(path) = pending.pop() {
+        if !tokio::fs::metadata(&path)
+            .await
+            .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?
+            .is_dir()
+        {
+            files.push(path);
+            continue;
+        }
+
+        let mut entries = Vec::new();
+        let mut dir = tokio::fs::read_dir(&path)
+            .await
+            .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?;
+        while let Some(entry) = dir.next_entry().await? {
+            let is_dir = entry.file_type().await?.is_dir();
+            let entry = entry.path();
+            if is_dir || entry.extension().is_some_and(|ext| ext == "json") {
+                entries.push(entry);
+            }
+        }
+
+        entries.sort();
+        pending.extend(entries.into_iter().rev());
+    }
+
+    Ok(files)
+}
+
+/// Dead letters hold the record that failed, which is replayed as it arrived
+fn from_dead_letter(value: Value) -> Value {
+    match (value.get("error"), value.get("message")) {
+        (Some(_), Some(message)) => {
+            let message = message.clone();
+            if message.get("EventSource").is_some() || message.get("eventSource").is_some() {
+                serde_json::json!({ "Records": [message] })
+            } else {
+                message
+            }
+        }
+        _ => value,
+    }
+}
+
+fn event_time(record: &Record) -> Option<DateTime<Utc>> {
+    let event = serde_json::from_str::<Value>(&record.message).ok()?;
+    let time = event.get("time")?.as_str()?;
+
+    DateTime::parse_from_rfc3339(time)
+        .ok()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+fn shift(value: &mut Value, key: &str, offset: Duration) {
+    let time = value
+        .get(key)
+        .and_then(Value::as_str)
+        .and_then(|time| DateTime::parse_from_rfc3339(time).ok());
+
+    if let Some(time) = time {
+        let time = time.with_timezone(&Utc) + offset;
+        value[key] = time.to_rfc3339_opts(SecondsFormat::Millis, true).into();
+    }
+}
+
+/// Moves the event time, and the ticket and placement times in it
+///
+/// SNS signatures no longer match afterwards, so don't verify them when shifting
+fn shift_record(record: &mut Record, offset: Duration) {
+    let mut event = match serde_json::from_str::<Value>(&record.message) {
+        Ok(event) => event,
+        // poison, left for the pipeline to dead-letter
+        Err(_) => return,
+    };
+
+    shift(&mut event, "time", offset);
+    if let Some(detail) = event.get_mut("detail") {
+        shift(detail, "startTime", offset);
+        shift(detail, "endTime", offset);
+
+        if let Some(tickets) = detail.get_mut("tickets").and_then(Value::as_array_mut) {
+            for ticket in tickets {
+                shift(ticket, "startTime", offset);
+            }
+        }
+    }
+
+    record.message = event.to_string();
+    if let Some(sns) = &mut record.sns {
+        sns.message.message = record.message.clone();
+        sns.message.timestamp += offset;
+        sns.timestamp = sns
+            .message
+            .timestamp
+            .to_rfc3339_opts(SecondsFormat::Millis, true);
+    }
+}
+
+/// Reads the recorded events and processes them like the Lambda would, failing if any of
+/// them would have been retried
+pub async fn replay(command: &ReplayCommand, sinks: &Sinks) -> anyhow::Result<()> {
+    if command.paths.is_empty() {
+        bail!("Nothing to replay, give some JSON files or directories");
+    }
+
+    let files = json_files(&command.paths).await?;
+
+    let mut records = Vec::new();
+    for file in &files {
+        let json = tokio::fs::read_to_string(file)
+            .await
+            .map_err(|err| anyhow!("Failed to read {}: {}", file.display(), err))?;
+        let value = serde_json::from_str::<Value>(&json)
+            .map_err(|err| anyhow!("Invalid JSON in {}: {}", file.display(), err))?;
+
+        let (source, file_records) = sources::records(from_dead_letter(value))
+            .map_err(|err| anyhow!("{}: {}", file.display(), err))?;
+        info!(
+            "{} records from {:?} in {}",
+            file_records.len(),
+            source,
+            file.display()
+        );
+
+        records.extend(file_records);
+    }
+
+    if command.shift_times {
+        if let Some(latest) = records.iter().filter_map(event_time).max() {
+            let offset = Utc::now() - latest;
+            info!("shifting event times by {}s", offset.num_seconds());
+
+            for record in &mut records {
+                shift_record(record, offset);
+            }
+        }
+    }
+
+    let failed = process_records(&records, sinks).await;
+
+    info!(
+        "replayed {} records from {} files",
+        records.len(),
+        files.len()
+    );
+
+    if !failed.is_empty() {
+        let ids = failed
+            .iter()
+            .map(|record| record.id.as_str())
+            .collect::<Vec<_>>();
+        bail!(
+            "{} of {} records failed: {:?}",
+            failed.len(),
+            records.len(),
+            ids
+        );
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use echo_protocol::{
+        store::file::{FileMessageStore, FileTicketStore},
+        tickets::TicketStatus,
+    };
+
+    use super::*;
+    use crate::dead_letter::DeadLetterSink;
+    use crate::dedup::Dedup;
+    use crate::tests::{fixture, fixtures, temp_dir};
+
+    fn sinks(dir: &std::path::Path) -> Sinks {
+        Sinks {
+            store: Some(Box::new(FileTicketStore::new(dir.join("tickets")))),
+            dedup: Dedup::new(Box::new(FileMessageStore::new(dir.join("messages")))),
+            dead_letter: DeadLetterSink::File(dir.join("dead-letters")),
+            ..Sinks::default()
+        }
+    }
+
+    /// The ids the fixtures are deduplicated by: the event ids, or the SNS or SQS message ids
+    fn message_ids() -> Vec<String> {
+        let mut ids = (1..=10)
+            .map(|n| format!("e0000001-0000-4000-8000-{:012}", n))
+            .collect::<Vec<_>>();
+        ids.extend(
+            [
+                "a0000001-0000-4000-8000-000000000011",
+                "a0000001-0000-4000-8000-000000000012",
+                "b0000001-0000-4000-8000-000000000013",
+                // poison, but dead-lettered so not retried
+                "e0000001-0000-4000-8000-000000000014",
+            ]
+            .map(str::to_owned),
+        );
+        ids
+    }
+
+    async fn ticket_statuses(sinks: &Sinks) -> Vec<(&'static str, Option<TicketStatus>)> {
+        let store = sinks.store.as_ref().unwrap();
+        let mut statuses = Vec::new();
+        for ticket_id in [
+            "ticket-a1",
+            "ticket-a2",
+            "ticket-b",
+            "ticket-c",
+            "ticket-d",
+            "ticket-e",
+            "ticket-f",
+        ] {
+            let state = store.get(ticket_id.to_owned()).await.unwrap();
+            statuses.push((ticket_id, state.map(|state| state.status)));
+        }
+        statuses
+    }
+
+    fn dead_letters(dir: &std::path::Path) -> Vec<(String, Value)> {
+        let mut letters = std::fs::read_dir(dir.join("dead-letters"))
+            .unwrap()
+            .map(|entry| {
+                let path = entry.unwrap().path();
+                let letter =
+                    serde_json::from_str(&std::fs::read_to_string(&path).unwrap()).unwrap();
+                (
+                    path.file_name().unwrap().to_string_lossy().into_owned(),
+                    letter,
+                )
+            })
+            .collect::<Vec<_>>();
+        letters.sort_by(|a, b| a.0.cmp(&b.0));
+        letters
+    }
+
+    #[tokio::test]
+    async fn replays_the_fixtures() {
+        let dir = temp_dir("replay");
+        let command = ReplayCommand {
+            paths: vec![fixtures()],
+            shift_times: false,
+        };
+
+        let sinks = sinks(&dir);
+        replay(&command, &sinks).await.unwrap();
+
+        let expected_statuses = vec![
+            ("ticket-a1", Some(TicketStatus::Completed)),
+            ("ticket-a2", Some(TicketStatus::Completed)),
+            ("ticket-b", Some(TicketStatus::TimedOut)),
+            ("ticket-c", Some(TicketStatus::Cancelled)),
+            ("ticket-d", Some(TicketStatus::Failed)),
+            // searching over SNS, then cancelled over SQS
+            ("ticket-e", Some(TicketStatus::Cancelled)),
+            // searching, then an event type GameLift doesn't send
+            ("ticket-f", Some(TicketStatus::Searching)),
+        ];
+        assert_eq!(ticket_statuses(&sinks).await, expected_statuses);
+
+        let letters = dead_letters(&dir);
+        assert_eq!(letters.len(), 1);
+        let (name, letter) = &letters[0];
+        assert_eq!(name, "e0000001-0000-4000-8000-000000000014.json");
+        assert!(letter["error"].is_string());
+        assert_eq!(
+            letter["message"],
+            serde_json::from_str::<Value>(&fixture("13-unknown-event.json")).unwrap()
+        );
+
+        for id in message_ids() {
+            assert!(sinks.dedup.is_processed(&id).await, "{}", id);
+        }
+
+        // a cold start skips everything, the processed ids are in the message store
+        std::fs::remove_dir_all(dir.join("dead-letters")).unwrap();
+        let sinks = self::sinks(&dir);
+        replay(&command, &sinks).await.unwrap();
+
+        assert_eq!(ticket_statuses(&sinks).await, expected_statuses);
+        assert!(!dir.join("dead-letters").exists());
+
+        std::fs::remove_dir_all(dir).unwrap();
+    }
+}
@@ -0,0 +1,323 @@
+use anyhow::{anyhow, bail};
+use aws_lambda_events::event::{
+    sns::{SnsEvent, SnsMessage},
+    sqs::{SqsEvent, SqsMessage},
+};
+use chrono::SecondsFormat;
+use serde_json::Value;
+
+/// What invoked the Lambda
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Source {
+    /// a topic subscription, with a batch of SNS records
+    Sns,
+
+    /// an event source mapping, with a batch of SQS messages holding SNS messages or events
+    Sqs,
+
+    /// an EventBridge rule, or invoked by hand, with the GameLift event itself
+    EventBridge,
+
+    /// invoked by hand with a list of GameLift events
+    Direct,
+}
+
+/// An SNS message, with its timestamp as sent since that's what SNS signs
+pub struct SnsRecord {
+    pub message: SnsMessage,
+    pub timestamp: String,
+}
+
+impl SnsRecord {
+    /// `raw` is the SNS message JSON, the timestamp falls back to the parsed one formatted like SNS does
+    fn new(message: SnsMessage, raw: &Value) -> Self {
+        let timestamp = raw
+            .get("Timestamp")
+            .and_then(Value::as_str)
+            .map(str::to_owned)
+            .unwrap_or_else(|| {
+                message
+                    .timestamp
+                    .to_rfc3339_opts(SecondsFormat::Millis, true)
+            });
+
+        Self { message, timestamp }
+    }
+}
+
+/// A GameLift event message, however it arrived
+pub struct Record {
+    /// skips redeliveries, the SNS message id when there is one, or the SQS message or event id
+    pub id: String,
+
+    /// the GameLift event JSON
+    pub message: String,
+
+    /// the SNS message the event arrived in, for signature checks
+    pub sns: Option<SnsRecord>,
+
+    /// the SQS message id, to report the record failed without failing the batch
+    pub batch_item_id: Option<String>,
+
+    /// the record as it arrived, for the dead-letter sink
+    pub raw: Value,
+}
+
+impl Record {
+    fn from_sns(sns: SnsRecord, raw: Value) -> Self {
+        Self {
+            id: sns.message.message_id.clone(),
+            message: sns.message.message.clone(),
+            sns: Some(sns),
+            batch_item_id: None,
+            raw,
+        }
+    }
+
+    fn from_event(event: Value) -> anyhow::Result<Self> {
+        let id = event
+            .get("id")
+            .and_then(Value::as_str)
+            .ok_or_else(|| anyhow!("Event has no id: {}", event))?
+            .to_owned();
+
+        Ok(Self {
+            id,
+            message: event.to_string(),
+            sns: None,
+            batch_item_id: None,
+            raw: event,
+        })
+    }
+
+    /// SQS bodies are SNS messages, unless the subscription uses raw message delivery
+    fn from_sqs(message: SqsMessage, raw: Value) -> anyhow::Result<Self> {
+        let message_id = message
+            .message_id
+            .ok_or_else(|| anyhow!("SQS message has no messageId"))?;
+        let body = message.body.unwrap_or_default();
+
+        let mut record = match sns_body(&body) {
+            Some(sns) => Self::from_sns(sns, raw),
+            None => Self {
+                id: message_id.clone(),
+                message: body,
+                sns: None,
+                batch_item_id: None,
+                raw,
+            },
+        };
+        record.batch_item_id = Some(message_id);
+
+        Ok(record)
+    }
+}
+
+/// The SNS message in an SQS body, which names a couple of fields differently to the Lambda event
+fn sns_body(body: &str) -> Option<SnsRecord> {
+    let mut body = match serde_json::from_str::<Value>(body) {
+        Ok(Value::Object(body)) => body,
+        _ => return None,
+    };
+    if body.get("Type").and_then(Value::as_str) != Some("Notification") {
+        return None;
+    }
+
+    for (from, to) in [
+        ("SigningCertURL", "SigningCertUrl"),
+        ("UnsubscribeURL", "UnsubscribeUrl"),
+    ] {
+        if let Some(value) = body.remove(from) {
+            body.insert(to.to_owned(), value);
+        }
+    }
+    // left out when empty, but the Lambda event always has them
+    body.entry("Subject").or_insert(Value::Null);
+    body.entry("MessageAttributes")
+        .or_insert_with(|| Value::Object(Default::default()));
+
+    let body = Value::Object(body);
+    let message = serde_json::from_value(body.clone()).ok()?;
+
+    Some(SnsRecord::new(message, &body))
+}
+
+fn event_source(record: &Value) -> Option<&str> {
+    record
+        .get("EventSource")
+        .or_else(|| record.get("eventSource"))
+        .and_then(Value::as_str)
+}
+
+/// Works out what invoked the Lambda, and splits the payload into records
+pub fn records(payload: Value) -> anyhow::Result<(Source, Vec<Record>)> {
+    if let Some(raw) = payload.get("Records").and_then(Value::as_array).cloned() {
+        let source = raw.first().and_then(event_source).map(str::to_owned);
+        return match source.as_deref() {
+            Some("aws:sns") | None => {
+                let event = serde_json::from_value::<SnsEvent>(payload)
+                    .map_err(|err| anyhow!("Invalid SNS event: {}", err))?;
+
+                let records = event
+                    .records
+                    .into_iter()
+                    .zip(raw)
+                    .map(|(record, raw)| {
+                        let sns = SnsRecord::new(record.sns, &raw["Sns"]);
+                        Record::from_sns(sns, raw)
+                    })
+                    .collect();
+                Ok((Source::Sns, records))
+            }
+            Some("aws:sqs") => {
+                let event = serde_json::from_value::<SqsEvent>(payload)
+                    .map_err(|err| anyhow!("Invalid SQS event: {}", err))?;
+
+                let records = event
+                    .records
+                    .into_iter()
+                    .zip(raw)
+                    .map(|(message, raw)| Record::from_sqs(message, raw))
+                    .collect::<anyhow::Result<_>>()?;
+                Ok((Source::Sqs, records))
+            }
+            Some(source) => bail!("Unsupported event source {}", source),
+        };
+    }
+
+    match payload {
+        Value::Object(_) if payload.get("detail-type").is_some() => {
+            Ok((Source::EventBridge, vec![Record::from_event(payload)?]))
+        }
+        Value::Array(events) => {
+            let records = events
+                .into_iter()
+                .map(Record::from_event)
+                .collect::<anyhow::Result<_>>()?;
+            Ok((Source::Direct, records))
+        }
+        payload => bail!(
+            "Expected SNS or SQS records, an EventBridge event or a list of events, got {}",
+            payload
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::tests::fixture;
+
+    fn payload(name: &str) -> Value {
+        serde_json::from_str(&fixture(name)).unwrap()
+    }
+
+    fn error(payload: Value) -> String {
+        match records(payload) {
+            Ok((source, _)) => panic!("split as {:?}", source),
+            Err(err) => err.to_string(),
+        }
+    }
+
+    #[test]
+    fn splits_sqs_messages() {
+        let (source, records) = records(payload("12-sqs-event.json")).unwrap();
+        assert_eq!(source, Source::Sqs);
+        assert_eq!(records.len(), 2);
+
+        // an SNS envelope without a Subject, ids come from SNS
+        let enveloped = &records[0];
+        assert_eq!(enveloped.id, "a0000001-0000-4000-8000-000000000012");
+        assert_eq!(
+            enveloped.batch_item_id.as_deref(),
+            Some("b0000001-0000-4000-8000-000000000012")
+        );
+        let sns = enveloped.sns.as_ref().unwrap();
+        assert_eq!(sns.timestamp, "2022-06-01T12:03:30.000Z");
+        assert_eq!(sns.message.subject, None);
+        assert_eq!(
+            sns.message.signing_cert_url,
+            "https://sns.us-west-2.amazonaws.com/SimpleNotificationService-EXAMPLE.pem"
+        );
+        assert!(enveloped.message.contains("MatchmakingCancelled"));
+
+        // raw message delivery, the body is the event and the id the SQS message's
+        let raw = &records[1];
+        assert_eq!(raw.id, "b0000001-0000-4000-8000-000000000013");
+        assert_eq!(raw.batch_item_id.as_deref(), Some(raw.id.as_str()));
+        assert!(raw.sns.is_none());
+        assert_eq!(
+            raw.message,
+            payload("12-sqs-event.json")["Records"][1]["body"]
+        );
+        assert_eq!(raw.raw["messageId"], raw.id.as_str());
+    }
+
+    #[test]
+    fn splits_sns_records() {
+        let (source, records) = records(payload("11-sns-event.json")).unwrap();
+        assert_eq!(source, Source::Sns);
+        assert_eq!(records.len(), 1);
+        assert!(records[0].sns.is_some());
+        assert!(records[0].batch_item_id.is_none());
+        assert_eq!(records[0].message, records[0].raw["Sns"]["Message"]);
+    }
+
+    #[test]
+    fn takes_eventbridge_events() {
+        let event = payload("05-placement-fulfilled.json");
+        let (source, records) = records(event.clone()).unwrap();
+        assert_eq!(source, Source::EventBridge);
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].id, "e0000001-0000-4000-8000-000000000005");
+        assert!(records[0].sns.is_none());
+        assert!(records[0].batch_item_id.is_none());
+        assert_eq!(records[0].raw, event);
+        assert_eq!(
+            serde_json::from_str::<Value>(&records[0].message).unwrap(),
+            event
+        );
+    }
+
+    #[test]
+    fn takes_lists_of_events() {
+        let events = json!([
+            payload("01-matchmaking-searching.json"),
+            payload("05-placement-fulfilled.json"),
+        ]);
+        let (source, records) = records(events).unwrap();
+        assert_eq!(source, Source::Direct);
+        let ids = records
+            .iter()
+            .map(|record| record.id.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(
+            ids,
+            [
+                "e0000001-0000-4000-8000-000000000001",
+                "e0000001-0000-4000-8000-000000000005"
+            ]
+        );
+
+        assert!(
+            error(json!([{"detail-type": "GameLift Matchmaking Event"}]))
+                .starts_with("Event has no id")
+        );
+    }
+
+    #[test]
+    fn rejects_other_payloads() {
+        assert_eq!(
+            error(json!({
+                "Records": [{"eventSource": "aws:kinesis", "kinesis": {}}],
+            })),
+            "Unsupported event source aws:kinesis"
+        );
+
+        for payload in [json!({"hello": "world"}), json!("event"), json!(null)] {
+            assert!(error(payload).starts_with("Expected SNS or SQS records"));
+        }
+    }
+}
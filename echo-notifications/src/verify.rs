@@ -0,0 +1,430 @@
+use std::collections::HashMap;
+use std::fmt;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use chrono::Utc;
+use rsa::{pkcs8::DecodePublicKey, Hash, PaddingScheme, PublicKey, RsaPublicKey};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use tracing::{info, warn};
+
+use crate::sources::SnsRecord;
+
+/// Why a message couldn't be verified
+pub enum VerifyError {
+    /// the message isn't from SNS, or was changed on the way
+    Invalid(String),
+
+    /// the signing cert couldn't be fetched, so the message might be fine
+    Unavailable(anyhow::Error),
+}
+
+impl fmt::Display for VerifyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Invalid(reason) => write!(f, "invalid signature: {}", reason),
+            Self::Unavailable(err) => write!(f, "signing cert unavailable: {}", err),
+        }
+    }
+}
+
+struct SigningCert {
+    key: RsaPublicKey,
+
+    /// seconds since the epoch
+    not_after: i64,
+}
+
+/// Checks SNS message signatures against the topic's signing cert
+///
+/// Certs are only fetched from SNS (https://sns.<region>.amazonaws.com[.cn]/...pem) or the
+/// allowed url prefixes, and are cached until they expire
+pub struct SignatureVerifier {
+    client: reqwest::Client,
+    allowed_urls: Vec<String>,
+    certs: Mutex<HashMap<String, SigningCert>>,
+    rejected: AtomicU64,
+}
+
+/// Whether the cert url is one SNS would sign with
+fn is_sns_cert_url(url: &reqwest::Url) -> bool {
+    let host = match url.host_str() {
+        Some(host) => host,
+        None => return false,
+    };
+
+    let region = host
+        .strip_suffix(".amazonaws.com")
+        .or_else(|| host.strip_suffix(".amazonaws.com.cn"))
+        .and_then(|host| host.strip_prefix("sns."));
+
+    url.scheme() == "https"
+        && url.port().is_none()
+        && url.path().ends_with(".pem")
+        && region.is_some_and(|region| {
+            !region.is_empty()
+                && region
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '-')
+        })
+}
+
+/// The string SNS signs, https://docs.aws.amazon.com/sns/latest/dg/sns-verify-signature-of-message.html
+fn string_to_sign(sns: &SnsRecord) -> Result<String, VerifyError> {
+    let message = &sns.message;
+    if message.sns_message_type != "Notification" {
+        return Err(VerifyError::Invalid(format!(
+            "unsupported message type {}",
+            message.sns_message_type
+        )));
+    }
+
+    let fields = [
+        ("Message", Some(message.message.as_str())),
+        ("MessageId", Some(message.message_id.as_str())),
+        ("Subject", message.subject.as_deref()),
+        ("Timestamp", Some(sns.timestamp.as_str())),
+        ("TopicArn", Some(message.topic_arn.as_str())),
+        ("Type", Some(message.sns_message_type.as_str())),
+    ];
+
+    let mut s = String::new();
+    for (name, value) in fields {
+        if let Some(value) = value {
+            s.push_str(name);
+            s.push('\n');
+            s.push_str(value);
+            s.push('\n');
+        }
+    }
+
+    Ok(s)
+}
+
+impl SignatureVerifier {
+    /// `allowed_urls` are extra cert url prefixes, eg. a local stand-in for SNS
+    pub fn new(allowed_urls: Vec<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            allowed_urls,
+            certs: Mutex::default(),
+            rejected: AtomicU64::new(0),
+        }
+    }
+
+    fn check_cert_url(&self, cert_url: &str) -> Result<(), VerifyError> {
+        if self
+            .allowed_urls
+            .iter()
+            .any(|prefix| cert_url.starts_with(prefix.as_str()))
+        {
+            return Ok(());
+        }
+
+        let url = reqwest::Url::parse(cert_url)
+            .map_err(|err| VerifyError::Invalid(format!("bad cert url {}: {}", cert_url, err)))?;
+        if !is_sns_cert_url(&url) {
+            return Err(VerifyError::Invalid(format!(
+                "cert url {} isn't allowed",
+                cert_url
+            )));
+        }
+
+        Ok(())
+    }
+
+    async fn fetch_cert(&self, cert_url: &str) -> Result<SigningCert, VerifyError> {
+        let pem = self
+            .client
+            .get(cert_url)
+            .send()
+            .await
+            .and_then(|response| response.error_for_status())
+            .map_err(|err| VerifyError::Unavailable(anyhow!("{}: {}", cert_url, err)))?
+            .bytes()
+            .await
+            .map_err(|err| VerifyError::Unavailable(anyhow!("{}: {}", cert_url, err)))?;
+
+        let invalid = |err: String| VerifyError::Invalid(format!("bad cert {}: {}", cert_url, err));
+
+        let (_, pem) =
+            x509_parser::pem::parse_x509_pem(&pem).map_err(|err| invalid(err.to_string()))?;
+        let cert = pem.parse_x509().map_err(|err| invalid(err.to_string()))?;
+        let key = RsaPublicKey::from_public_key_der(cert.public_key().raw)
+            .map_err(|err| invalid(err.to_string()))?;
+
+        info!("fetched signing cert {}", cert_url);
+
+        Ok(SigningCert {
+            key,
+            not_after: cert.validity().not_after.timestamp(),
+        })
+    }
+
+    async fn check_signature(&self, sns: &SnsRecord) -> Result<(), VerifyError> {
+        let message = &sns.message;
+        let (hashed, hash) = match message.signature_version.as_str() {
+            "1" => (
+                Sha1::digest(string_to_sign(sns)?.as_bytes()).to_vec(),
+                Hash::SHA1,
+            ),
+            "2" => (
+                Sha256::digest(string_to_sign(sns)?.as_bytes()).to_vec(),
+                Hash::SHA2_256,
+            ),
+            version => {
+                return Err(VerifyError::Invalid(format!(
+                    "unsupported signature version {}",
+                    version
+                )))
+            }
+        };
+
+        let signature = base64::decode(&message.signature)
+            .map_err(|err| VerifyError::Invalid(format!("bad signature encoding: {}", err)))?;
+
+        let cert_url = &message.signing_cert_url;
+        self.check_cert_url(cert_url)?;
+
+        let now = Utc::now().timestamp();
+        let cached = self
+            .certs
+            .lock()
+            .unwrap()
+            .get(cert_url)
+            .filter(|cert| cert.not_after > now)
+            .map(|cert| cert.key.clone());
+        let key = match cached {
+            Some(key) => key,
+            None => {
+                let cert = self.fetch_cert(cert_url).await?;
+                if cert.not_after <= now {
+                    return Err(VerifyError::Invalid(format!(
+                        "cert {} has expired",
+                        cert_url
+                    )));
+                }
+
+                let key = cert.key.clone();
+                self.certs.lock().unwrap().insert(cert_url.clone(), cert);
+                key
+            }
+        };
+
+        key.verify(
+            PaddingScheme::new_pkcs1v15_sign(Some(hash)),
+            &hashed,
+            &signature,
+        )
+        .map_err(|_| VerifyError::Invalid("signature doesn't match".to_owned()))
+    }
+
+    /// Checks the message came from SNS unchanged, counting the ones that didn't
+    pub async fn verify(&self, sns: &SnsRecord) -> Result<(), VerifyError> {
+        let res = self.check_signature(sns).await;
+
+        if let Err(VerifyError::Invalid(reason)) = &res {
+            let rejected = self.rejected.fetch_add(1, Ordering::Relaxed) + 1;
+            warn!(
+                "rejected message {} ({} rejected so far): {}",
+                sns.message.message_id, rejected, reason
+            );
+        }
+
+        res
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    use hyper::{
+        service::{make_service_fn, service_fn},
+        Body, Response, Server,
+    };
+    use rsa::{pkcs8::DecodePrivateKey, RsaPrivateKey};
+    use serde_json::{json, Value};
+
+    use super::*;
+    use crate::sources;
+
+    const CERT: &str = include_str!("../testdata/signing-cert.pem");
+    const KEY: &str = include_str!("../testdata/signing-key.pem");
+
+    /// Serves the test signing cert, counting the fetches
+    fn serve_cert() -> (String, Arc<AtomicUsize>) {
+        let fetches = Arc::new(AtomicUsize::new(0));
+
+        let make_service = make_service_fn({
+            let fetches = fetches.clone();
+            move |_| {
+                let fetches = fetches.clone();
+                async move {
+                    Ok::<_, Infallible>(service_fn(move |_| {
+                        fetches.fetch_add(1, Ordering::Relaxed);
+                        async { Ok::<_, Infallible>(Response::new(Body::from(CERT))) }
+                    }))
+                }
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let port = server.local_addr().port();
+        tokio::spawn(server);
+
+        (format!("http://127.0.0.1:{}/", port), fetches)
+    }
+
+    /// An SNS Lambda event, signed with the test key
+    ///
+    /// The timestamp has no millis, so it only verifies when the timestamp is signed as sent
+    fn signed_event(cert_url: &str, version: &str, message: &str) -> Value {
+        let mut sns = json!({
+            "Type": "Notification",
+            "MessageId": "95df01b4-ee98-5cb9-9903-4c221d41eb5e",
+            "TopicArn": "arn:aws:sns:us-east-1:123456789012:echo-matchmaking",
+            "Subject": null,
+            "Message": message,
+            "Timestamp": "2022-05-17T10:30:00Z",
+            "SignatureVersion": version,
+            "Signature": "",
+            "SigningCertUrl": format!("{}cert.pem", cert_url),
+            "UnsubscribeUrl": "https://sns.us-east-1.amazonaws.com/?Action=Unsubscribe",
+            "MessageAttributes": {}
+        });
+
+        let string_to_sign = [
+            "Message",
+            message,
+            "MessageId",
+            "95df01b4-ee98-5cb9-9903-4c221d41eb5e",
+            "Timestamp",
+            "2022-05-17T10:30:00Z",
+            "TopicArn",
+            "arn:aws:sns:us-east-1:123456789012:echo-matchmaking",
+            "Type",
+            "Notification",
+            "",
+        ]
+        .join("\n");
+        let (hashed, hash) = match version {
+            "1" => (Sha1::digest(&string_to_sign).to_vec(), Hash::SHA1),
+            _ => (Sha256::digest(&string_to_sign).to_vec(), Hash::SHA2_256),
+        };
+        let key = RsaPrivateKey::from_pkcs8_pem(KEY).unwrap();
+        let signature = key
+            .sign(PaddingScheme::new_pkcs1v15_sign(Some(hash)), &hashed)
+            .unwrap();
+        sns["Signature"] = json!(base64::encode(signature));
+
+        json!({
+            "Records": [{
+                "EventSource": "aws:sns",
+                "EventVersion": "1.0",
+                "EventSubscriptionArn": "arn:aws:sns:us-east-1:123456789012:echo-matchmaking:2bcfbf39",
+                "Sns": sns
+            }]
+        })
+    }
+
+    fn sns_record(event: Value) -> SnsRecord {
+        let (_, mut records) = sources::records(event).unwrap();
+        records.remove(0).sns.unwrap()
+    }
+
+    #[tokio::test]
+    async fn verifies_both_signature_versions() {
+        let (cert_url, fetches) = serve_cert();
+        let verifier = SignatureVerifier::new(vec![cert_url.clone()]);
+
+        for version in ["1", "2"] {
+            let sns = sns_record(signed_event(&cert_url, version, "{}"));
+            assert!(
+                verifier.verify(&sns).await.is_ok(),
+                "signature version {}",
+                version
+            );
+        }
+
+        // the cert is cached
+        assert_eq!(fetches.load(Ordering::Relaxed), 1);
+        assert_eq!(verifier.rejected.load(Ordering::Relaxed), 0);
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_messages() {
+        let (cert_url, _) = serve_cert();
+        let verifier = SignatureVerifier::new(vec![cert_url.clone()]);
+
+        let mut event = signed_event(
+            &cert_url,
+            "2",
+            r#"{"detail":{"type":"MatchmakingSearching"}}"#,
+        );
+        event["Records"][0]["Sns"]["Message"] =
+            json!(r#"{"detail":{"type":"MatchmakingSucceeded"}}"#);
+        let sns = sns_record(event);
+
+        assert!(matches!(
+            verifier.verify(&sns).await,
+            Err(VerifyError::Invalid(_))
+        ));
+        assert_eq!(verifier.rejected.load(Ordering::Relaxed), 1);
+    }
+
+    #[tokio::test]
+    async fn rejects_disallowed_cert_urls() {
+        let (cert_url, fetches) = serve_cert();
+        let verifier = SignatureVerifier::new(Vec::new());
+
+        let sns = sns_record(signed_event(&cert_url, "2", "{}"));
+
+        assert!(matches!(
+            verifier.verify(&sns).await,
+            Err(VerifyError::Invalid(_))
+        ));
+        assert_eq!(fetches.load(Ordering::Relaxed), 0);
+        assert_eq!(verifier.rejected.load(Ordering::Relaxed), 1);
+    }
+
+    #[test]
+    fn only_sns_cert_urls_are_allowed() {
+        for (url, allowed) in [
+            (
+                "https://sns.us-east-1.amazonaws.com/SimpleNotificationService-1.pem",
+                true,
+            ),
+            (
+                "https://sns.cn-north-1.amazonaws.com.cn/SimpleNotificationService-1.pem",
+                true,
+            ),
+            (
+                "http://sns.us-east-1.amazonaws.com/SimpleNotificationService-1.pem",
+                false,
+            ),
+            (
+                "https://sns.us-east-1.amazonaws.com:8443/SimpleNotificationService-1.pem",
+                false,
+            ),
+            (
+                "https://sns.us-east-1.amazonaws.com.example.com/cert.pem",
+                false,
+            ),
+            (
+                "https://sns.amazonaws.com/SimpleNotificationService-1.pem",
+                false,
+            ),
+            ("https://sns.us-east-1.amazonaws.com/cert.txt", false),
+            ("https://example.com/SimpleNotificationService-1.pem", false),
+        ] {
+            let url = reqwest::Url::parse(url).unwrap();
+            assert_eq!(is_sns_cert_url(&url), allowed, "{}", url);
+        }
+    }
+}
@@ -0,0 +1,736 @@
+use std::collections::BTreeMap;
+use std::time::Duration;
+
+use anyhow::{anyhow, bail};
+use chrono::{DateTime, Utc};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use tracing::{info, warn};
+
+use echo_protocol::events::{GameLiftEvent, MatchmakingDetail, PlacementDetail, Ticket};
+
+use crate::analytics::resource_name;
+
+/// Event types a webhook can be sent for
+const EVENT_TYPES: [&str; 12] = [
+    "MatchmakingSearching",
+    "PotentialMatchCreated",
+    "AcceptMatch",
+    "AcceptMatchCompleted",
+    "MatchmakingSucceeded",
+    "MatchmakingTimedOut",
+    "MatchmakingCancelled",
+    "MatchmakingFailed",
+    "PlacementFulfilled",
+    "PlacementCancelled",
+    "PlacementTimedOut",
+    "PlacementFailed",
+];
+
+/// Fields templates can use, as {name}, fields an event doesn't have are empty
+const FIELDS: [&str; 13] = [
+    "event",
+    "id",
+    "time",
+    "region",
+    "resource",
+    "tickets",
+    "players",
+    "matchId",
+    "placementId",
+    "reason",
+    "message",
+    "waitSeconds",
+    "endpoint",
+];
+
+const MATCHMAKING_TEMPLATE: &str =
+    "{event} in {resource} for tickets {tickets} after {waitSeconds}s {reason}";
+const PLACEMENT_TEMPLATE: &str =
+    "{event} in {resource} for placement {placementId} after {waitSeconds}s";
+
+const DEFAULT_RETRIES: u32 = 3;
+const INITIAL_BACKOFF: Duration = Duration::from_millis(500);
+const MAX_BACKOFF: Duration = Duration::from_secs(8);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Header with the seconds since the epoch the request was signed at
+const TIMESTAMP_HEADER: &str = "X-Echo-Timestamp";
+
+/// Header with sha256=<hex HMAC-SHA256 of "{timestamp}.{body}">
+const SIGNATURE_HEADER: &str = "X-Echo-Signature";
+
+/// What the request body looks like
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// {"content": text}
+    Discord,
+
+    /// {"text": text}
+    Slack,
+
+    /// {"event", "id", "text", "fields"}
+    Generic,
+}
+
+/// A webhook destination, as configured in WEBHOOKS
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase", deny_unknown_fields)]
+struct WebhookConfig {
+    url: String,
+
+    /// used in the log instead of the url, which usually contains a token
+    #[serde(default)]
+    name: Option<String>,
+
+    format: WebhookFormat,
+
+    /// signs requests with HMAC-SHA256 when set
+    #[serde(default)]
+    secret: Option<String>,
+
+    /// event types to send
+    events: Vec<String>,
+
+    /// only sends when a ticket or placement waited at least this long
+    #[serde(default)]
+    min_wait_seconds: Option<i64>,
+
+    #[serde(default)]
+    template: Option<String>,
+
+    #[serde(default = "default_retries")]
+    retries: u32,
+}
+
+fn default_retries() -> u32 {
+    DEFAULT_RETRIES
+}
+
+/// Replaces each {name} in the template with the field, text without a closing } is kept as is
+fn render(template: &str, mut field: impl FnMut(&str) -> String) -> String {
+    let mut text = String::new();
+    let mut rest = template;
+    while let Some(start) = rest.find('{') {
+        let end = match rest[start..].find('}') {
+            Some(end) => start + end,
+            None => break,
+        };
+
+        text.push_str(&rest[..start]);
+        text.push_str(&field(&rest[start + 1..end]));
+        rest = &rest[end + 1..];
+    }
+    text.push_str(rest);
+
+    text
+}
+
+fn longest_wait(tickets: &[Ticket], time: DateTime<Utc>) -> Option<i64> {
+    tickets
+        .iter()
+        .map(|ticket| (time - ticket.start_time).num_seconds())
+        .max()
+}
+
+/// The fields of an event templates can use, see FIELDS
+fn event_fields(event: &GameLiftEvent) -> BTreeMap<&'static str, String> {
+    let mut fields = BTreeMap::new();
+    fields.insert("event", event.event_type().to_owned());
+    fields.insert("id", event.id().to_owned());
+
+    match event {
+        GameLiftEvent::Matchmaking(event) => {
+            fields.insert("time", event.time.to_rfc3339());
+            fields.insert("region", event.region.clone());
+            fields.insert("resource", resource_name(&event.resources));
+
+            let tickets = event.detail.tickets();
+            let ticket_ids = tickets
+                .iter()
+                .map(|ticket| ticket.ticket_id.as_str())
+                .collect::<Vec<_>>();
+            let player_ids = tickets
+                .iter()
+                .flat_map(|ticket| &ticket.players)
+                .map(|player| player.player_id.as_str())
+                .collect::<Vec<_>>();
+            fields.insert("tickets", ticket_ids.join(", "));
+            fields.insert("players", player_ids.join(", "));
+            if let Some(wait) = longest_wait(tickets, event.time) {
+                fields.insert("waitSeconds", wait.to_string());
+            }
+
+            match &event.detail {
+                MatchmakingDetail::MatchmakingSearching(_) => {}
+                MatchmakingDetail::PotentialMatchCreated(detail) => {
+                    fields.insert("matchId", detail.match_id.clone());
+                }
+                MatchmakingDetail::AcceptMatch(detail) => {
+                    fields.insert("matchId", detail.match_id.clone());
+                }
+                MatchmakingDetail::AcceptMatchCompleted(detail) => {
+                    fields.insert("matchId", detail.match_id.clone());
+                    fields.insert("reason", format!("{:?}", detail.acceptance));
+                }
+                MatchmakingDetail::MatchmakingSucceeded(detail) => {
+                    fields.insert("matchId", detail.match_id.clone());
+
+                    let info = &detail.game_session_info;
+                    if let (Some(ip_address), Some(port)) = (&info.ip_address, info.port) {
+                        fields.insert("endpoint", format!("{}:{}", ip_address, port));
+                    }
+                }
+                MatchmakingDetail::MatchmakingTimedOut(detail)
+                | MatchmakingDetail::MatchmakingCancelled(detail)
+                | MatchmakingDetail::MatchmakingFailed(detail) => {
+                    if let Some(match_id) = &detail.match_id {
+                        fields.insert("matchId", match_id.clone());
+                    }
+                    fields.insert("reason", detail.reason.clone());
+                    if let Some(message) = &detail.message {
+                        fields.insert("message", message.clone());
+                    }
+                }
+            }
+        }
+        GameLiftEvent::Placement(event) => {
+            fields.insert("time", event.time.to_rfc3339());
+            fields.insert("region", event.region.clone());
+            fields.insert("resource", resource_name(&event.resources));
+            fields.insert("placementId", event.detail.placement_id().to_owned());
+
+            let (start_time, end_time) = match &event.detail {
+                PlacementDetail::PlacementFulfilled(detail) => {
+                    fields.insert("endpoint", format!("{}:{}", detail.ip_address, detail.port));
+                    let player_ids = detail
+                        .placed_player_sessions
+                        .iter()
+                        .map(|session| session.player_id.as_str())
+                        .collect::<Vec<_>>();
+                    fields.insert("players", player_ids.join(", "));
+
+                    (detail.start_time, detail.end_time)
+                }
+                PlacementDetail::PlacementCancelled(detail)
+                | PlacementDetail::PlacementTimedOut(detail)
+                | PlacementDetail::PlacementFailed(detail) => (detail.start_time, detail.end_time),
+            };
+            let wait = end_time.unwrap_or(event.time) - start_time;
+            fields.insert("waitSeconds", wait.num_seconds().to_string());
+        }
+    }
+
+    fields
+}
+
+/// Why an attempt failed
+enum AttemptError {
+    /// eg. the destination is down or rate limiting, with how long it asked us to wait
+    Retryable(anyhow::Error, Option<Duration>),
+
+    /// eg. the destination rejected the body, sending it again won't help
+    Fatal(anyhow::Error),
+}
+
+struct Webhook {
+    name: String,
+    url: reqwest::Url,
+    format: WebhookFormat,
+    secret: Option<String>,
+    events: Vec<String>,
+    min_wait_seconds: Option<i64>,
+    template: Option<String>,
+    retries: u32,
+}
+
+impl Webhook {
+    fn new(config: WebhookConfig) -> anyhow::Result<Self> {
+        let url = reqwest::Url::parse(&config.url)
+            .map_err(|err| anyhow!("Invalid webhook url {}: {}", config.url, err))?;
+        let name = config
+            .name
+            .or_else(|| url.host_str().map(str::to_owned))
+            .unwrap_or_else(|| config.url.clone());
+
+        if config.events.is_empty() {
+            bail!("Webhook {} has no events", name);
+        }
+        for event_type in &config.events {
+            if !EVENT_TYPES.contains(&event_type.as_str()) {
+                bail!("Webhook {} has unknown event type {}", name, event_type);
+            }
+        }
+
+        if let Some(template) = &config.template {
+            let mut unknown = Vec::new();
+            render(template, |field| {
+                if !FIELDS.contains(&field) {
+                    unknown.push(field.to_owned());
+                }
+                String::new()
+            });
+            if !unknown.is_empty() {
+                bail!(
+                    "Webhook {} template has unknown fields {:?}, expected {:?}",
+                    name,
+                    unknown,
+                    FIELDS
+                );
+            }
+        }
+
+        Ok(Self {
+            name,
+            url,
+            format: config.format,
+            secret: config.secret,
+            events: config.events,
+            min_wait_seconds: config.min_wait_seconds,
+            template: config.template,
+            retries: config.retries,
+        })
+    }
+
+    fn wants(&self, event: &GameLiftEvent, fields: &BTreeMap<&'static str, String>) -> bool {
+        if !self
+            .events
+            .iter()
+            .any(|event_type| event_type == event.event_type())
+        {
+            return false;
+        }
+
+        match self.min_wait_seconds {
+            Some(min_wait) => fields
+                .get("waitSeconds")
+                .and_then(|wait| wait.parse::<i64>().ok())
+                .is_some_and(|wait| wait >= min_wait),
+            None => true,
+        }
+    }
+
+    fn body(&self, event: &GameLiftEvent, fields: &BTreeMap<&'static str, String>) -> Vec<u8> {
+        let template = match (&self.template, event) {
+            (Some(template), _) => template.as_str(),
+            (None, GameLiftEvent::Matchmaking(_)) => MATCHMAKING_TEMPLATE,
+            (None, GameLiftEvent::Placement(_)) => PLACEMENT_TEMPLATE,
+        };
+        let text = render(template, |field| {
+            fields.get(field).cloned().unwrap_or_default()
+        });
+        let text = text.trim();
+
+        let body = match self.format {
+            WebhookFormat::Discord => serde_json::json!({ "content": text }),
+            WebhookFormat::Slack => serde_json::json!({ "text": text }),
+            WebhookFormat::Generic => serde_json::json!({
+                "event": event.event_type(),
+                "id": event.id(),
+                "text": text,
+                "fields": fields,
+            }),
+        };
+
+        body.to_string().into_bytes()
+    }
+
+    fn signature(secret: &str, timestamp: &str, body: &[u8]) -> String {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(secret.as_bytes()).expect("HMAC takes any key size");
+        mac.update(timestamp.as_bytes());
+        mac.update(b".");
+        mac.update(body);
+
+        let signature = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        format!("sha256={}", signature)
+    }
+
+    async fn attempt(&self, client: &reqwest::Client, body: &[u8]) -> Result<(), AttemptError> {
+        let mut request = client
+            .post(self.url.clone())
+            .header(reqwest::header::CONTENT_TYPE, "application/json");
+
+        // signed on each attempt, so the timestamp stays fresh
+        if let Some(secret) = &self.secret {
+            let timestamp = Utc::now().timestamp().to_string();
+            request = request
+                .header(SIGNATURE_HEADER, Self::signature(secret, &timestamp, body))
+                .header(TIMESTAMP_HEADER, timestamp);
+        }
+
+        let response = request
+            .body(body.to_vec())
+            .send()
+            .await
+            .map_err(|err| AttemptError::Retryable(err.into(), None))?;
+
+        let status = response.status();
+        if status.is_success() {
+            return Ok(());
+        }
+
+        let retry_after = response
+            .headers()
+            .get(reqwest::header::RETRY_AFTER)
+            .and_then(|value| value.to_str().ok())
+            .and_then(|value| value.parse::<u64>().ok())
+            .map(Duration::from_secs);
+        let err = anyhow!("{}: {}", status, response.text().await.unwrap_or_default());
+
+        if status.is_server_error() || status == reqwest::StatusCode::TOO_MANY_REQUESTS {
+            Err(AttemptError::Retryable(err, retry_after))
+        } else {
+            Err(AttemptError::Fatal(err))
+        }
+    }
+
+    /// Posts the body, retrying with exponential backoff while the destination might recover
+    async fn post(&self, client: &reqwest::Client, body: &[u8]) -> anyhow::Result<()> {
+        let mut backoff = INITIAL_BACKOFF;
+        let mut attempts = 0;
+        loop {
+            attempts += 1;
+            let (err, retry_after) = match self.attempt(client, body).await {
+                Ok(()) => return Ok(()),
+                Err(AttemptError::Fatal(err)) => return Err(err),
+                Err(AttemptError::Retryable(err, retry_after)) => (err, retry_after),
+            };
+
+            if attempts > self.retries {
+                bail!("{} after {} attempts", err, attempts);
+            }
+
+            let delay = retry_after.map_or(backoff, |retry_after| retry_after.min(MAX_BACKOFF));
+            warn!(
+                "webhook {} failed, retrying in {:?}: {}",
+                self.name, delay, err
+            );
+
+            tokio::time::sleep(delay).await;
+            backoff = (backoff * 2).min(MAX_BACKOFF);
+        }
+    }
+}
+
+/// Sends chosen events on to Discord, Slack or any HTTP endpoint
+///
+/// Configured with a JSON list of destinations, eg.
+/// [{"url": "https://discord.com/api/webhooks/...", "format": "discord", "events": ["MatchmakingTimedOut"], "minWaitSeconds": 120}]
+pub struct Webhooks {
+    client: reqwest::Client,
+    hooks: Vec<Webhook>,
+}
+
+impl Webhooks {
+    /// `config` is the JSON list itself, or a file containing it
+    pub async fn load(config: &str) -> anyhow::Result<Self> {
+        let json = if config.trim_start().starts_with('[') {
+            config.to_owned()
+        } else {
+            tokio::fs::read_to_string(config)
+                .await
+                .map_err(|err| anyhow!("Failed to read webhooks {}: {}", config, err))?
+        };
+
+        let configs = serde_json::from_str::<Vec<WebhookConfig>>(&json)
+            .map_err(|err| anyhow!("Invalid webhooks: {}", err))?;
+        let hooks = configs
+            .into_iter()
+            .map(Webhook::new)
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        for hook in &hooks {
+            info!(
+                "webhook {} ({:?}) for {:?}",
+                hook.name, hook.format, hook.events
+            );
+        }
+
+        Ok(Self {
+            client: reqwest::Client::builder().timeout(TIMEOUT).build()?,
+            hooks,
+        })
+    }
+
+    /// Sends the event to each webhook that wants it, one at a time
+    ///
+    /// Failures are only logged, failing the record would resend it to every webhook
+    pub async fn send(&self, event: &GameLiftEvent) {
+        let fields = event_fields(event);
+
+        for hook in &self.hooks {
+            if !hook.wants(event, &fields) {
+                continue;
+            }
+
+            match hook.post(&self.client, &hook.body(event, &fields)).await {
+                Ok(()) => info!(
+                    "sent {} {} to webhook {}",
+                    event.event_type(),
+                    event.id(),
+                    hook.name
+                ),
+                Err(err) => warn!(
+                    "failed to send {} {} to webhook {}: {}",
+                    event.event_type(),
+                    event.id(),
+                    hook.name,
+                    err
+                ),
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::VecDeque;
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+    use std::time::Instant;
+
+    use hyper::{
+        body,
+        service::{make_service_fn, service_fn},
+        Body, HeaderMap, Request, Response, Server,
+    };
+    use serde_json::{json, Value};
+
+    use echo_protocol::events;
+
+    use super::*;
+    use crate::tests::fixture;
+
+    struct Received {
+        path: String,
+        headers: HeaderMap,
+        body: Vec<u8>,
+        at: Instant,
+    }
+
+    /// Local stand-in for a webhook destination, recording the requests made to it
+    ///
+    /// Answers with the queued statuses and Retry-After headers, then 204s
+    struct WebhookServer {
+        requests: Mutex<Vec<Received>>,
+        responses: Mutex<VecDeque<(u16, Option<&'static str>)>>,
+    }
+
+    impl WebhookServer {
+        fn start(responses: &[(u16, Option<&'static str>)]) -> (Arc<Self>, String) {
+            let server = Arc::new(Self {
+                requests: Mutex::new(Vec::new()),
+                responses: Mutex::new(responses.iter().copied().collect()),
+            });
+
+            let make_service = make_service_fn({
+                let server = server.clone();
+                move |_| {
+                    let server = server.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |request| {
+                            server.clone().handle_request(request)
+                        }))
+                    }
+                }
+            });
+
+            let http = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+            let url = format!("http://127.0.0.1:{}", http.local_addr().port());
+            tokio::spawn(http);
+
+            (server, url)
+        }
+
+        async fn handle_request(
+            self: Arc<Self>,
+            request: Request<Body>,
+        ) -> Result<Response<Body>, Infallible> {
+            let at = Instant::now();
+            let path = request.uri().path().to_owned();
+            let headers = request.headers().clone();
+            let body = body::to_bytes(request.into_body())
+                .await
+                .unwrap_or_default()
+                .to_vec();
+            self.requests.lock().unwrap().push(Received {
+                path,
+                headers,
+                body,
+                at,
+            });
+
+            let (status, retry_after) = self
+                .responses
+                .lock()
+                .unwrap()
+                .pop_front()
+                .unwrap_or((204, None));
+            let mut response = Response::builder().status(status);
+            if let Some(retry_after) = retry_after {
+                response = response.header("Retry-After", retry_after);
+            }
+
+            Ok(response.body(Body::empty()).unwrap())
+        }
+
+        fn bodies(&self) -> Vec<(String, Value)> {
+            self.requests
+                .lock()
+                .unwrap()
+                .iter()
+                .map(|request| {
+                    (
+                        request.path.clone(),
+                        serde_json::from_slice(&request.body).unwrap(),
+                    )
+                })
+                .collect()
+        }
+
+        /// Time between each request and the one before it
+        fn gaps(&self) -> Vec<Duration> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .windows(2)
+                .map(|pair| pair[1].at - pair[0].at)
+                .collect()
+        }
+    }
+
+    fn webhook(config: Value) -> Webhook {
+        Webhook::new(serde_json::from_value(config).unwrap()).unwrap()
+    }
+
+    fn event(name: &str) -> GameLiftEvent {
+        events::parse_event(&fixture(name)).unwrap()
+    }
+
+    #[tokio::test]
+    async fn retries_with_backoff() {
+        let (server, url) = WebhookServer::start(&[(429, Some("1")), (503, None), (500, None)]);
+        let hook =
+            webhook(json!({ "url": url, "format": "slack", "events": ["MatchmakingFailed"] }));
+
+        hook.post(&reqwest::Client::new(), b"{}").await.unwrap();
+
+        // Retry-After wins over the first 500ms backoff, then the backoff doubles
+        let gaps = server.gaps();
+        assert_eq!(gaps.len(), 3);
+        assert!(gaps[0] >= Duration::from_secs(1), "{:?}", gaps);
+        assert!(gaps[1] >= Duration::from_secs(1), "{:?}", gaps);
+        assert!(gaps[2] >= Duration::from_secs(2), "{:?}", gaps);
+    }
+
+    #[tokio::test]
+    async fn gives_up_after_retries_or_fatal_errors() {
+        let client = reqwest::Client::new();
+
+        let (server, url) = WebhookServer::start(&[(503, None), (503, None), (503, None)]);
+        let hook = webhook(json!({
+            "url": url,
+            "format": "discord",
+            "events": ["MatchmakingFailed"],
+            "retries": 1,
+        }));
+        assert!(hook.post(&client, b"{}").await.is_err());
+        assert_eq!(server.gaps().len(), 1);
+
+        let (server, url) = WebhookServer::start(&[(400, None)]);
+        let hook =
+            webhook(json!({ "url": url, "format": "discord", "events": ["MatchmakingFailed"] }));
+        assert!(hook.post(&client, b"{}").await.is_err());
+        assert!(server.gaps().is_empty());
+    }
+
+    #[tokio::test]
+    async fn signs_requests() {
+        let (server, url) = WebhookServer::start(&[]);
+        let hook = webhook(json!({
+            "url": url,
+            "format": "generic",
+            "events": ["MatchmakingTimedOut"],
+            "secret": "hush",
+        }));
+
+        let event = event("07-matchmaking-timed-out.json");
+        let body = hook.body(&event, &event_fields(&event));
+        hook.post(&reqwest::Client::new(), &body).await.unwrap();
+
+        let requests = server.requests.lock().unwrap();
+        let request = &requests[0];
+        assert_eq!(request.body, body);
+
+        let timestamp = request.headers[TIMESTAMP_HEADER].to_str().unwrap();
+        let signed_at = timestamp.parse::<i64>().unwrap();
+        assert!((Utc::now().timestamp() - signed_at).abs() < 60);
+
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"hush").unwrap();
+        mac.update(format!("{}.", timestamp).as_bytes());
+        mac.update(&body);
+        let expected = mac
+            .finalize()
+            .into_bytes()
+            .iter()
+            .map(|b| format!("{:02x}", b))
+            .collect::<String>();
+        assert_eq!(
+            request.headers[SIGNATURE_HEADER].to_str().unwrap(),
+            format!("sha256={}", expected)
+        );
+    }
+
+    #[tokio::test]
+    async fn sends_discord_and_slack_payloads() {
+        let (server, url) = WebhookServer::start(&[]);
+        let config = json!([
+            {
+                "url": format!("{}/discord", url),
+                "format": "discord",
+                "events": ["MatchmakingTimedOut", "MatchmakingFailed"],
+                "minWaitSeconds": 60,
+            },
+            {
+                "url": format!("{}/slack", url),
+                "format": "slack",
+                "events": ["MatchmakingSucceeded"],
+                "template": "{event} {matchId} at {endpoint}",
+            },
+        ]);
+        let webhooks = Webhooks::load(&config.to_string()).await.unwrap();
+
+        for name in [
+            "06-matchmaking-succeeded.json",
+            "07-matchmaking-timed-out.json",
+            // not waited long enough
+            "09-matchmaking-failed.json",
+            // not wanted
+            "08-matchmaking-cancelled.json",
+        ] {
+            webhooks.send(&event(name)).await;
+        }
+
+        assert_eq!(
+            server.bodies(),
+            [
+                (
+                    "/slack".to_owned(),
+                    json!({ "text": "MatchmakingSucceeded match-a at 192.0.2.10:7777" })
+                ),
+                (
+                    "/discord".to_owned(),
+                    json!({ "content": "MatchmakingTimedOut in echo for tickets ticket-b after 120s TimedOut" })
+                ),
+            ]
+        );
+    }
+}
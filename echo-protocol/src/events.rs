@@ -0,0 +1,645 @@
+use std::fmt;
+
+use chrono::{DateTime, Utc};
+use serde::{
+    de::{self, DeserializeOwned},
+    Deserialize, Deserializer,
+};
+
+/// A player on a matchmaking ticket
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketPlayer {
+    pub player_id: String,
+
+    #[serde(default)]
+    pub team: Option<String>,
+
+    /// whether the player accepted the potential match, for AcceptMatch events
+    #[serde(default)]
+    pub accepted: Option<bool>,
+
+    #[serde(default)]
+    pub player_session_id: Option<String>,
+}
+
+/// A matchmaking ticket included in an event
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Ticket {
+    pub ticket_id: String,
+    pub start_time: DateTime<Utc>,
+
+    #[serde(default)]
+    pub players: Vec<TicketPlayer>,
+}
+
+/// A player in the (potential) game session
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSessionPlayer {
+    pub player_id: String,
+
+    #[serde(default)]
+    pub team: Option<String>,
+
+    #[serde(default)]
+    pub player_session_id: Option<String>,
+}
+
+/// The game session a match is for, connection details are only set once it succeeds
+#[derive(Debug, Clone, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct GameSessionInfo {
+    #[serde(default)]
+    pub game_session_arn: Option<String>,
+
+    #[serde(default)]
+    pub ip_address: Option<String>,
+
+    #[serde(default)]
+    pub port: Option<u16>,
+
+    #[serde(default)]
+    pub players: Vec<GameSessionPlayer>,
+}
+
+/// How many times a rule passed or failed while searching
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct RuleEvaluationMetric {
+    pub rule_name: String,
+    pub passed_count: u64,
+    pub failed_count: u64,
+}
+
+/// Estimated wait, GameLift sends NOT_AVAILABLE until it has enough data
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(untagged)]
+pub enum EstimatedWait {
+    Millis(u64),
+    Unavailable(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchmakingSearching {
+    pub tickets: Vec<Ticket>,
+
+    #[serde(default)]
+    pub estimated_wait_millis: Option<EstimatedWait>,
+
+    #[serde(default)]
+    pub game_session_info: GameSessionInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PotentialMatchCreated {
+    pub tickets: Vec<Ticket>,
+    pub match_id: String,
+    pub acceptance_required: bool,
+
+    /// seconds players have to accept, when acceptance is required
+    #[serde(default)]
+    pub acceptance_timeout: Option<u64>,
+
+    #[serde(default)]
+    pub rule_evaluation_metrics: Vec<RuleEvaluationMetric>,
+
+    #[serde(default)]
+    pub game_session_info: GameSessionInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptMatch {
+    pub tickets: Vec<Ticket>,
+    pub match_id: String,
+
+    #[serde(default)]
+    pub game_session_info: GameSessionInfo,
+}
+
+/// How match acceptance ended
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum Acceptance {
+    Accepted,
+    TimedOut,
+    Rejected,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct AcceptMatchCompleted {
+    pub tickets: Vec<Ticket>,
+    pub match_id: String,
+    pub acceptance: Acceptance,
+
+    #[serde(default)]
+    pub game_session_info: GameSessionInfo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchmakingSucceeded {
+    pub tickets: Vec<Ticket>,
+    pub match_id: String,
+    pub game_session_info: GameSessionInfo,
+}
+
+/// A ticket that ended without a match, shared by the timed out, cancelled and failed events
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchmakingEnded {
+    pub tickets: Vec<Ticket>,
+    pub reason: String,
+
+    #[serde(default)]
+    pub message: Option<String>,
+
+    #[serde(default)]
+    pub match_id: Option<String>,
+
+    #[serde(default)]
+    pub rule_evaluation_metrics: Vec<RuleEvaluationMetric>,
+
+    #[serde(default)]
+    pub custom_event_data: Option<String>,
+
+    #[serde(default)]
+    pub game_session_info: GameSessionInfo,
+}
+
+/// The event specific part of a FlexMatch event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum MatchmakingDetail {
+    MatchmakingSearching(MatchmakingSearching),
+    PotentialMatchCreated(PotentialMatchCreated),
+    AcceptMatch(AcceptMatch),
+    AcceptMatchCompleted(AcceptMatchCompleted),
+    MatchmakingSucceeded(MatchmakingSucceeded),
+    MatchmakingTimedOut(MatchmakingEnded),
+    MatchmakingCancelled(MatchmakingEnded),
+    MatchmakingFailed(MatchmakingEnded),
+}
+
+impl MatchmakingDetail {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            MatchmakingDetail::MatchmakingSearching(_) => "MatchmakingSearching",
+            MatchmakingDetail::PotentialMatchCreated(_) => "PotentialMatchCreated",
+            MatchmakingDetail::AcceptMatch(_) => "AcceptMatch",
+            MatchmakingDetail::AcceptMatchCompleted(_) => "AcceptMatchCompleted",
+            MatchmakingDetail::MatchmakingSucceeded(_) => "MatchmakingSucceeded",
+            MatchmakingDetail::MatchmakingTimedOut(_) => "MatchmakingTimedOut",
+            MatchmakingDetail::MatchmakingCancelled(_) => "MatchmakingCancelled",
+            MatchmakingDetail::MatchmakingFailed(_) => "MatchmakingFailed",
+        }
+    }
+
+    pub fn tickets(&self) -> &[Ticket] {
+        match self {
+            MatchmakingDetail::MatchmakingSearching(detail) => &detail.tickets,
+            MatchmakingDetail::PotentialMatchCreated(detail) => &detail.tickets,
+            MatchmakingDetail::AcceptMatch(detail) => &detail.tickets,
+            MatchmakingDetail::AcceptMatchCompleted(detail) => &detail.tickets,
+            MatchmakingDetail::MatchmakingSucceeded(detail) => &detail.tickets,
+            MatchmakingDetail::MatchmakingTimedOut(detail)
+            | MatchmakingDetail::MatchmakingCancelled(detail)
+            | MatchmakingDetail::MatchmakingFailed(detail) => &detail.tickets,
+        }
+    }
+}
+
+/// A FlexMatch event, in the EventBridge format GameLift publishes to SNS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchmakingEvent {
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub region: String,
+
+    /// the matchmaking configuration ARN
+    pub resources: Vec<String>,
+
+    pub detail: MatchmakingDetail,
+}
+
+/// GameLift sends ports as strings in placement events
+fn port<'de, D>(deserializer: D) -> Result<u16, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Port {
+        Number(u16),
+        String(String),
+    }
+
+    match Port::deserialize(deserializer)? {
+        Port::Number(port) => Ok(port),
+        Port::String(port) => port.parse().map_err(de::Error::custom),
+    }
+}
+
+/// A player session created for a player on a placement
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacedPlayerSession {
+    pub player_id: String,
+    pub player_session_id: String,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementFulfilled {
+    pub placement_id: String,
+    pub game_session_arn: String,
+    pub ip_address: String,
+
+    #[serde(deserialize_with = "port")]
+    pub port: u16,
+
+    #[serde(default)]
+    pub dns_name: Option<String>,
+
+    #[serde(default)]
+    pub game_session_region: Option<String>,
+
+    pub start_time: DateTime<Utc>,
+
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+
+    #[serde(default)]
+    pub placed_player_sessions: Vec<PlacedPlayerSession>,
+}
+
+/// A placement that ended without a game session, shared by the cancelled, timed out and
+/// failed events
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementEnded {
+    pub placement_id: String,
+    pub start_time: DateTime<Utc>,
+
+    #[serde(default)]
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// The event specific part of a queue placement event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlacementDetail {
+    PlacementFulfilled(PlacementFulfilled),
+    PlacementCancelled(PlacementEnded),
+    PlacementTimedOut(PlacementEnded),
+    PlacementFailed(PlacementEnded),
+}
+
+impl PlacementDetail {
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            PlacementDetail::PlacementFulfilled(_) => "PlacementFulfilled",
+            PlacementDetail::PlacementCancelled(_) => "PlacementCancelled",
+            PlacementDetail::PlacementTimedOut(_) => "PlacementTimedOut",
+            PlacementDetail::PlacementFailed(_) => "PlacementFailed",
+        }
+    }
+
+    pub fn placement_id(&self) -> &str {
+        match self {
+            PlacementDetail::PlacementFulfilled(detail) => &detail.placement_id,
+            PlacementDetail::PlacementCancelled(detail)
+            | PlacementDetail::PlacementTimedOut(detail)
+            | PlacementDetail::PlacementFailed(detail) => &detail.placement_id,
+        }
+    }
+}
+
+/// A game session queue placement event, in the EventBridge format GameLift publishes to SNS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlacementEvent {
+    pub id: String,
+    pub time: DateTime<Utc>,
+    pub region: String,
+
+    /// the game session queue ARN
+    pub resources: Vec<String>,
+
+    pub detail: PlacementDetail,
+}
+
+/// An event GameLift publishes to SNS
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameLiftEvent {
+    Matchmaking(MatchmakingEvent),
+    Placement(PlacementEvent),
+}
+
+impl GameLiftEvent {
+    pub fn id(&self) -> &str {
+        match self {
+            GameLiftEvent::Matchmaking(event) => &event.id,
+            GameLiftEvent::Placement(event) => &event.id,
+        }
+    }
+
+    pub fn event_type(&self) -> &'static str {
+        match self {
+            GameLiftEvent::Matchmaking(event) => event.detail.event_type(),
+            GameLiftEvent::Placement(event) => event.detail.event_type(),
+        }
+    }
+}
+
+/// Why an SNS message couldn't be turned into a GameLift event
+#[derive(Debug)]
+pub enum EventError {
+    /// not JSON, or missing / mistyped fields
+    Malformed(String),
+
+    /// a well formed event of a type we don't handle
+    Unknown(String),
+}
+
+impl fmt::Display for EventError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EventError::Malformed(err) => write!(f, "malformed GameLift event: {}", err),
+            EventError::Unknown(event_type) => {
+                write!(f, "unknown GameLift event type {}", event_type)
+            }
+        }
+    }
+}
+
+impl std::error::Error for EventError {}
+
+#[derive(Debug, Deserialize)]
+struct RawEvent {
+    id: String,
+    time: DateTime<Utc>,
+    region: String,
+
+    #[serde(default)]
+    resources: Vec<String>,
+
+    detail: serde_json::Value,
+}
+
+fn detail<T>(event_type: &str, detail: serde_json::Value) -> Result<T, EventError>
+where
+    T: DeserializeOwned,
+{
+    serde_json::from_value(detail)
+        .map_err(|err| EventError::Malformed(format!("{} detail: {}", event_type, err)))
+}
+
+fn matchmaking_detail(
+    event_type: &str,
+    value: serde_json::Value,
+) -> Result<MatchmakingDetail, EventError> {
+    Ok(match event_type {
+        "MatchmakingSearching" => {
+            MatchmakingDetail::MatchmakingSearching(detail(event_type, value)?)
+        }
+        "PotentialMatchCreated" => {
+            MatchmakingDetail::PotentialMatchCreated(detail(event_type, value)?)
+        }
+        "AcceptMatch" => MatchmakingDetail::AcceptMatch(detail(event_type, value)?),
+        "AcceptMatchCompleted" => {
+            MatchmakingDetail::AcceptMatchCompleted(detail(event_type, value)?)
+        }
+        "MatchmakingSucceeded" => {
+            MatchmakingDetail::MatchmakingSucceeded(detail(event_type, value)?)
+        }
+        "MatchmakingTimedOut" => MatchmakingDetail::MatchmakingTimedOut(detail(event_type, value)?),
+        "MatchmakingCancelled" => {
+            MatchmakingDetail::MatchmakingCancelled(detail(event_type, value)?)
+        }
+        "MatchmakingFailed" => MatchmakingDetail::MatchmakingFailed(detail(event_type, value)?),
+        _ => return Err(EventError::Unknown(event_type.to_owned())),
+    })
+}
+
+fn placement_detail(
+    event_type: &str,
+    value: serde_json::Value,
+) -> Result<PlacementDetail, EventError> {
+    Ok(match event_type {
+        "PlacementFulfilled" => PlacementDetail::PlacementFulfilled(detail(event_type, value)?),
+        "PlacementCancelled" => PlacementDetail::PlacementCancelled(detail(event_type, value)?),
+        "PlacementTimedOut" => PlacementDetail::PlacementTimedOut(detail(event_type, value)?),
+        "PlacementFailed" => PlacementDetail::PlacementFailed(detail(event_type, value)?),
+        _ => return Err(EventError::Unknown(event_type.to_owned())),
+    })
+}
+
+/// Parses an SNS message body into a FlexMatch or queue placement event
+pub fn parse_event(message: &str) -> Result<GameLiftEvent, EventError> {
+    let event: RawEvent =
+        serde_json::from_str(message).map_err(|err| EventError::Malformed(err.to_string()))?;
+
+    let event_type = event
+        .detail
+        .get("type")
+        .and_then(|event_type| event_type.as_str())
+        .ok_or_else(|| EventError::Malformed("detail has no type".to_owned()))?
+        .to_owned();
+
+    Ok(if event_type.starts_with("Placement") {
+        GameLiftEvent::Placement(PlacementEvent {
+            id: event.id,
+            time: event.time,
+            region: event.region,
+            resources: event.resources,
+            detail: placement_detail(&event_type, event.detail)?,
+        })
+    } else {
+        GameLiftEvent::Matchmaking(MatchmakingEvent {
+            id: event.id,
+            time: event.time,
+            region: event.region,
+            resources: event.resources,
+            detail: matchmaking_detail(&event_type, event.detail)?,
+        })
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    fn raw_event(detail: Value) -> String {
+        json!({
+            "version": "0",
+            "id": "event-1",
+            "detail-type": "GameLift Matchmaking Event",
+            "source": "aws.gamelift",
+            "time": "2022-06-01T12:00:00.000Z",
+            "region": "us-west-2",
+            "resources": ["arn:aws:gamelift:us-west-2:123456789012:matchmakingconfiguration/echo"],
+            "detail": detail,
+        })
+        .to_string()
+    }
+
+    fn tickets() -> Value {
+        json!([{
+            "ticketId": "ticket-a",
+            "startTime": "2022-06-01T11:59:58.000Z",
+            "players": [{"playerId": "player-1", "team": "players"}],
+        }])
+    }
+
+    fn matchmaking(message: &str) -> MatchmakingDetail {
+        match parse_event(message).unwrap() {
+            GameLiftEvent::Matchmaking(event) => event.detail,
+            event => panic!("not a matchmaking event: {:?}", event),
+        }
+    }
+
+    fn placement(message: &str) -> PlacementDetail {
+        match parse_event(message).unwrap() {
+            GameLiftEvent::Placement(event) => event.detail,
+            event => panic!("not a placement event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn parses_matchmaking_events() {
+        let message = raw_event(json!({
+            "type": "MatchmakingSearching",
+            "tickets": tickets(),
+            "estimatedWaitMillis": "NOT_AVAILABLE",
+        }));
+        let event = parse_event(&message).unwrap();
+        assert_eq!(event.id(), "event-1");
+        assert_eq!(event.event_type(), "MatchmakingSearching");
+
+        let GameLiftEvent::Matchmaking(searching) = event else {
+            panic!("not a matchmaking event");
+        };
+        assert_eq!(searching.region, "us-west-2");
+        assert_eq!(searching.resources.len(), 1);
+        let MatchmakingDetail::MatchmakingSearching(detail) = &searching.detail else {
+            panic!("not searching: {:?}", searching.detail);
+        };
+        assert_eq!(
+            detail.estimated_wait_millis,
+            Some(EstimatedWait::Unavailable("NOT_AVAILABLE".to_owned()))
+        );
+        assert_eq!(searching.detail.tickets()[0].ticket_id, "ticket-a");
+        assert_eq!(
+            searching.detail.tickets()[0].players[0].player_id,
+            "player-1"
+        );
+
+        let detail = matchmaking(&raw_event(json!({
+            "type": "AcceptMatchCompleted",
+            "tickets": tickets(),
+            "matchId": "match-a",
+            "acceptance": "TimedOut",
+        })));
+        let MatchmakingDetail::AcceptMatchCompleted(completed) = &detail else {
+            panic!("not accept match completed: {:?}", detail);
+        };
+        assert_eq!(completed.acceptance, Acceptance::TimedOut);
+        assert_eq!(detail.event_type(), "AcceptMatchCompleted");
+    }
+
+    #[test]
+    fn ended_events_share_a_detail() {
+        for event_type in [
+            "MatchmakingTimedOut",
+            "MatchmakingCancelled",
+            "MatchmakingFailed",
+        ] {
+            let detail = matchmaking(&raw_event(json!({
+                "type": event_type,
+                "tickets": tickets(),
+                "reason": "TimedOut",
+            })));
+            assert_eq!(detail.event_type(), event_type);
+            assert_eq!(detail.tickets().len(), 1);
+        }
+    }
+
+    #[test]
+    fn parses_placement_events() {
+        let detail = placement(&raw_event(json!({
+            "type": "PlacementFulfilled",
+            "placementId": "placement-a",
+            "gameSessionArn": "arn:aws:gamelift:us-west-2::gamesession/fleet-1/gsess-1",
+            "ipAddress": "192.0.2.10",
+            "port": "7777",
+            "startTime": "2022-06-01T12:00:08.500Z",
+            "placedPlayerSessions": [
+                {"playerId": "player-1", "playerSessionId": "psess-1"},
+            ],
+        })));
+        assert_eq!(detail.event_type(), "PlacementFulfilled");
+        assert_eq!(detail.placement_id(), "placement-a");
+        let PlacementDetail::PlacementFulfilled(fulfilled) = &detail else {
+            panic!("not fulfilled: {:?}", detail);
+        };
+        assert_eq!(fulfilled.port, 7777);
+        assert_eq!(
+            fulfilled.placed_player_sessions[0].player_session_id,
+            "psess-1"
+        );
+
+        for event_type in ["PlacementCancelled", "PlacementTimedOut", "PlacementFailed"] {
+            let detail = placement(&raw_event(json!({
+                "type": event_type,
+                "placementId": "placement-b",
+                "startTime": "2022-06-01T12:00:08.500Z",
+            })));
+            assert_eq!(detail.event_type(), event_type);
+            assert_eq!(detail.placement_id(), "placement-b");
+        }
+    }
+
+    #[test]
+    fn rejects_unknown_types() {
+        for event_type in ["MatchmakingRewound", "PlacementRewound"] {
+            let message = raw_event(json!({"type": event_type, "tickets": tickets()}));
+            match parse_event(&message) {
+                Err(EventError::Unknown(unknown)) => assert_eq!(unknown, event_type),
+                result => panic!("{} parsed as {:?}", event_type, result),
+            }
+        }
+    }
+
+    #[test]
+    fn rejects_malformed_events() {
+        let messages = [
+            "not json".to_owned(),
+            json!({"id": "event-1"}).to_string(),
+            raw_event(json!({"tickets": tickets()})),
+            raw_event(json!({"type": 1})),
+            // known type with a missing field
+            raw_event(json!({"type": "MatchmakingSucceeded", "tickets": tickets()})),
+            raw_event(json!({
+                "type": "PlacementFulfilled",
+                "placementId": "placement-a",
+                "gameSessionArn": "arn",
+                "ipAddress": "192.0.2.10",
+                "port": "not a port",
+                "startTime": "2022-06-01T12:00:08.500Z",
+            })),
+        ];
+
+        for message in messages {
+            match parse_event(&message) {
+                Err(err @ EventError::Malformed(_)) => {
+                    assert!(err.to_string().starts_with("malformed GameLift event"))
+                }
+                result => panic!("{} parsed as {:?}", message, result),
+            }
+        }
+    }
+}
@@ -0,0 +1,10 @@
+//! Types shared by the echo client / server and echo-notifications
+
+pub mod events;
+pub mod placements;
+pub mod session;
+pub mod store;
+pub mod tickets;
+pub mod wire;
+
+pub use wire::{read_player_session_id, write_player_session_id, Message};
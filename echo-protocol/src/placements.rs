@@ -0,0 +1,218 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{PlacementDetail, PlacementEnded, PlacementEvent};
+use crate::tickets::MatchedPlayerSession;
+
+/// Placement status, named as DescribeGameSessionPlacement names them
+///
+/// Queues only publish events once a placement is over, so there's no pending state
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum PlacementStatus {
+    Fulfilled,
+    Cancelled,
+    TimedOut,
+    Failed,
+}
+
+/// How a game session placement ended, built from a queue placement event
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PlacementState {
+    pub placement_id: String,
+    pub status: PlacementStatus,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_session_arn: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dns_name: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    #[serde(default)]
+    pub player_sessions: Vec<MatchedPlayerSession>,
+
+    pub start_time: DateTime<Utc>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub end_time: Option<DateTime<Utc>>,
+
+    /// the time of the event this state came from
+    pub updated_at: DateTime<Utc>,
+}
+
+impl PlacementState {
+    fn ended(status: PlacementStatus, detail: &PlacementEnded, updated_at: DateTime<Utc>) -> Self {
+        Self {
+            placement_id: detail.placement_id.clone(),
+            status,
+            game_session_arn: None,
+            ip_address: None,
+            dns_name: None,
+            port: None,
+            player_sessions: Vec::new(),
+            start_time: detail.start_time,
+            end_time: detail.end_time,
+            updated_at,
+        }
+    }
+
+    pub fn from_event(event: &PlacementEvent) -> Self {
+        match &event.detail {
+            PlacementDetail::PlacementFulfilled(detail) => Self {
+                placement_id: detail.placement_id.clone(),
+                status: PlacementStatus::Fulfilled,
+                game_session_arn: Some(detail.game_session_arn.clone()),
+                ip_address: Some(detail.ip_address.clone()),
+                dns_name: detail.dns_name.clone(),
+                port: Some(detail.port),
+                player_sessions: detail
+                    .placed_player_sessions
+                    .iter()
+                    .map(|player_session| MatchedPlayerSession {
+                        player_id: player_session.player_id.clone(),
+                        player_session_id: player_session.player_session_id.clone(),
+                    })
+                    .collect(),
+                start_time: detail.start_time,
+                end_time: detail.end_time,
+                updated_at: event.time,
+            },
+            PlacementDetail::PlacementCancelled(detail) => {
+                Self::ended(PlacementStatus::Cancelled, detail, event.time)
+            }
+            PlacementDetail::PlacementTimedOut(detail) => {
+                Self::ended(PlacementStatus::TimedOut, detail, event.time)
+            }
+            PlacementDetail::PlacementFailed(detail) => {
+                Self::ended(PlacementStatus::Failed, detail, event.time)
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+    use crate::events::{self, GameLiftEvent};
+
+    fn placement_event(detail: serde_json::Value) -> PlacementEvent {
+        let message = json!({
+            "version": "0",
+            "id": "event-1",
+            "detail-type": "GameLift Queue Placement Event",
+            "source": "aws.gamelift",
+            "time": "2022-06-01T12:00:11.000Z",
+            "region": "us-west-2",
+            "resources": ["arn:aws:gamelift:us-west-2:123456789012:gamesessionqueue/echo-queue"],
+            "detail": detail,
+        });
+
+        match events::parse_event(&message.to_string()).unwrap() {
+            GameLiftEvent::Placement(event) => event,
+            event => panic!("not a placement event: {:?}", event),
+        }
+    }
+
+    #[test]
+    fn fulfilled_placements() {
+        let state = PlacementState::from_event(&placement_event(json!({
+            "type": "PlacementFulfilled",
+            "placementId": "placement-1",
+            "gameSessionArn": "arn:aws:gamelift:us-west-2::gamesession/fleet-1/gsess-1",
+            "ipAddress": "192.0.2.10",
+            "dnsName": "ec2-192-0-2-10.us-west-2.compute.amazonaws.com",
+            "port": "7777",
+            "startTime": "2022-06-01T12:00:08.500Z",
+            "endTime": "2022-06-01T12:00:10.900Z",
+            "placedPlayerSessions": [
+                {"playerId": "player-1", "playerSessionId": "psess-1"},
+                {"playerId": "player-2", "playerSessionId": "psess-2"},
+            ],
+        })));
+
+        assert_eq!(state.placement_id, "placement-1");
+        assert_eq!(state.status, PlacementStatus::Fulfilled);
+        assert_eq!(
+            state.game_session_arn.as_deref(),
+            Some("arn:aws:gamelift:us-west-2::gamesession/fleet-1/gsess-1")
+        );
+        assert_eq!(state.ip_address.as_deref(), Some("192.0.2.10"));
+        assert_eq!(state.port, Some(7777));
+        assert_eq!(
+            state.player_sessions,
+            [
+                MatchedPlayerSession {
+                    player_id: "player-1".to_owned(),
+                    player_session_id: "psess-1".to_owned(),
+                },
+                MatchedPlayerSession {
+                    player_id: "player-2".to_owned(),
+                    player_session_id: "psess-2".to_owned(),
+                },
+            ]
+        );
+        assert_eq!(
+            state.updated_at,
+            "2022-06-01T12:00:11Z".parse::<DateTime<Utc>>().unwrap()
+        );
+        assert!(state.end_time.is_some());
+    }
+
+    #[test]
+    fn ended_placements() {
+        for (event_type, status) in [
+            ("PlacementCancelled", PlacementStatus::Cancelled),
+            ("PlacementTimedOut", PlacementStatus::TimedOut),
+            ("PlacementFailed", PlacementStatus::Failed),
+        ] {
+            let state = PlacementState::from_event(&placement_event(json!({
+                "type": event_type,
+                "placementId": "placement-1",
+                "startTime": "2022-06-01T12:00:08.500Z",
+            })));
+
+            assert_eq!(state.placement_id, "placement-1");
+            assert_eq!(state.status, status);
+            // no game session was placed
+            assert_eq!(state.game_session_arn, None);
+            assert_eq!(state.ip_address, None);
+            assert_eq!(state.port, None);
+            assert!(state.player_sessions.is_empty());
+            assert_eq!(state.end_time, None);
+        }
+    }
+
+    #[test]
+    fn round_trips_as_json() {
+        let state = PlacementState::from_event(&placement_event(json!({
+            "type": "PlacementTimedOut",
+            "placementId": "placement-1",
+            "startTime": "2022-06-01T12:00:08.500Z",
+        })));
+
+        let value = serde_json::to_value(&state).unwrap();
+        assert_eq!(
+            value,
+            json!({
+                "placementId": "placement-1",
+                "status": "TIMED_OUT",
+                "playerSessions": [],
+                "startTime": "2022-06-01T12:00:08.500Z",
+                "updatedAt": "2022-06-01T12:00:11Z",
+            })
+        );
+        assert_eq!(
+            serde_json::from_value::<PlacementState>(value).unwrap(),
+            state
+        );
+    }
+}
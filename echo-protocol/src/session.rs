@@ -0,0 +1,6 @@
+/// A game session the hosting service wants us to run
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionInfo {
+    pub session_id: String,
+    pub max_players: Option<u32>,
+}
@@ -0,0 +1,185 @@
+#[cfg(feature = "dynamodb")]
+pub mod dynamodb;
+pub mod file;
+
+use std::future::Future;
+use std::pin::Pin;
+use std::str::FromStr;
+
+use crate::tickets::TicketState;
+
+pub type StoreFuture<'a, T> = Pin<Box<dyn Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Where the latest state of each matchmaking ticket is kept
+pub trait TicketStore: Send + Sync {
+    fn get(&self, ticket_id: String) -> StoreFuture<'_, Option<TicketState>>;
+
+    /// Saves the state unless the stored one came from a newer event or has finished,
+    /// returns whether it was saved
+    fn put(&self, state: TicketState) -> StoreFuture<'_, bool>;
+}
+
+/// Remembers which notification messages have been processed, so redelivered ones are skipped
+pub trait MessageStore: Send + Sync {
+    fn is_processed(&self, message_id: String) -> StoreFuture<'_, bool>;
+
+    fn mark_processed(&self, message_id: String) -> StoreFuture<'_, ()>;
+}
+
+/// A store to open, eg. file:tickets or dynamodb:echo-tickets
+///
+/// DynamoDB tables can take an endpoint (dynamodb:echo-tickets@http://localhost:8000),
+/// for DynamoDB Local
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StoreConfig {
+    File(String),
+    DynamoDb {
+        table_name: String,
+        endpoint: Option<String>,
+    },
+}
+
+impl FromStr for StoreConfig {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once(':') {
+            Some(("file", path)) if !path.is_empty() => Ok(Self::File(path.to_owned())),
+            Some(("dynamodb", table)) if !table.is_empty() => {
+                let (table_name, endpoint) = match table.split_once('@') {
+                    Some((table_name, endpoint)) => (table_name, Some(endpoint.to_owned())),
+                    None => (table, None),
+                };
+
+                Ok(Self::DynamoDb {
+                    table_name: table_name.to_owned(),
+                    endpoint,
+                })
+            }
+            _ => Err(format!(
+                "invalid store '{}', expected file:<dir> or dynamodb:<table>[@<endpoint>]",
+                s
+            )),
+        }
+    }
+}
+
+impl StoreConfig {
+    #[cfg(feature = "dynamodb")]
+    pub async fn open_tickets(&self) -> anyhow::Result<Box<dyn TicketStore>> {
+        Ok(match self {
+            Self::File(path) => Box::new(file::FileTicketStore::new(path)),
+            Self::DynamoDb {
+                table_name,
+                endpoint,
+            } => Box::new(dynamodb::DynamoTicketStore::new(table_name, endpoint.as_deref()).await?),
+        })
+    }
+
+    #[cfg(not(feature = "dynamodb"))]
+    pub async fn open_tickets(&self) -> anyhow::Result<Box<dyn TicketStore>> {
+        match self {
+            Self::File(path) => Ok(Box::new(file::FileTicketStore::new(path))),
+            Self::DynamoDb { .. } => {
+                anyhow::bail!("DynamoDB ticket stores need the dynamodb feature")
+            }
+        }
+    }
+
+    #[cfg(feature = "dynamodb")]
+    pub async fn open_messages(&self) -> anyhow::Result<Box<dyn MessageStore>> {
+        Ok(match self {
+            Self::File(path) => Box::new(file::FileMessageStore::new(path)),
+            Self::DynamoDb {
+                table_name,
+                endpoint,
+            } => {
+                Box::new(dynamodb::DynamoMessageStore::new(table_name, endpoint.as_deref()).await?)
+            }
+        })
+    }
+
+    #[cfg(not(feature = "dynamodb"))]
+    pub async fn open_messages(&self) -> anyhow::Result<Box<dyn MessageStore>> {
+        match self {
+            Self::File(path) => Ok(Box::new(file::FileMessageStore::new(path))),
+            Self::DynamoDb { .. } => {
+                anyhow::bail!("DynamoDB message stores need the dynamodb feature")
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use std::path::PathBuf;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    use chrono::{TimeZone, Utc};
+
+    use super::*;
+    use crate::tickets::TicketStatus;
+
+    /// A directory that doesn't exist yet, unique to the test
+    pub fn temp_dir(name: &str) -> PathBuf {
+        static COUNT: AtomicUsize = AtomicUsize::new(0);
+
+        std::env::temp_dir().join(format!(
+            "echo-protocol-{}-{}-{}",
+            name,
+            std::process::id(),
+            COUNT.fetch_add(1, Ordering::Relaxed)
+        ))
+    }
+
+    /// A ticket state from an event the given number of seconds after the ticket started
+    pub fn ticket(ticket_id: &str, status: TicketStatus, seconds: i64) -> TicketState {
+        let start_time = Utc.timestamp_opt(1_600_000_000, 0).unwrap();
+
+        TicketState {
+            ticket_id: ticket_id.to_owned(),
+            status,
+            status_reason: None,
+            status_message: None,
+            match_id: None,
+            game_session_arn: None,
+            ip_address: None,
+            port: None,
+            player_ids: vec!["player-1".to_owned()],
+            player_sessions: Vec::new(),
+            start_time,
+            updated_at: start_time + chrono::Duration::seconds(seconds),
+        }
+    }
+
+    #[test]
+    fn supersedes() {
+        let searching = ticket("ticket-1", TicketStatus::Searching, 1);
+        let placing = ticket("ticket-1", TicketStatus::Placing, 2);
+        let completed = ticket("ticket-1", TicketStatus::Completed, 3);
+
+        assert!(placing.supersedes(&searching));
+        assert!(!searching.supersedes(&placing));
+        assert!(completed.supersedes(&placing));
+        assert!(!ticket("ticket-1", TicketStatus::Searching, 4).supersedes(&completed));
+        assert!(ticket("ticket-1", TicketStatus::Failed, 4).supersedes(&completed));
+        assert!(!ticket("ticket-1", TicketStatus::Failed, 2).supersedes(&completed));
+    }
+
+    #[test]
+    fn store_config() {
+        assert_eq!(
+            "file:tickets".parse(),
+            Ok(StoreConfig::File("tickets".to_owned()))
+        );
+        assert_eq!(
+            "dynamodb:echo-tickets@http://localhost:8000".parse(),
+            Ok(StoreConfig::DynamoDb {
+                table_name: "echo-tickets".to_owned(),
+                endpoint: Some("http://localhost:8000".to_owned()),
+            })
+        );
+        assert!("dynamodb:".parse::<StoreConfig>().is_err());
+        assert!("tickets".parse::<StoreConfig>().is_err());
+    }
+}
@@ -0,0 +1,287 @@
+use anyhow::anyhow;
+use aws_sdk_dynamodb::{
+    error::PutItemErrorKind, model::AttributeValue, Client, Endpoint, SdkError,
+};
+use chrono::{Duration, Utc};
+use futures_util::FutureExt;
+use http::Uri;
+
+use super::{MessageStore, StoreFuture, TicketStore};
+use crate::tickets::TicketState;
+
+const TICKET_ID: &str = "ticketId";
+const UPDATED_AT: &str = "updatedAt";
+const STATE: &str = "state";
+const FINISHED: &str = "finished";
+
+const MESSAGE_ID: &str = "messageId";
+const PROCESSED_AT: &str = "processedAt";
+const EXPIRES_AT: &str = "expiresAt";
+
+/// How long processed messages are remembered, well past how long SNS keeps retrying
+const MESSAGE_RETENTION_DAYS: i64 = 7;
+
+async fn client(endpoint: Option<&str>) -> anyhow::Result<Client> {
+    let shared_config = aws_config::from_env().load().await;
+
+    let mut config = aws_sdk_dynamodb::config::Builder::from(&shared_config);
+    if let Some(endpoint) = endpoint {
+        let uri = endpoint
+            .parse::<Uri>()
+            .map_err(|err| anyhow!("Invalid DynamoDB endpoint {}: {}", endpoint, err))?;
+        config = config.endpoint_resolver(Endpoint::immutable(uri));
+    }
+
+    Ok(Client::from_conf(config.build()))
+}
+
+/// Keeps tickets in a DynamoDB table keyed by ticketId (a string)
+///
+/// The state is stored as JSON, next to the update time and whether the ticket finished,
+/// which are used to drop out of order events
+pub struct DynamoTicketStore {
+    client: Client,
+    table_name: String,
+}
+
+impl DynamoTicketStore {
+    pub async fn new(
+        table_name: impl Into<String>,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: client(endpoint).await?,
+            table_name: table_name.into(),
+        })
+    }
+}
+
+impl TicketStore for DynamoTicketStore {
+    fn get(&self, ticket_id: String) -> StoreFuture<'_, Option<TicketState>> {
+        async move {
+            let output = self
+                .client
+                .get_item()
+                .table_name(&self.table_name)
+                .key(TICKET_ID, AttributeValue::S(ticket_id.clone()))
+                .consistent_read(true)
+                .send()
+                .await?;
+
+            let item = match output.item {
+                Some(item) => item,
+                None => return Ok(None),
+            };
+
+            let state = item
+                .get(STATE)
+                .and_then(|state| state.as_s().ok())
+                .ok_or_else(|| anyhow!("Ticket {} has no state", ticket_id))?;
+
+            serde_json::from_str(state)
+                .map(Some)
+                .map_err(|err| anyhow!("Invalid ticket state for {}: {}", ticket_id, err))
+        }
+        .boxed()
+    }
+
+    fn put(&self, state: TicketState) -> StoreFuture<'_, bool> {
+        async move {
+            let updated_at = state.updated_at.timestamp_millis().to_string();
+
+            let request = self
+                .client
+                .put_item()
+                .table_name(&self.table_name)
+                .item(TICKET_ID, AttributeValue::S(state.ticket_id.clone()))
+                .item(UPDATED_AT, AttributeValue::N(updated_at.clone()))
+                .item(FINISHED, AttributeValue::Bool(state.is_finished()))
+                .item(STATE, AttributeValue::S(serde_json::to_string(&state)?))
+                .expression_attribute_names("#ticketId", TICKET_ID)
+                .expression_attribute_names("#updatedAt", UPDATED_AT)
+                .expression_attribute_values(":updatedAt", AttributeValue::N(updated_at));
+
+            // only a finished state can replace a finished one
+            let request = if state.is_finished() {
+                request.condition_expression(
+                    "attribute_not_exists(#ticketId) OR #updatedAt <= :updatedAt",
+                )
+            } else {
+                request
+                    .condition_expression(
+                        "attribute_not_exists(#ticketId) OR (#updatedAt <= :updatedAt \
+                         AND (attribute_not_exists(#finished) OR #finished = :false))",
+                    )
+                    .expression_attribute_names("#finished", FINISHED)
+                    .expression_attribute_values(":false", AttributeValue::Bool(false))
+            };
+
+            let res = request.send().await;
+
+            match res {
+                Ok(_) => Ok(true),
+                // a newer event already updated the ticket
+                Err(SdkError::ServiceError { err, .. })
+                    if matches!(
+                        err.kind,
+                        PutItemErrorKind::ConditionalCheckFailedException(_)
+                    ) =>
+                {
+                    Ok(false)
+                }
+                Err(err) => Err(err.into()),
+            }
+        }
+        .boxed()
+    }
+}
+
+/// Keeps processed message ids in a DynamoDB table keyed by messageId (a string)
+///
+/// Items carry an expiresAt time, enable TTL on it to have old ones removed
+pub struct DynamoMessageStore {
+    client: Client,
+    table_name: String,
+}
+
+impl DynamoMessageStore {
+    pub async fn new(
+        table_name: impl Into<String>,
+        endpoint: Option<&str>,
+    ) -> anyhow::Result<Self> {
+        Ok(Self {
+            client: client(endpoint).await?,
+            table_name: table_name.into(),
+        })
+    }
+}
+
+impl MessageStore for DynamoMessageStore {
+    fn is_processed(&self, message_id: String) -> StoreFuture<'_, bool> {
+        async move {
+            let output = self
+                .client
+                .get_item()
+                .table_name(&self.table_name)
+                .key(MESSAGE_ID, AttributeValue::S(message_id))
+                .consistent_read(true)
+                .send()
+                .await?;
+
+            // TTL deletes lag behind, so check the expiry too
+            let now = Utc::now().timestamp();
+            Ok(output
+                .item
+                .as_ref()
+                .and_then(|item| item.get(EXPIRES_AT))
+                .and_then(|expires_at| expires_at.as_n().ok())
+                .and_then(|expires_at| expires_at.parse::<i64>().ok())
+                .is_some_and(|expires_at| expires_at > now))
+        }
+        .boxed()
+    }
+
+    fn mark_processed(&self, message_id: String) -> StoreFuture<'_, ()> {
+        async move {
+            let now = Utc::now();
+            let expires_at = now + Duration::days(MESSAGE_RETENTION_DAYS);
+
+            self.client
+                .put_item()
+                .table_name(&self.table_name)
+                .item(MESSAGE_ID, AttributeValue::S(message_id))
+                .item(
+                    PROCESSED_AT,
+                    AttributeValue::N(now.timestamp_millis().to_string()),
+                )
+                .item(
+                    EXPIRES_AT,
+                    AttributeValue::N(expires_at.timestamp().to_string()),
+                )
+                .send()
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_dynamodb::model::{
+        AttributeDefinition, BillingMode, KeySchemaElement, KeyType, ScalarAttributeType,
+    };
+
+    use super::*;
+    use crate::store::tests::ticket;
+    use crate::tickets::TicketStatus;
+
+    /// DynamoDB Local, eg. docker run -p 8000:8000 amazon/dynamodb-local
+    ///
+    /// The SDK still wants a region and credentials, any will do
+    const LOCAL_ENDPOINT: &str = "http://localhost:8000";
+
+    async fn local_store() -> DynamoTicketStore {
+        let table_name = format!("echo-tickets-test-{}", Utc::now().timestamp_millis());
+        let store = DynamoTicketStore::new(&table_name, Some(LOCAL_ENDPOINT))
+            .await
+            .unwrap();
+
+        store
+            .client
+            .create_table()
+            .table_name(&table_name)
+            .key_schema(
+                KeySchemaElement::builder()
+                    .attribute_name(TICKET_ID)
+                    .key_type(KeyType::Hash)
+                    .build(),
+            )
+            .attribute_definitions(
+                AttributeDefinition::builder()
+                    .attribute_name(TICKET_ID)
+                    .attribute_type(ScalarAttributeType::S)
+                    .build(),
+            )
+            .billing_mode(BillingMode::PayPerRequest)
+            .send()
+            .await
+            .unwrap();
+
+        store
+    }
+
+    #[tokio::test]
+    #[ignore = "needs DynamoDB Local on port 8000"]
+    async fn conditional_put() {
+        let store = local_store().await;
+        assert_eq!(store.get("ticket-1".to_owned()).await.unwrap(), None);
+
+        let placing = ticket("ticket-1", TicketStatus::Placing, 2);
+        assert!(store.put(placing.clone()).await.unwrap());
+
+        // older events are dropped
+        assert!(!store
+            .put(ticket("ticket-1", TicketStatus::Searching, 1))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.get("ticket-1".to_owned()).await.unwrap(),
+            Some(placing)
+        );
+
+        let completed = ticket("ticket-1", TicketStatus::Completed, 3);
+        assert!(store.put(completed.clone()).await.unwrap());
+
+        // finished tickets stay finished, even for newer events
+        assert!(!store
+            .put(ticket("ticket-1", TicketStatus::Searching, 4))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.get("ticket-1".to_owned()).await.unwrap(),
+            Some(completed)
+        );
+    }
+}
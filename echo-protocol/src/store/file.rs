@@ -0,0 +1,203 @@
+use std::path::{Path, PathBuf};
+
+use anyhow::{anyhow, bail};
+use futures_util::FutureExt;
+
+use super::{MessageStore, StoreFuture, TicketStore};
+use crate::tickets::TicketState;
+
+/// A file in the directory named after an id, which has to be a plain file name
+fn id_path(dir: &Path, id: &str, extension: &str) -> anyhow::Result<PathBuf> {
+    if id.is_empty() || id.contains(['/', '\\']) || id.starts_with('.') {
+        bail!("Invalid id {}", id);
+    }
+
+    Ok(dir.join(format!("{}.{}", id, extension)))
+}
+
+/// Keeps each ticket as {ticket id}.json in a directory
+pub struct FileTicketStore {
+    dir: PathBuf,
+}
+
+impl FileTicketStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+
+    fn path(&self, ticket_id: &str) -> anyhow::Result<PathBuf> {
+        id_path(&self.dir, ticket_id, "json")
+    }
+
+    async fn read(&self, ticket_id: &str) -> anyhow::Result<Option<TicketState>> {
+        let path = self.path(ticket_id)?;
+
+        let state = match tokio::fs::read_to_string(&path).await {
+            Ok(state) => state,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(err) => return Err(anyhow!("Failed to read {}: {}", path.display(), err)),
+        };
+
+        serde_json::from_str(&state)
+            .map(Some)
+            .map_err(|err| anyhow!("Invalid ticket state {}: {}", path.display(), err))
+    }
+
+    async fn write(&self, state: &TicketState) -> anyhow::Result<bool> {
+        if let Some(stored) = self.read(&state.ticket_id).await? {
+            if !state.supersedes(&stored) {
+                return Ok(false);
+            }
+        }
+
+        tokio::fs::create_dir_all(&self.dir)
+            .await
+            .map_err(|err| anyhow!("Failed to create {}: {}", self.dir.display(), err))?;
+
+        // write then rename, so readers never see a partial file
+        let path = self.path(&state.ticket_id)?;
+        let tmp_path = path.with_extension("tmp");
+        tokio::fs::write(&tmp_path, serde_json::to_string_pretty(state)?)
+            .await
+            .map_err(|err| anyhow!("Failed to write {}: {}", tmp_path.display(), err))?;
+        tokio::fs::rename(&tmp_path, &path)
+            .await
+            .map_err(|err| anyhow!("Failed to write {}: {}", path.display(), err))?;
+
+        Ok(true)
+    }
+}
+
+impl TicketStore for FileTicketStore {
+    fn get(&self, ticket_id: String) -> StoreFuture<'_, Option<TicketState>> {
+        async move { self.read(&ticket_id).await }.boxed()
+    }
+
+    fn put(&self, state: TicketState) -> StoreFuture<'_, bool> {
+        async move { self.write(&state).await }.boxed()
+    }
+}
+
+/// Marks each processed message with an empty {message id}.processed file in a directory
+pub struct FileMessageStore {
+    dir: PathBuf,
+}
+
+impl FileMessageStore {
+    pub fn new(dir: impl Into<PathBuf>) -> Self {
+        Self { dir: dir.into() }
+    }
+}
+
+impl MessageStore for FileMessageStore {
+    fn is_processed(&self, message_id: String) -> StoreFuture<'_, bool> {
+        async move {
+            let path = id_path(&self.dir, &message_id, "processed")?;
+
+            match tokio::fs::metadata(&path).await {
+                Ok(_) => Ok(true),
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(false),
+                Err(err) => Err(anyhow!("Failed to read {}: {}", path.display(), err)),
+            }
+        }
+        .boxed()
+    }
+
+    fn mark_processed(&self, message_id: String) -> StoreFuture<'_, ()> {
+        async move {
+            let path = id_path(&self.dir, &message_id, "processed")?;
+
+            tokio::fs::create_dir_all(&self.dir)
+                .await
+                .map_err(|err| anyhow!("Failed to create {}: {}", self.dir.display(), err))?;
+            tokio::fs::write(&path, "")
+                .await
+                .map_err(|err| anyhow!("Failed to write {}: {}", path.display(), err))
+        }
+        .boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::{temp_dir, ticket};
+    use crate::tickets::TicketStatus;
+
+    #[tokio::test]
+    async fn ticket_round_trip() {
+        let store = FileTicketStore::new(temp_dir("tickets"));
+        assert_eq!(store.get("ticket-1".to_owned()).await.unwrap(), None);
+
+        let state = ticket("ticket-1", TicketStatus::Searching, 1);
+        assert!(store.put(state.clone()).await.unwrap());
+        assert_eq!(store.get("ticket-1".to_owned()).await.unwrap(), Some(state));
+    }
+
+    #[tokio::test]
+    async fn older_state_dropped() {
+        let store = FileTicketStore::new(temp_dir("tickets"));
+
+        let placing = ticket("ticket-1", TicketStatus::Placing, 2);
+        assert!(store.put(placing.clone()).await.unwrap());
+        assert!(!store
+            .put(ticket("ticket-1", TicketStatus::Searching, 1))
+            .await
+            .unwrap());
+        assert_eq!(
+            store.get("ticket-1".to_owned()).await.unwrap(),
+            Some(placing)
+        );
+
+        // events from the same time arrive in order, so the later one wins
+        let requires_acceptance = ticket("ticket-1", TicketStatus::RequiresAcceptance, 2);
+        assert!(store.put(requires_acceptance.clone()).await.unwrap());
+        assert_eq!(
+            store.get("ticket-1".to_owned()).await.unwrap(),
+            Some(requires_acceptance)
+        );
+    }
+
+    #[tokio::test]
+    async fn finished_state_sticks() {
+        let store = FileTicketStore::new(temp_dir("tickets"));
+
+        let cancelled = ticket("ticket-1", TicketStatus::Cancelled, 2);
+        assert!(store.put(cancelled.clone()).await.unwrap());
+        for status in [TicketStatus::Searching, TicketStatus::Placing] {
+            assert!(!store.put(ticket("ticket-1", status, 3)).await.unwrap());
+        }
+        assert_eq!(
+            store.get("ticket-1".to_owned()).await.unwrap(),
+            Some(cancelled)
+        );
+    }
+
+    #[tokio::test]
+    async fn invalid_ids_rejected() {
+        let store = FileTicketStore::new(temp_dir("tickets"));
+
+        for ticket_id in ["", "../ticket-1", ".ticket-1"] {
+            assert!(store.get(ticket_id.to_owned()).await.is_err());
+            assert!(store
+                .put(ticket(ticket_id, TicketStatus::Searching, 1))
+                .await
+                .is_err());
+        }
+    }
+
+    #[tokio::test]
+    async fn messages_processed() {
+        let store = FileMessageStore::new(temp_dir("messages"));
+
+        assert!(!store.is_processed("message-1".to_owned()).await.unwrap());
+        store.mark_processed("message-1".to_owned()).await.unwrap();
+        assert!(store.is_processed("message-1".to_owned()).await.unwrap());
+        assert!(!store.is_processed("message-2".to_owned()).await.unwrap());
+
+        assert!(store
+            .mark_processed("../message-1".to_owned())
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,231 @@
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+use crate::events::{
+    Acceptance, GameSessionInfo, MatchmakingDetail, MatchmakingEnded, MatchmakingEvent,
+    Ticket as EventTicket,
+};
+
+/// Ticket status, named as DescribeMatchmaking names them
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "SCREAMING_SNAKE_CASE")]
+pub enum TicketStatus {
+    Searching,
+    RequiresAcceptance,
+    Placing,
+    Completed,
+    TimedOut,
+    Cancelled,
+    Failed,
+}
+
+/// What an event says about the tickets in it
+struct Change<'a> {
+    status: TicketStatus,
+    match_id: Option<&'a str>,
+    reason: Option<String>,
+    message: Option<String>,
+    game_session_info: &'a GameSessionInfo,
+}
+
+impl<'a> Change<'a> {
+    fn new(
+        status: TicketStatus,
+        match_id: Option<&'a str>,
+        game_session_info: &'a GameSessionInfo,
+    ) -> Self {
+        Self {
+            status,
+            match_id,
+            reason: None,
+            message: None,
+            game_session_info,
+        }
+    }
+
+    fn ended(status: TicketStatus, detail: &'a MatchmakingEnded) -> Self {
+        Self {
+            reason: Some(detail.reason.clone()),
+            message: detail.message.clone(),
+            ..Self::new(
+                status,
+                detail.match_id.as_deref(),
+                &detail.game_session_info,
+            )
+        }
+    }
+}
+
+/// A player session created for a matched player
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct MatchedPlayerSession {
+    pub player_id: String,
+    pub player_session_id: String,
+}
+
+/// The latest known state of a matchmaking ticket, built from notification events
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct TicketState {
+    pub ticket_id: String,
+    pub status: TicketStatus,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_reason: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub status_message: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub match_id: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub game_session_arn: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ip_address: Option<String>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+
+    /// players on this ticket, with their player sessions once matched
+    #[serde(default)]
+    pub player_ids: Vec<String>,
+
+    #[serde(default)]
+    pub player_sessions: Vec<MatchedPlayerSession>,
+
+    pub start_time: DateTime<Utc>,
+
+    /// the time of the event this state came from, older events don't replace it
+    pub updated_at: DateTime<Utc>,
+}
+
+impl TicketState {
+    fn new(
+        ticket: &EventTicket,
+        status: TicketStatus,
+        game_session_info: &GameSessionInfo,
+        updated_at: DateTime<Utc>,
+    ) -> Self {
+        let player_ids = ticket
+            .players
+            .iter()
+            .map(|player| player.player_id.clone())
+            .collect::<Vec<_>>();
+
+        // the game session has every matched player, only keep the ones on this ticket
+        let player_sessions = game_session_info
+            .players
+            .iter()
+            .filter(|player| player_ids.contains(&player.player_id))
+            .filter_map(|player| {
+                Some(MatchedPlayerSession {
+                    player_id: player.player_id.clone(),
+                    player_session_id: player.player_session_id.clone()?,
+                })
+            })
+            .collect();
+
+        Self {
+            ticket_id: ticket.ticket_id.clone(),
+            status,
+            status_reason: None,
+            status_message: None,
+            match_id: None,
+            game_session_arn: game_session_info.game_session_arn.clone(),
+            ip_address: game_session_info.ip_address.clone(),
+            port: game_session_info.port,
+            player_ids,
+            player_sessions,
+            start_time: ticket.start_time,
+            updated_at,
+        }
+    }
+
+    /// Whether the ticket won't change any more
+    pub fn is_finished(&self) -> bool {
+        matches!(
+            self.status,
+            TicketStatus::Completed
+                | TicketStatus::TimedOut
+                | TicketStatus::Cancelled
+                | TicketStatus::Failed
+        )
+    }
+
+    /// Whether this state should replace the stored one
+    ///
+    /// Older events don't replace newer ones, and a finished ticket stays finished
+    pub fn supersedes(&self, stored: &TicketState) -> bool {
+        self.updated_at >= stored.updated_at && (self.is_finished() || !stored.is_finished())
+    }
+
+    /// The state of every ticket in the event
+    pub fn from_event(event: &MatchmakingEvent) -> Vec<TicketState> {
+        let change = match &event.detail {
+            MatchmakingDetail::MatchmakingSearching(detail) => {
+                Change::new(TicketStatus::Searching, None, &detail.game_session_info)
+            }
+            MatchmakingDetail::PotentialMatchCreated(detail) => Change::new(
+                if detail.acceptance_required {
+                    TicketStatus::RequiresAcceptance
+                } else {
+                    TicketStatus::Placing
+                },
+                Some(&detail.match_id),
+                &detail.game_session_info,
+            ),
+            MatchmakingDetail::AcceptMatch(detail) => Change::new(
+                TicketStatus::RequiresAcceptance,
+                Some(&detail.match_id),
+                &detail.game_session_info,
+            ),
+            MatchmakingDetail::AcceptMatchCompleted(detail) => match detail.acceptance {
+                Acceptance::Accepted => Change::new(
+                    TicketStatus::Placing,
+                    Some(&detail.match_id),
+                    &detail.game_session_info,
+                ),
+                // followed by searching or cancelled events for each ticket
+                Acceptance::TimedOut | Acceptance::Rejected => Change {
+                    reason: Some(format!("{:?}", detail.acceptance)),
+                    ..Change::new(
+                        TicketStatus::RequiresAcceptance,
+                        Some(&detail.match_id),
+                        &detail.game_session_info,
+                    )
+                },
+            },
+            MatchmakingDetail::MatchmakingSucceeded(detail) => Change::new(
+                TicketStatus::Completed,
+                Some(&detail.match_id),
+                &detail.game_session_info,
+            ),
+            MatchmakingDetail::MatchmakingTimedOut(detail) => {
+                Change::ended(TicketStatus::TimedOut, detail)
+            }
+            MatchmakingDetail::MatchmakingCancelled(detail) => {
+                Change::ended(TicketStatus::Cancelled, detail)
+            }
+            MatchmakingDetail::MatchmakingFailed(detail) => {
+                Change::ended(TicketStatus::Failed, detail)
+            }
+        };
+
+        event
+            .detail
+            .tickets()
+            .iter()
+            .map(|ticket| {
+                let mut state =
+                    Self::new(ticket, change.status, change.game_session_info, event.time);
+                state.match_id = change.match_id.map(str::to_owned);
+                state.status_reason = change.reason.clone();
+                state.status_message = change.message.clone();
+                state
+            })
+            .collect()
+    }
+}
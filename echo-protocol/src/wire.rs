@@ -0,0 +1,220 @@
+use anyhow::bail;
+use tokio::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+
+const MESSAGE_ECHO: u8 = 0;
+const MESSAGE_SHUTDOWN_WARNING: u8 = 1;
+
+/// Messages exchanged after the handshake
+///
+/// Each message is framed as a 1 byte type, a 2 byte (big endian) length and the payload
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Message {
+    /// Text to echo back to the sender
+    Echo(String),
+
+    /// The server is shutting down in the given number of seconds
+    ShutdownWarning(u64),
+}
+
+impl Message {
+    /// Reads the next message, returns None if the connection was closed
+    pub async fn read<R>(reader: &mut R) -> anyhow::Result<Option<Self>>
+    where
+        R: AsyncRead + Unpin,
+    {
+        let message_type = match reader.read_u8().await {
+            Ok(message_type) => message_type,
+            Err(err) if err.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(None),
+            Err(err) => return Err(err.into()),
+        };
+
+        let len = reader.read_u16().await? as usize;
+
+        let mut buf = vec![0; len];
+        reader.read_exact(&mut buf).await?;
+
+        match message_type {
+            MESSAGE_ECHO => Ok(Some(Self::Echo(String::from_utf8(buf)?))),
+            MESSAGE_SHUTDOWN_WARNING => {
+                if len != 8 {
+                    bail!("Invalid shutdown warning length: {}", len);
+                }
+
+                let mut seconds = [0; 8];
+                seconds.copy_from_slice(&buf);
+
+                Ok(Some(Self::ShutdownWarning(u64::from_be_bytes(seconds))))
+            }
+            _ => bail!("Invalid message type: {}", message_type),
+        }
+    }
+
+    pub async fn write<W>(&self, writer: &mut W) -> anyhow::Result<()>
+    where
+        W: AsyncWrite + Unpin,
+    {
+        let (message_type, payload) = match self {
+            Self::Echo(message) => (MESSAGE_ECHO, message.as_bytes().to_vec()),
+            Self::ShutdownWarning(seconds) => {
+                (MESSAGE_SHUTDOWN_WARNING, seconds.to_be_bytes().to_vec())
+            }
+        };
+
+        if payload.len() > u16::MAX as usize {
+            bail!("Message too large: {} bytes", payload.len());
+        }
+
+        writer.write_u8(message_type).await?;
+        writer.write_u16(payload.len() as u16).await?;
+        writer.write_all(&payload).await?;
+
+        Ok(())
+    }
+}
+
+/// Reads the connection handshake (the player session id)
+///
+/// An empty player session id is a health probe rather than a player
+pub async fn read_player_session_id<R>(reader: &mut R) -> anyhow::Result<String>
+where
+    R: AsyncRead + Unpin,
+{
+    let len = reader.read_u8().await? as usize;
+
+    let mut buf = vec![0; len];
+
+    let mut t = 0;
+    while t < len {
+        let n = reader.read(&mut buf[t..]).await?;
+        if n == 0 {
+            bail!("Connection closed!");
+        }
+
+        t += n;
+    }
+
+    Ok(std::str::from_utf8(&buf)?.to_string())
+}
+
+/// Writes the connection handshake (the player session id)
+pub async fn write_player_session_id<W>(
+    writer: &mut W,
+    player_session_id: impl AsRef<str>,
+) -> anyhow::Result<()>
+where
+    W: AsyncWrite + Unpin,
+{
+    let player_session_id = player_session_id.as_ref();
+    if player_session_id.len() > u8::MAX as usize {
+        bail!("Player session id too long: {}", player_session_id);
+    }
+
+    writer.write_u8(player_session_id.len() as u8).await?;
+    writer.write_all(player_session_id.as_bytes()).await?;
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    async fn round_trip(message: Message) -> Vec<u8> {
+        let mut buf = Vec::new();
+        message.write(&mut buf).await.unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_eq!(Message::read(&mut reader).await.unwrap(), Some(message));
+        assert!(reader.is_empty());
+
+        buf
+    }
+
+    #[tokio::test]
+    async fn messages_round_trip() {
+        assert_eq!(
+            round_trip(Message::Echo("hi".to_owned())).await,
+            [MESSAGE_ECHO, 0, 2, b'h', b'i']
+        );
+        assert_eq!(
+            round_trip(Message::ShutdownWarning(30)).await,
+            [MESSAGE_SHUTDOWN_WARNING, 0, 8, 0, 0, 0, 0, 0, 0, 0, 30]
+        );
+        round_trip(Message::Echo(String::new())).await;
+        round_trip(Message::Echo("x".repeat(u16::MAX as usize))).await;
+    }
+
+    #[tokio::test]
+    async fn messages_follow_each_other() {
+        let mut buf = Vec::new();
+        Message::Echo("one".to_owned())
+            .write(&mut buf)
+            .await
+            .unwrap();
+        Message::ShutdownWarning(5).write(&mut buf).await.unwrap();
+
+        let mut reader = buf.as_slice();
+        assert_eq!(
+            Message::read(&mut reader).await.unwrap(),
+            Some(Message::Echo("one".to_owned()))
+        );
+        assert_eq!(
+            Message::read(&mut reader).await.unwrap(),
+            Some(Message::ShutdownWarning(5))
+        );
+        // closed between messages
+        assert_eq!(Message::read(&mut reader).await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn bad_frames_rejected() {
+        for frame in [
+            // unknown type
+            vec![7, 0, 0],
+            // shutdown warnings are 8 bytes
+            vec![MESSAGE_SHUTDOWN_WARNING, 0, 1, 30],
+            // closed mid message
+            vec![MESSAGE_ECHO, 0, 5, b'h'],
+            // not UTF-8
+            vec![MESSAGE_ECHO, 0, 1, 0xff],
+        ] {
+            assert!(
+                Message::read(&mut frame.as_slice()).await.is_err(),
+                "{:?}",
+                frame
+            );
+        }
+
+        let mut buf = Vec::new();
+        assert!(Message::Echo("x".repeat(u16::MAX as usize + 1))
+            .write(&mut buf)
+            .await
+            .is_err());
+        assert!(buf.is_empty());
+    }
+
+    #[tokio::test]
+    async fn handshake_round_trip() {
+        for player_session_id in ["psess-1", ""] {
+            let mut buf = Vec::new();
+            write_player_session_id(&mut buf, player_session_id)
+                .await
+                .unwrap();
+            assert_eq!(buf[0] as usize, player_session_id.len());
+
+            assert_eq!(
+                read_player_session_id(&mut buf.as_slice()).await.unwrap(),
+                player_session_id
+            );
+        }
+
+        assert!(read_player_session_id(&mut [5, b'p'].as_slice())
+            .await
+            .is_err());
+
+        let mut buf = Vec::new();
+        assert!(write_player_session_id(&mut buf, "x".repeat(256))
+            .await
+            .is_err());
+    }
+}
@@ -0,0 +1,324 @@
+use std::str::FromStr;
+
+use anyhow::bail;
+use futures_util::future::join_all;
+use tokio::{net::UdpSocket, time};
+use tracing::{debug, info, warn};
+
+use crate::gamelift::PlayerDetails;
+
+/// GameLift UDP ping beacons listen on this port in every region
+const GAMELIFT_BEACON_PORT: u16 = 7770;
+
+/// Regions probed when no beacons are configured
+const DEFAULT_REGIONS: &[&str] = &[
+    "us-west-2",
+    "us-east-1",
+    "eu-west-1",
+    "eu-central-1",
+    "ap-northeast-1",
+    "ap-southeast-2",
+    "sa-east-1",
+];
+
+/// How many pings to send to each beacon
+pub const DEFAULT_PING_SAMPLES: u32 = 3;
+
+/// How long to wait for each ping
+const PING_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// A region and the UDP beacon to measure it by (region=host:port)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BeaconArg {
+    pub region: String,
+    pub addr: String,
+}
+
+impl FromStr for BeaconArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.split_once('=') {
+            Some((region, addr)) if !region.is_empty() && addr.contains(':') => Ok(Self {
+                region: region.to_owned(),
+                addr: addr.to_owned(),
+            }),
+            _ => Err(format!("invalid beacon '{}', expected region=host:port", s)),
+        }
+    }
+}
+
+fn default_beacons() -> Vec<BeaconArg> {
+    DEFAULT_REGIONS
+        .iter()
+        .map(|region| BeaconArg {
+            region: region.to_string(),
+            addr: format!("gamelift-ping.{}.api.aws:{}", region, GAMELIFT_BEACON_PORT),
+        })
+        .collect()
+}
+
+#[derive(Debug, Clone)]
+pub struct RegionLatency {
+    pub region: String,
+    pub addr: String,
+
+    /// average round trip time, None if no pings came back
+    pub latency: Option<time::Duration>,
+    pub lost: u32,
+}
+
+impl RegionLatency {
+    fn latency_in_ms(&self) -> Option<i32> {
+        self.latency.map(|latency| latency.as_millis() as i32)
+    }
+}
+
+async fn ping(socket: &UdpSocket, sequence: u64) -> anyhow::Result<time::Duration> {
+    let start = time::Instant::now();
+    socket.send(&sequence.to_be_bytes()).await?;
+
+    let mut buf = [0; 64];
+    loop {
+        let len = time::timeout_at(start + PING_TIMEOUT, socket.recv(&mut buf)).await??;
+
+        // ignore late replies to earlier pings
+        if len == 8 && buf[..8] == sequence.to_be_bytes() {
+            return Ok(start.elapsed());
+        }
+    }
+}
+
+async fn probe(beacon: &BeaconArg, samples: u32) -> RegionLatency {
+    let mut result = RegionLatency {
+        region: beacon.region.clone(),
+        addr: beacon.addr.clone(),
+        latency: None,
+        lost: samples,
+    };
+
+    let socket = match UdpSocket::bind("0.0.0.0:0").await {
+        Ok(socket) => socket,
+        Err(err) => {
+            warn!("Failed to bind ping socket: {}", err);
+            return result;
+        }
+    };
+
+    if let Err(err) = socket.connect(&beacon.addr).await {
+        warn!("Failed to resolve beacon {}: {}", beacon.addr, err);
+        return result;
+    }
+
+    let mut total = time::Duration::ZERO;
+    let mut received = 0;
+    for sequence in 0..samples {
+        match ping(&socket, sequence as u64).await {
+            Ok(rtt) => {
+                total += rtt;
+                received += 1;
+            }
+            Err(err) => debug!("Ping {} to {} failed: {}", sequence, beacon.addr, err),
+        }
+    }
+
+    if received > 0 {
+        result.latency = Some(total / received);
+        result.lost = samples - received;
+    }
+
+    result
+}
+
+/// Pings every beacon (or the default GameLift beacons), sorted by latency
+pub async fn probe_regions(beacons: &[BeaconArg], samples: u32) -> Vec<RegionLatency> {
+    let beacons = if beacons.is_empty() {
+        default_beacons()
+    } else {
+        beacons.to_vec()
+    };
+
+    info!("Pinging {} regions ...", beacons.len());
+
+    let mut results = join_all(beacons.iter().map(|beacon| probe(beacon, samples))).await;
+    results.sort_by_key(|result| result.latency.unwrap_or(time::Duration::MAX));
+
+    results
+}
+
+pub fn print_regions(results: &[RegionLatency]) {
+    info!("{:<16} {:>10} {:>6}  beacon", "region", "latency", "lost");
+    for result in results {
+        let latency = match result.latency_in_ms() {
+            Some(latency_in_ms) => format!("{}ms", latency_in_ms),
+            None => "-".to_owned(),
+        };
+
+        info!(
+            "{:<16} {:>10} {:>6}  {}",
+            result.region, latency, result.lost, result.addr
+        );
+    }
+}
+
+/// Probes the regions, attaches the latencies to the players and returns the best region
+///
+/// Latencies the players already have are kept
+pub async fn choose_region(
+    beacons: &[BeaconArg],
+    samples: u32,
+    players: &mut [PlayerDetails],
+) -> anyhow::Result<String> {
+    let results = probe_regions(beacons, samples).await;
+    print_regions(&results);
+
+    for result in &results {
+        if let Some(latency_in_ms) = result.latency_in_ms() {
+            for player in players.iter_mut() {
+                player
+                    .latency_in_ms
+                    .entry(result.region.clone())
+                    .or_insert(latency_in_ms);
+            }
+        }
+    }
+
+    match results.first() {
+        Some(result) if result.latency.is_some() => {
+            info!("Using region {}", result.region);
+            Ok(result.region.clone())
+        }
+        _ => bail!("No regions could be reached"),
+    }
+}
+
+/// Echoes UDP pings back, so a dedicated server can stand in for a GameLift beacon
+pub async fn run(addr: impl AsRef<str>) -> anyhow::Result<()> {
+    let socket = UdpSocket::bind(addr.as_ref()).await?;
+    info!("Ping beacon listening on {}", socket.local_addr()?);
+
+    let mut buf = [0; 64];
+    loop {
+        let (len, peer) = socket.recv_from(&mut buf).await?;
+        if let Err(err) = socket.send_to(&buf[..len], peer).await {
+            warn!("Failed to answer ping from {}: {}", peer, err);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn free_port() -> u16 {
+        std::net::UdpSocket::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn beacon(region: &str, port: u16) -> BeaconArg {
+        format!("{}=127.0.0.1:{}", region, port).parse().unwrap()
+    }
+
+    /// A beacon that answers each ping after `delay`
+    async fn slow_beacon(delay: time::Duration) -> u16 {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            let mut buf = [0; 64];
+            loop {
+                let (len, peer) = socket.recv_from(&mut buf).await.unwrap();
+                time::sleep(delay).await;
+                socket.send_to(&buf[..len], peer).await.unwrap();
+            }
+        });
+
+        port
+    }
+
+    #[test]
+    fn parses_beacons() {
+        assert_eq!(
+            "us-west-2=127.0.0.1:7770".parse(),
+            Ok(BeaconArg {
+                region: "us-west-2".to_owned(),
+                addr: "127.0.0.1:7770".to_owned()
+            })
+        );
+
+        for arg in ["us-west-2", "=127.0.0.1:7770", "us-west-2=localhost"] {
+            assert!(arg.parse::<BeaconArg>().is_err(), "{} parsed", arg);
+        }
+    }
+
+    #[tokio::test]
+    async fn chooses_the_closest_region() {
+        let port = free_port();
+        let server = tokio::spawn(run(format!("127.0.0.1:{}", port)));
+        let slow_port = slow_beacon(time::Duration::from_millis(100)).await;
+        // bound but never answers
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let silent_port = silent.local_addr().unwrap().port();
+
+        let beacons = [
+            beacon("us-east-1", slow_port),
+            beacon("eu-west-1", silent_port),
+            beacon("us-west-2", port),
+        ];
+
+        // the beacon may not be listening yet
+        time::timeout(time::Duration::from_secs(5), async {
+            while probe(&beacons[2], 1).await.latency.is_none() {}
+        })
+        .await
+        .unwrap();
+
+        let results = probe_regions(&beacons, 2).await;
+        let regions = results
+            .iter()
+            .map(|result| result.region.as_str())
+            .collect::<Vec<_>>();
+        assert_eq!(regions, ["us-west-2", "us-east-1", "eu-west-1"]);
+        assert_eq!(results[0].lost, 0);
+        assert!(results[1].latency.unwrap() >= time::Duration::from_millis(100));
+        assert_eq!(results[2].latency, None);
+        assert_eq!(results[2].lost, 2);
+
+        let mut players = [
+            PlayerDetails {
+                player_id: "player-1".to_owned(),
+                ..Default::default()
+            },
+            PlayerDetails {
+                player_id: "player-2".to_owned(),
+                latency_in_ms: [("us-west-2".to_owned(), 250)].into(),
+                ..Default::default()
+            },
+        ];
+        let region = choose_region(&beacons, 1, &mut players).await.unwrap();
+        assert_eq!(region, "us-west-2");
+
+        assert!(players[0].latency_in_ms["us-west-2"] < 100);
+        assert!(players[0].latency_in_ms["us-east-1"] >= 100);
+        assert!(!players[0].latency_in_ms.contains_key("eu-west-1"));
+        // latencies the player came with are kept
+        assert_eq!(players[1].latency_in_ms["us-west-2"], 250);
+        assert!(players[1].latency_in_ms["us-east-1"] >= 100);
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn fails_without_reachable_regions() {
+        let silent = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let beacons = [beacon("eu-west-1", silent.local_addr().unwrap().port())];
+
+        let mut players = [PlayerDetails::default()];
+        let err = choose_region(&beacons, 1, &mut players).await.unwrap_err();
+        assert_eq!(err.to_string(), "No regions could be reached");
+        assert!(players[0].latency_in_ms.is_empty());
+    }
+}
@@ -1,38 +1,176 @@
+use std::future::Future;
+
 use anyhow::bail;
-use aws_sdk_gamelift::model::{
-    DesiredPlayerSession, GameSession, GameSessionPlacement, GameSessionPlacementState,
-    MatchmakingConfigurationStatus, MatchmakingTicket, Player, PlayerSession,
-};
+use derive_more::Display;
+use futures_util::future::try_join_all;
 use tokio::{
-    io::{stdin, AsyncBufReadExt, AsyncReadExt, AsyncWriteExt, BufReader},
-    net::TcpStream,
+    io::{stdin, AsyncBufRead, AsyncBufReadExt, BufReader, Lines, Stdin},
+    net::{tcp::OwnedWriteHalf, TcpStream},
+    sync::{broadcast, mpsc, watch},
+    time,
 };
-use tracing::info;
+use tracing::{info, warn};
 use uuid::Uuid;
 
-use crate::gamelift::new_client;
+use echo_protocol::{store::TicketStore, Message};
+
+use crate::gamelift::{
+    self, GameLiftControl, GameSession, MatchConnection, Placement, PlacementStatus, PlayerDetails,
+    PlayerSessionInfo, SearchTarget, Ticket, TicketPlayer, TicketStatus,
+};
+use crate::relay::RelayClient;
+use crate::state::{PendingPlacement, PendingTicket, StateFile};
+use crate::util;
+
+/// How to poll placements and matchmaking tickets
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PollOptions {
+    /// interval before the first poll, doubles with each poll after that
+    pub initial_interval: time::Duration,
+
+    /// the interval stops doubling here
+    pub max_interval: time::Duration,
+
+    /// the placement or ticket is stopped if it hasn't finished after this long
+    pub deadline: time::Duration,
+}
+
+/// Why polling stopped before the placement or ticket finished
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Display)]
+enum StopReason {
+    #[display(fmt = "timed out")]
+    Deadline,
+
+    #[display(fmt = "cancelled")]
+    Interrupted,
+}
+
+/// The placement or ticket is over without a result, so there's nothing left to resume
+#[derive(Debug, Display)]
+#[display(fmt = "{}", _0)]
+struct RequestEnded(String);
+
+impl std::error::Error for RequestEnded {}
+
+/// Whether the placement or ticket being waited on no longer needs to be kept
+fn finished<T>(result: &anyhow::Result<T>) -> bool {
+    match result {
+        Ok(_) => true,
+        Err(err) => err.is::<RequestEnded>(),
+    }
+}
+
+struct Poller {
+    options: PollOptions,
+    interval: time::Duration,
+    deadline: time::Instant,
+    interrupt: watch::Receiver<bool>,
+}
+
+impl Poller {
+    fn new(options: PollOptions) -> Self {
+        Self::with_interrupt(options, util::interrupt())
+    }
+
+    fn with_interrupt(options: PollOptions, interrupt: watch::Receiver<bool>) -> Self {
+        Self {
+            options,
+            interval: options.initial_interval,
+            deadline: time::Instant::now() + options.deadline,
+            interrupt,
+        }
+    }
+
+    /// Backs off the interval, returning the current one with jitter applied
+    fn next_interval(&mut self) -> time::Duration {
+        let interval = self.interval;
+        self.interval = (interval * 2).min(self.options.max_interval);
+
+        // equal jitter, so polls from many clients don't line up
+        let half = interval / 2;
+        half + half.mul_f64(rand::random::<f64>())
+    }
+
+    /// Runs the future unless the deadline passes or Ctrl-C is pressed first
+    async fn interruptible<F>(&self, future: F) -> Result<F::Output, StopReason>
+    where
+        F: Future,
+    {
+        tokio::select! {
+            output = future => Ok(output),
+            _ = time::sleep_until(self.deadline) => Err(StopReason::Deadline),
+            _ = util::wait_for_interrupt(self.interrupt.clone()) => Err(StopReason::Interrupted),
+        }
+    }
+
+    /// Waits until it's time to poll again
+    async fn wait(&mut self) -> Result<(), StopReason> {
+        let interval = self.next_interval();
+        self.interruptible(time::sleep(interval)).await
+    }
+}
 
 #[derive(Debug)]
 enum Event {
     Input(String),
-    Message(String),
+    Message(Message),
 }
 
-async fn handle_event(event: Event, stream: &mut TcpStream) -> anyhow::Result<()> {
+async fn handle_event(
+    event: Event,
+    player_id: &str,
+    writer: &mut OwnedWriteHalf,
+) -> anyhow::Result<()> {
     match event {
-        Event::Input(line) => stream.write_all(line.as_bytes()).await?,
-        Event::Message(message) => {
-            info!("Read: {}", message);
+        Event::Input(line) => Message::Echo(line).write(writer).await?,
+        Event::Message(Message::Echo(message)) => {
+            info!("{} read: {}", player_id, message);
+        }
+        Event::Message(Message::ShutdownWarning(seconds)) => {
+            warn!("Server shutting down in {} seconds!", seconds);
         }
     }
 
     Ok(())
 }
 
+/// Lines of stdin
+///
+/// Only one is made per command, a second buffer would lose whatever the first read ahead
+type InputLines = Lines<BufReader<Stdin>>;
+
+fn stdin_lines() -> InputLines {
+    BufReader::new(stdin()).lines()
+}
+
+/// Reads stdin on its own task and sends every line to each of the receivers
+///
+/// This lets every member of a party echo the same input
+fn spawn_input(mut input: InputLines, receivers: usize) -> Vec<broadcast::Receiver<String>> {
+    let (sender, receiver) = broadcast::channel(16);
+
+    let mut receivers = (1..receivers)
+        .map(|_| sender.subscribe())
+        .collect::<Vec<_>>();
+    receivers.push(receiver);
+
+    tokio::spawn(async move {
+        while let Ok(Some(line)) = input.next_line().await {
+            if sender.send(line).is_err() {
+                break;
+            }
+        }
+    });
+
+    receivers
+}
+
 async fn connect_server(
     addr: impl AsRef<str>,
     player_id: impl AsRef<str>,
     player_session_id: impl AsRef<str>,
+    mut input: broadcast::Receiver<String>,
+    interrupt: watch::Receiver<bool>,
 ) -> anyhow::Result<()> {
     let player_id = player_id.as_ref();
     let player_session_id = player_session_id.as_ref();
@@ -43,183 +181,363 @@ async fn connect_server(
         addr.as_ref(),
         player_session_id
     );
-    let mut stream = TcpStream::connect(addr.as_ref()).await?;
+    let stream = TcpStream::connect(addr.as_ref()).await?;
     info!("Success!");
 
+    let (mut reader, mut writer) = stream.into_split();
+
     // TODO: send player id
 
     // first thing we send is our player session id
-    stream.write_u8(player_session_id.len() as u8).await?;
-    stream.write_all(player_session_id.as_bytes()).await?;
+    echo_protocol::write_player_session_id(&mut writer, player_session_id).await?;
+
+    // reading a message isn't cancel safe, so read them on their own task
+    let (message_sender, mut message_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let message = Message::read(&mut reader).await;
+            let done = !matches!(message, Ok(Some(_)));
+            if message_sender.send(message).is_err() || done {
+                break;
+            }
+        }
+    });
 
-    let mut buf = [0; 1024];
-    let mut stdin = BufReader::new(stdin()).lines();
     loop {
         let event = tokio::select! {
-            line = stdin.next_line() => {
-                match line? {
-                    Some(line) => Event::Input(line),
-                    None => return Ok(()),
+            line = input.recv() => {
+                match line {
+                    Ok(line) => Event::Input(line),
+                    Err(broadcast::error::RecvError::Lagged(skipped)) => {
+                        warn!("{} skipped {} lines of input", player_id, skipped);
+                        continue;
+                    }
+                    Err(broadcast::error::RecvError::Closed) => return Ok(()),
                 }
             },
-            n = stream.read(&mut buf) => {
-                match n? {
-                    0 => bail!("Server disconnected!"),
-                    n => Event::Message(std::str::from_utf8(&buf[0..n])?.to_string()),
+            message = message_receiver.recv() => {
+                match message {
+                    Some(Ok(Some(message))) => Event::Message(message),
+                    Some(Ok(None)) | None => bail!("Server disconnected!"),
+                    Some(Err(err)) => return Err(err),
                 }
             },
+            // Ctrl-C is handled for the whole process once something listens for it
+            _ = util::wait_for_interrupt(interrupt.clone()) => {
+                info!("{} disconnecting", player_id);
+                return Ok(());
+            },
             else => bail!("Unhandled event!"),
         };
 
-        handle_event(event, &mut stream).await?;
+        handle_event(event, player_id, &mut writer).await?;
     }
 }
 
-pub async fn connect(addr: impl AsRef<str>, player_id: impl AsRef<str>) -> anyhow::Result<()> {
-    connect_server(addr, &player_id, &player_id).await
+/// Connects every player to its own session, sharing stdin between them
+async fn connect_player_sessions(
+    player_sessions: Vec<PlayerSessionInfo>,
+    input: InputLines,
+) -> anyhow::Result<()> {
+    let inputs = spawn_input(input, player_sessions.len());
+
+    try_join_all(player_sessions.into_iter().zip(inputs).map(
+        |(player_session, input)| async move {
+            connect_server(
+                player_session.connect_addr(),
+                &player_session.player_id,
+                &player_session.player_session_id,
+                input,
+                util::interrupt(),
+            )
+            .await
+        },
+    ))
+    .await?;
+
+    Ok(())
 }
 
-fn print_game_session(game_session: &GameSession) {
-    info!("Game Session: {:?}", game_session.game_session_id);
+pub async fn connect(addr: impl AsRef<str>, player_id: impl AsRef<str>) -> anyhow::Result<()> {
+    let input = spawn_input(stdin_lines(), 1).remove(0);
+    connect_server(addr, &player_id, &player_id, input, util::interrupt()).await
 }
 
 pub async fn create_gamelift_local(
-    region: impl Into<String>,
-    fleet_id: impl AsRef<str>,
-    player_id: impl AsRef<str>,
+    control: &dyn GameLiftControl,
+    fleet_id: impl Into<String>,
+    player_id: impl Into<String>,
 ) -> anyhow::Result<()> {
     info!("Creating GameLift server (local) ...");
 
-    let region = region.into();
+    let game_session_id = control.create_game_session(fleet_id.into(), 10).await?;
+    info!("Game Session: {}", game_session_id);
 
-    let client = new_client(region.clone(), true).await;
+    connect_gamelift(control, player_id, game_session_id).await
+}
 
-    let output = client
-        .create_game_session()
-        .fleet_id(fleet_id.as_ref().to_owned())
-        .maximum_player_session_count(10)
-        .send()
-        .await?;
+fn print_placement(placement: &Placement) {
+    info!("Placement ID: {}", placement.placement_id);
+    info!("Status: {:?}", placement.status);
+}
 
-    let game_session = output.game_session.unwrap();
-    print_game_session(&game_session);
+async fn stop_placement(control: &dyn GameLiftControl, placement_id: &str) {
+    info!("Stopping placement {} ...", placement_id);
+
+    if let Err(err) = control
+        .stop_game_session_placement(placement_id.to_owned())
+        .await
+    {
+        warn!("Failed to stop placement {}: {}", placement_id, err);
+    }
+}
 
-    let game_session_id = game_session.game_session_id.unwrap();
+/// Where placement updates come from while waiting for a game session
+pub enum PlacementSource<'a> {
+    /// DescribeGameSessionPlacement
+    Describe,
 
-    connect_gamelift(region, player_id, game_session_id, true).await
+    /// a notification relay, which answers once the placement event arrives
+    Notify(&'a RelayClient),
 }
 
-fn print_game_session_placement(game_session_placement: &GameSessionPlacement) {
-    info!("Placement ID: {:?}", game_session_placement.placement_id);
-    info!("Status: {:?}", game_session_placement.status);
+impl PlacementSource<'_> {
+    /// Waits for the next placement update
+    async fn next_placement(
+        &self,
+        control: &dyn GameLiftControl,
+        poller: &mut Poller,
+        placement_id: &str,
+    ) -> Result<anyhow::Result<Placement>, StopReason> {
+        let relay = match self {
+            PlacementSource::Describe => {
+                poller.wait().await?;
+                return Ok(control
+                    .describe_game_session_placement(placement_id.to_owned())
+                    .await);
+            }
+            PlacementSource::Notify(relay) => relay,
+        };
+
+        loop {
+            match poller
+                .interruptible(relay.wait_for_placement(placement_id))
+                .await?
+            {
+                Ok(Some(state)) => return Ok(gamelift::placement_from_state(state)),
+                // no event yet, wait again
+                Ok(None) => {}
+                Err(err) => return Ok(Err(err)),
+            }
+        }
+    }
 }
 
+/// Polls a placement until it is fulfilled, returns the placed game session id
+///
+/// The placement is stopped if the deadline passes or the wait is interrupted
+async fn wait_for_placement(
+    control: &dyn GameLiftControl,
+    source: &PlacementSource<'_>,
+    placement_id: impl Into<String>,
+    poll: PollOptions,
+) -> anyhow::Result<String> {
+    let placement_id = placement_id.into();
+
+    let mut poller = Poller::new(poll);
+    loop {
+        let placement = match source
+            .next_placement(control, &mut poller, &placement_id)
+            .await
+        {
+            Ok(placement) => placement?,
+            Err(reason) => {
+                stop_placement(control, &placement_id).await;
+                bail!(RequestEnded(format!("Placement {}", reason)));
+            }
+        };
+
+        match placement.status {
+            PlacementStatus::Pending => print_placement(&placement),
+            PlacementStatus::Fulfilled { game_session_id } => return Ok(game_session_id),
+            PlacementStatus::Cancelled
+            | PlacementStatus::TimedOut
+            | PlacementStatus::Failed
+            | PlacementStatus::Unknown(_) => bail!(RequestEnded(format!(
+                "Placement failed: {:?}",
+                placement.status
+            ))),
+        }
+    }
+}
+
+/// Places a new game session from the queue, then connects to it
+///
+/// The placement is kept in the state file until it finishes, so it can be resumed
 pub async fn create_gamelift(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
     region: impl Into<String>,
     queue_name: impl Into<String>,
-    player_id: impl Into<String>,
+    player: PlayerDetails,
+    poll: PollOptions,
+    source: &PlacementSource<'_>,
 ) -> anyhow::Result<()> {
-    info!("Creating GameLift server ...");
-
-    let region = region.into();
-    let player_id = player_id.into();
+    if let Some(placement) = state.load()?.placement {
+        bail!(
+            "Placement {} is still pending, resume it with --resume or cancel it with --cancel",
+            placement.placement_id
+        );
+    }
 
-    let placement_id = Uuid::new_v4().to_string();
+    info!("Creating GameLift server ...");
 
-    let client = new_client(region.clone(), false).await;
+    let player_id = player.player_id.clone();
 
-    let output = client
-        .start_game_session_placement()
-        .game_session_queue_name(queue_name)
-        .placement_id(placement_id.clone())
-        .desired_player_sessions(
-            DesiredPlayerSession::builder()
-                .player_id(player_id.clone())
-                .build(),
+    let placement = control
+        .start_game_session_placement(
+            queue_name.into(),
+            Uuid::new_v4().to_string(),
+            10,
+            vec![player],
         )
-        .maximum_player_session_count(10)
-        .send()
         .await?;
+    print_placement(&placement);
 
-    let game_session_placement = output.game_session_placement.unwrap();
-    print_game_session_placement(&game_session_placement);
+    let pending = PendingPlacement {
+        placement_id: placement.placement_id,
+        player_id,
+        region: region.into(),
+    };
+    state.update(|state| state.placement = Some(pending.clone()))?;
 
-    let game_session_id;
+    finish_placement(control, state, pending, poll, source).await
+}
 
+/// Picks up polling a placement from an earlier run
+pub async fn resume_create_gamelift(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    placement: PendingPlacement,
+    poll: PollOptions,
+    source: &PlacementSource<'_>,
+) -> anyhow::Result<()> {
+    info!("Resuming placement {} ...", placement.placement_id);
+
+    finish_placement(control, state, placement, poll, source).await
+}
+
+/// Stops a placement from an earlier run and forgets it
+pub async fn cancel_create_gamelift(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    placement: PendingPlacement,
+) -> anyhow::Result<()> {
+    stop_placement(control, &placement.placement_id).await;
+
+    state.update(|state| state.placement = None)
+}
+
+async fn finish_placement(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    placement: PendingPlacement,
+    poll: PollOptions,
+    source: &PlacementSource<'_>,
+) -> anyhow::Result<()> {
     // poll until the session is placed or timeout
-    loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-        let output = client
-            .describe_game_session_placement()
-            .placement_id(placement_id.clone())
-            .send()
-            .await?;
-
-        let game_session_placement = output.game_session_placement.as_ref().unwrap();
-
-        let status = game_session_placement.status.as_ref().unwrap();
-        match status {
-            GameSessionPlacementState::Cancelled
-            | GameSessionPlacementState::Failed
-            | GameSessionPlacementState::TimedOut
-            | GameSessionPlacementState::Unknown(_) => bail!("Find failed: {:?}", status),
-            GameSessionPlacementState::Pending => {
-                print_game_session_placement(game_session_placement)
-            }
-            GameSessionPlacementState::Fulfilled => {
-                game_session_id = game_session_placement.game_session_id.clone();
+    let result = wait_for_placement(control, source, placement.placement_id, poll).await;
 
-                break;
-            }
-            _ => unreachable!(),
-        }
+    // transient errors keep the placement around to resume later
+    if finished(&result) {
+        state.update(|state| state.placement = None)?;
+    }
+    let game_session_id = result?;
+
+    info!("Session placed: {}", game_session_id);
+
+    connect_gamelift(control, placement.player_id, game_session_id).await
+}
+
+fn print_game_sessions(game_sessions: &[GameSession]) {
+    info!(
+        "{:<40} {:<16} {:<10} {:>7}  properties",
+        "game session", "name", "status", "players"
+    );
+    for game_session in game_sessions {
+        let mut properties = game_session
+            .game_properties
+            .iter()
+            .map(|(key, value)| format!("{}={}", key, value))
+            .collect::<Vec<_>>();
+        properties.sort();
+
+        info!(
+            "{:<40} {:<16} {:<10} {:>7}  {}",
+            game_session.game_session_id,
+            game_session.name.as_deref().unwrap_or("-"),
+            game_session.status,
+            format!(
+                "{}/{}",
+                game_session.current_player_session_count,
+                game_session.maximum_player_session_count
+            ),
+            properties.join(",")
+        );
     }
+}
 
-    info!("Session placed: {:?}", game_session_id);
+/// Searches for game sessions and lists them, optionally joining the first one with room
+pub async fn search(
+    control: &dyn GameLiftControl,
+    target: SearchTarget,
+    filter: Option<String>,
+    sort: Option<String>,
+    limit: i32,
+    join_player_id: Option<String>,
+) -> anyhow::Result<()> {
+    info!("Searching {:?} for game sessions ...", target);
 
-    let game_session_id = game_session_id.unwrap();
+    let game_sessions = control
+        .search_game_sessions(target, filter, sort, limit)
+        .await?;
+    print_game_sessions(&game_sessions);
+
+    let player_id = match join_player_id {
+        Some(player_id) => player_id,
+        None => return Ok(()),
+    };
 
-    connect_gamelift(region, player_id, game_session_id, false).await
+    // results are in sort order, so the first one with room is the best
+    let game_session = match game_sessions.into_iter().find(GameSession::has_room) {
+        Some(game_session) => game_session,
+        None => bail!("No game sessions with room to join"),
+    };
+
+    connect_gamelift(control, player_id, game_session.game_session_id).await
 }
 
-fn print_player_session(player_session: &PlayerSession) {
-    info!("Player Session: {:?}", player_session.player_session_id);
+fn print_player_session(player_session: &PlayerSessionInfo) {
+    info!("Player Session: {}", player_session.player_session_id);
 }
 
 pub async fn connect_gamelift(
-    region: impl Into<String>,
-    player_id: impl AsRef<str>,
-    session_id: impl AsRef<str>,
-    local: bool,
+    control: &dyn GameLiftControl,
+    player_id: impl Into<String>,
+    session_id: impl Into<String>,
 ) -> anyhow::Result<()> {
     info!("Joining GameLift server ...");
 
-    let client = new_client(region, local).await;
-
-    let output = client
-        .create_player_session()
-        .game_session_id(session_id.as_ref().to_owned())
-        .player_id(player_id.as_ref().to_owned())
-        .send()
+    let player_session = control
+        .create_player_session(session_id.into(), player_id.into())
         .await?;
-
-    let player_session = output.player_session.unwrap();
     print_player_session(&player_session);
 
-    let player_session_id = player_session.player_session_id.unwrap();
-
-    let connect_addr = format!(
-        "{}:{}",
-        player_session.ip_address.unwrap(),
-        player_session.port.unwrap()
-    );
-
-    connect_server(connect_addr, player_id, player_session_id).await
+    connect_player_sessions(vec![player_session], stdin_lines()).await
 }
 
-fn print_ticket(ticket: &MatchmakingTicket) {
-    info!("Ticket ID: {:?}", ticket.ticket_id);
+fn print_ticket(ticket: &Ticket) {
+    info!("Ticket ID: {}", ticket.ticket_id);
     info!(
         "Status: {:?} (reason: {:?}) - {:?}",
         ticket.status, ticket.status_reason, ticket.status_message
@@ -227,70 +545,682 @@ fn print_ticket(ticket: &MatchmakingTicket) {
     info!("Estimated wait: {:?}", ticket.estimated_wait_time);
 }
 
-pub async fn find(region: impl Into<String>) -> anyhow::Result<()> {
-    info!("Searching for server ...");
+async fn stop_matchmaking(control: &dyn GameLiftControl, ticket_id: &str) {
+    info!("Stopping matchmaking {} ...", ticket_id);
 
-    let player_id = Uuid::new_v4().to_string();
+    if let Err(err) = control.stop_matchmaking(ticket_id.to_owned()).await {
+        warn!("Failed to stop matchmaking {}: {}", ticket_id, err);
+    }
+}
 
-    let client = new_client(region, false).await;
+fn print_proposed_match(ticket: &Ticket) {
+    info!("Proposed match for ticket {}:", ticket.ticket_id);
+    for player in &ticket.players {
+        info!("  {} (team: {:?})", player.player_id, player.team);
+    }
+}
 
-    let output = client
-        .start_matchmaking()
-        .configuration_name("echo")
-        .players(Player::builder().player_id(&player_id).build())
-        .send()
-        .await?;
+/// Asks whether to accept a proposed match
+async fn prompt_acceptance(
+    input: &mut Lines<impl AsyncBufRead + Unpin>,
+    auto_accept: bool,
+) -> anyhow::Result<bool> {
+    if auto_accept {
+        info!("Accepting match");
+        return Ok(true);
+    }
 
-    let ticket = output.matchmaking_ticket.unwrap();
-    print_ticket(&ticket);
+    info!("Accept match? [y/N]");
+
+    let line = input.next_line().await?;
+    Ok(matches!(
+        line.as_deref().map(str::trim),
+        Some("y") | Some("Y") | Some("yes")
+    ))
+}
 
-    let connection_info;
+/// Where ticket updates come from while waiting for a match
+pub enum TicketSource<'a> {
+    /// DescribeMatchmaking, which AWS throttles
+    Describe,
 
-    // poll until we find a match or timeout
-    let ticket_id = ticket.ticket_id.unwrap();
+    /// the store echo-notifications keeps ticket state in
+    Store(&'a dyn TicketStore),
+
+    /// a notification relay, which answers as soon as the ticket changes
+    Notify(&'a RelayClient),
+}
+
+impl TicketSource<'_> {
+    /// Waits for the next ticket update
+    ///
+    /// `after` is the update time of the last ticket a relay returned, so it only answers
+    /// once there's a newer one
+    async fn next_ticket(
+        &self,
+        control: &dyn GameLiftControl,
+        poller: &mut Poller,
+        ticket_id: &str,
+        player_ids: &[String],
+        after: &mut Option<i64>,
+    ) -> Result<anyhow::Result<Ticket>, StopReason> {
+        let relay = match self {
+            TicketSource::Notify(relay) => relay,
+            _ => {
+                poller.wait().await?;
+                return Ok(self.ticket(control, ticket_id, player_ids).await);
+            }
+        };
+
+        loop {
+            match poller
+                .interruptible(relay.wait_for_ticket(ticket_id, *after))
+                .await?
+            {
+                Ok(Some(state)) => {
+                    *after = Some(state.updated_at.timestamp_millis());
+                    return Ok(gamelift::ticket_from_state(state));
+                }
+                // nothing new yet, wait again
+                Ok(None) => {}
+                Err(err) => return Ok(Err(err)),
+            }
+        }
+    }
+
+    async fn ticket(
+        &self,
+        control: &dyn GameLiftControl,
+        ticket_id: &str,
+        player_ids: &[String],
+    ) -> anyhow::Result<Ticket> {
+        match self {
+            TicketSource::Describe | TicketSource::Notify(_) => {
+                control.describe_matchmaking(ticket_id.to_owned()).await
+            }
+            TicketSource::Store(store) => match store.get(ticket_id.to_owned()).await? {
+                Some(state) => gamelift::ticket_from_state(state),
+                // no notifications about the ticket yet
+                None => Ok(Ticket {
+                    ticket_id: ticket_id.to_owned(),
+                    status: TicketStatus::Queued,
+                    players: player_ids
+                        .iter()
+                        .map(|player_id| TicketPlayer {
+                            player_id: player_id.clone(),
+                            team: None,
+                        })
+                        .collect(),
+                    status_reason: None,
+                    status_message: None,
+                    estimated_wait_time: None,
+                }),
+            },
+        }
+    }
+}
+
+/// Polls a matchmaking ticket until it completes, returns the match connection info
+///
+/// Proposed matches are accepted or rejected once, then polling continues.
+/// The ticket is stopped if the deadline passes or the wait is interrupted
+async fn wait_for_match(
+    control: &dyn GameLiftControl,
+    source: &TicketSource<'_>,
+    pending: &PendingTicket,
+    poll: PollOptions,
+    auto_accept: bool,
+    input: &mut Lines<impl AsyncBufRead + Unpin>,
+) -> anyhow::Result<MatchConnection> {
+    let ticket_id = pending.ticket_id.clone();
+    let mut answered = false;
+    let mut after = None;
+
+    let mut poller = Poller::new(poll);
     loop {
-        tokio::time::sleep(tokio::time::Duration::from_secs(5)).await;
-
-        let output = client
-            .describe_matchmaking()
-            .ticket_ids(ticket_id.clone())
-            .send()
-            .await?;
-
-        let ticket = output.ticket_list.as_ref().unwrap().first().unwrap();
-
-        let status = ticket.status.as_ref().unwrap();
-        match status {
-            MatchmakingConfigurationStatus::Cancelled
-            | MatchmakingConfigurationStatus::Failed
-            | MatchmakingConfigurationStatus::TimedOut
-            | MatchmakingConfigurationStatus::Unknown(_) => bail!("Find failed: {:?}", status),
-            MatchmakingConfigurationStatus::Queued
-            | MatchmakingConfigurationStatus::Searching
-            | MatchmakingConfigurationStatus::Placing => print_ticket(ticket),
-            MatchmakingConfigurationStatus::Completed => {
-                connection_info = ticket.game_session_connection_info.clone();
+        let ticket = match source
+            .next_ticket(
+                control,
+                &mut poller,
+                &ticket_id,
+                &pending.player_ids,
+                &mut after,
+            )
+            .await
+        {
+            Ok(ticket) => ticket?,
+            Err(reason) => {
+                stop_matchmaking(control, &ticket_id).await;
+                bail!(RequestEnded(format!("Find {}", reason)));
+            }
+        };
 
-                break;
+        match ticket.status {
+            TicketStatus::Queued | TicketStatus::Searching | TicketStatus::Placing => {
+                print_ticket(&ticket)
             }
-            _ => unreachable!(),
+            // the ticket stays here until every player has answered
+            TicketStatus::RequiresAcceptance if answered => print_ticket(&ticket),
+            TicketStatus::RequiresAcceptance => {
+                print_proposed_match(&ticket);
+
+                let accept = match poller
+                    .interruptible(prompt_acceptance(input, auto_accept))
+                    .await
+                {
+                    Ok(accept) => accept?,
+                    Err(reason) => {
+                        stop_matchmaking(control, &ticket_id).await;
+                        bail!(RequestEnded(format!("Find {}", reason)));
+                    }
+                };
+
+                let player_ids = ticket
+                    .players
+                    .into_iter()
+                    .map(|player| player.player_id)
+                    .collect();
+
+                control
+                    .accept_match(ticket_id.clone(), player_ids, accept)
+                    .await?;
+                answered = true;
+
+                info!("Match {}", if accept { "accepted" } else { "rejected" });
+            }
+            TicketStatus::Completed(connection) => return Ok(connection),
+            TicketStatus::Cancelled
+            | TicketStatus::TimedOut
+            | TicketStatus::Failed
+            | TicketStatus::Unknown(_) => {
+                bail!(RequestEnded(format!("Find failed: {:?}", ticket.status)))
+            }
+        }
+    }
+}
+
+/// Matchmakes a party of one or more players on a single ticket, then connects each of them
+///
+/// The ticket is kept in the state file until it finishes, so it can be resumed
+pub async fn find(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    region: impl Into<String>,
+    party: Vec<PlayerDetails>,
+    poll: PollOptions,
+    auto_accept: bool,
+    source: &TicketSource<'_>,
+) -> anyhow::Result<()> {
+    if let Some(ticket) = state.load()?.ticket {
+        bail!(
+            "Ticket {} is still pending, resume it with --resume or cancel it with --cancel",
+            ticket.ticket_id
+        );
+    }
+
+    info!("Searching for server for {} players ...", party.len());
+
+    let player_ids = party
+        .iter()
+        .map(|player| player.player_id.clone())
+        .collect::<Vec<_>>();
+
+    let ticket = control.start_matchmaking("echo".to_owned(), party).await?;
+    print_ticket(&ticket);
+
+    let pending = PendingTicket {
+        ticket_id: ticket.ticket_id,
+        player_ids,
+        region: region.into(),
+    };
+    state.update(|state| state.ticket = Some(pending.clone()))?;
+
+    finish_find(control, state, pending, poll, auto_accept, source).await
+}
+
+/// Picks up polling a matchmaking ticket from an earlier run
+pub async fn resume_find(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    ticket: PendingTicket,
+    poll: PollOptions,
+    auto_accept: bool,
+    source: &TicketSource<'_>,
+) -> anyhow::Result<()> {
+    info!("Resuming ticket {} ...", ticket.ticket_id);
+
+    finish_find(control, state, ticket, poll, auto_accept, source).await
+}
+
+/// Stops a matchmaking ticket from an earlier run and forgets it
+pub async fn cancel_find(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    ticket: PendingTicket,
+) -> anyhow::Result<()> {
+    stop_matchmaking(control, &ticket.ticket_id).await;
+
+    state.update(|state| state.ticket = None)
+}
+
+async fn finish_find(
+    control: &dyn GameLiftControl,
+    state: &StateFile,
+    ticket: PendingTicket,
+    poll: PollOptions,
+    auto_accept: bool,
+    source: &TicketSource<'_>,
+) -> anyhow::Result<()> {
+    // read by the acceptance prompt, then the sessions
+    let mut input = stdin_lines();
+
+    // poll until we find a match or timeout
+    let result = wait_for_match(control, source, &ticket, poll, auto_accept, &mut input).await;
+
+    // transient errors keep the ticket around to resume later
+    if finished(&result) {
+        state.update(|state| state.ticket = None)?;
+    }
+    let mut connection = result?;
+
+    info!("Found a match: {:?}", connection);
+
+    // each party member connects with its own player session
+    let mut player_sessions = Vec::with_capacity(ticket.player_ids.len());
+    for player_id in ticket.player_ids {
+        let index = match connection
+            .player_sessions
+            .iter()
+            .position(|player_session| player_session.player_id == player_id)
+        {
+            Some(index) => index,
+            None => bail!("Match has no player session for {}", player_id),
+        };
+
+        let player_session = connection.player_sessions.swap_remove(index);
+        print_player_session(&player_session);
+
+        player_sessions.push(player_session);
+    }
+
+    connect_player_sessions(player_sessions, input).await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+    use std::future;
+
+    use super::*;
+    use crate::gamelift::mock::{MockGameLiftControl, MockOutcome};
+
+    const OUTCOMES: [(MockOutcome, &str); 3] = [
+        (MockOutcome::Failed, "Failed"),
+        (MockOutcome::TimedOut, "TimedOut"),
+        (MockOutcome::Cancelled, "Cancelled"),
+    ];
+
+    fn mock(outcome: MockOutcome) -> MockGameLiftControl {
+        MockGameLiftControl::new(outcome, "127.0.0.1", 8065)
+    }
+
+    /// Quick polls, with a deadline none of the mock's requests get near
+    fn poll_options() -> PollOptions {
+        PollOptions {
+            initial_interval: time::Duration::from_millis(2),
+            max_interval: time::Duration::from_millis(10),
+            deadline: time::Duration::from_secs(10),
+        }
+    }
+
+    /// Polls that wait past the deadline before the first describe
+    fn short_deadline() -> PollOptions {
+        PollOptions {
+            initial_interval: time::Duration::from_secs(1),
+            max_interval: time::Duration::from_secs(1),
+            deadline: time::Duration::from_millis(20),
         }
     }
 
-    info!("Found a match: {:?}", connection_info);
+    fn state_file() -> StateFile {
+        StateFile::new(std::env::temp_dir().join(format!("echo-state-{}.json", Uuid::new_v4())))
+    }
+
+    fn player(player_id: &str) -> PlayerDetails {
+        PlayerDetails {
+            player_id: player_id.to_owned(),
+            attributes: HashMap::new(),
+            latency_in_ms: HashMap::new(),
+        }
+    }
+
+    fn pending_ticket(ticket: Ticket) -> PendingTicket {
+        PendingTicket {
+            ticket_id: ticket.ticket_id,
+            player_ids: ticket
+                .players
+                .into_iter()
+                .map(|player| player.player_id)
+                .collect(),
+            region: "us-west-2".to_owned(),
+        }
+    }
+
+    /// Input for tests that never prompt
+    fn no_input() -> Lines<&'static [u8]> {
+        b"".as_slice().lines()
+    }
+
+    fn assert_ended(result: anyhow::Result<()>, message: &str) {
+        let err = result.unwrap_err();
+        assert!(err.is::<RequestEnded>(), "unexpected error: {}", err);
+        assert_eq!(err.to_string(), message);
+    }
+
+    #[test]
+    fn poller_backs_off_with_jitter() {
+        let mut poller = Poller::new(PollOptions {
+            initial_interval: time::Duration::from_millis(100),
+            max_interval: time::Duration::from_millis(400),
+            deadline: time::Duration::from_secs(10),
+        });
+
+        for max in [100, 200, 400, 400, 400] {
+            let interval = poller.next_interval();
+            assert!(
+                interval >= time::Duration::from_millis(max / 2)
+                    && interval <= time::Duration::from_millis(max),
+                "{:?} outside {}ms / 2 ..= {}ms",
+                interval,
+                max,
+                max
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn poller_stops_at_deadline() {
+        let mut poller = Poller::new(short_deadline());
+
+        assert_eq!(poller.wait().await, Err(StopReason::Deadline));
+        assert_eq!(
+            poller.interruptible(future::pending::<()>()).await,
+            Err(StopReason::Deadline)
+        );
+    }
+
+    #[tokio::test]
+    async fn poller_remembers_interrupts() {
+        let (interrupt, receiver) = watch::channel(false);
+        let mut poller = Poller::with_interrupt(poll_options(), receiver);
+        assert_eq!(poller.wait().await, Ok(()));
+
+        // pressed while nothing was waiting on it
+        interrupt.send_replace(true);
+        assert_eq!(poller.wait().await, Err(StopReason::Interrupted));
+        assert_eq!(
+            poller.interruptible(future::pending::<()>()).await,
+            Err(StopReason::Interrupted)
+        );
+    }
+
+    #[tokio::test]
+    async fn find_ends_with_the_ticket() {
+        for (outcome, status) in OUTCOMES {
+            let control = mock(outcome);
+            let state = state_file();
+
+            let result = find(
+                &control,
+                &state,
+                "us-west-2",
+                vec![player("player-1")],
+                poll_options(),
+                true,
+                &TicketSource::Describe,
+            )
+            .await;
+
+            assert_ended(result, &format!("Find failed: {}", status));
+            assert_eq!(state.load().unwrap().ticket, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn create_gamelift_ends_with_the_placement() {
+        for (outcome, status) in OUTCOMES {
+            let control = mock(outcome);
+            let state = state_file();
+
+            let result = create_gamelift(
+                &control,
+                &state,
+                "us-west-2",
+                "echo-queue",
+                player("player-1"),
+                poll_options(),
+                &PlacementSource::Describe,
+            )
+            .await;
+
+            assert_ended(result, &format!("Placement failed: {}", status));
+            assert_eq!(state.load().unwrap().placement, None);
+        }
+    }
+
+    #[tokio::test]
+    async fn find_stops_the_ticket_at_deadline() {
+        let control = mock(MockOutcome::Completed);
+        let state = state_file();
+
+        let result = find(
+            &control,
+            &state,
+            "us-west-2",
+            vec![player("player-1")],
+            short_deadline(),
+            true,
+            &TicketSource::Describe,
+        )
+        .await;
 
-    let connection_info = connection_info.unwrap();
+        assert_ended(result, "Find timed out");
+        assert_eq!(state.load().unwrap().ticket, None);
+    }
+
+    #[tokio::test]
+    async fn wait_for_match_stops_the_ticket_at_deadline() {
+        let control = mock(MockOutcome::Completed);
+        let ticket = control
+            .start_matchmaking("echo".to_owned(), vec![player("player-1")])
+            .await
+            .unwrap();
+        let pending = pending_ticket(ticket);
 
-    let connect_addr = Some(format!(
-        "{}:{}",
-        connection_info.ip_address.as_ref().unwrap(),
-        connection_info.port.unwrap()
-    ));
+        let err = wait_for_match(
+            &control,
+            &TicketSource::Describe,
+            &pending,
+            short_deadline(),
+            true,
+            &mut no_input(),
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(err.to_string(), "Find timed out");
+
+        let ticket = control
+            .describe_matchmaking(pending.ticket_id)
+            .await
+            .unwrap();
+        assert_eq!(ticket.status, TicketStatus::Cancelled);
+    }
 
-    let player_session_id = connection_info.matched_player_sessions().as_ref().unwrap()[0]
-        .player_session_id
-        .clone()
+    #[tokio::test]
+    async fn wait_for_placement_stops_the_placement_at_deadline() {
+        let control = mock(MockOutcome::Completed);
+        let placement = control
+            .start_game_session_placement(
+                "echo-queue".to_owned(),
+                "placement-1".to_owned(),
+                10,
+                vec![player("player-1")],
+            )
+            .await
+            .unwrap();
+
+        let err = wait_for_placement(
+            &control,
+            &PlacementSource::Describe,
+            &placement.placement_id,
+            short_deadline(),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.is::<RequestEnded>());
+        assert_eq!(err.to_string(), "Placement timed out");
+
+        let placement = control
+            .describe_game_session_placement(placement.placement_id)
+            .await
+            .unwrap();
+        assert_eq!(placement.status, PlacementStatus::Cancelled);
+    }
+
+    #[tokio::test]
+    async fn wait_for_match_accepts_and_completes() {
+        let control = mock(MockOutcome::Completed);
+        let ticket = control
+            .start_matchmaking(
+                "echo".to_owned(),
+                vec![player("player-1"), player("player-2")],
+            )
+            .await
+            .unwrap();
+        let pending = pending_ticket(ticket);
+
+        let connection = wait_for_match(
+            &control,
+            &TicketSource::Describe,
+            &pending,
+            poll_options(),
+            true,
+            &mut no_input(),
+        )
+        .await
         .unwrap();
 
-    connect_server(connect_addr.unwrap(), player_id, player_session_id).await
+        let mut player_ids = connection
+            .player_sessions
+            .into_iter()
+            .map(|player_session| player_session.player_id)
+            .collect::<Vec<_>>();
+        player_ids.sort();
+        assert_eq!(player_ids, pending.player_ids);
+    }
+
+    #[tokio::test]
+    async fn prompts_share_the_input() {
+        // read in one go, as a buffered stdin would
+        let mut input = b"y\nno\nyes\n".as_slice().lines();
+
+        for answer in [true, false, true] {
+            assert_eq!(prompt_acceptance(&mut input, false).await.unwrap(), answer);
+        }
+        // nothing left to answer with
+        assert!(!prompt_acceptance(&mut input, false).await.unwrap());
+        assert!(prompt_acceptance(&mut input, true).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn wait_for_match_asks_each_time() {
+        let control = mock(MockOutcome::Completed);
+        let mut input = b"y\nn\n".as_slice().lines();
+
+        let mut results = Vec::new();
+        for _ in 0..2 {
+            let ticket = control
+                .start_matchmaking("echo".to_owned(), vec![player("player-1")])
+                .await
+                .unwrap();
+            let result = wait_for_match(
+                &control,
+                &TicketSource::Describe,
+                &pending_ticket(ticket),
+                poll_options(),
+                false,
+                &mut input,
+            )
+            .await;
+            results.push(result.map_err(|err| err.to_string()));
+        }
+
+        assert!(results[0].is_ok(), "{:?}", results[0]);
+        assert_eq!(results[1].as_ref().unwrap_err(), "Find failed: Cancelled");
+    }
+
+    #[tokio::test]
+    async fn interrupt_disconnects_the_session() {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap().to_string();
+
+        let (connected, connected_receiver) = tokio::sync::oneshot::channel();
+        tokio::spawn(async move {
+            let (mut stream, _) = listener.accept().await.unwrap();
+            let player_session_id = echo_protocol::read_player_session_id(&mut stream)
+                .await
+                .unwrap();
+            let _ = connected.send(player_session_id);
+
+            // keep the connection open, the client has to leave by itself
+            future::pending::<()>().await;
+            drop(stream);
+        });
+
+        let (_input, input_receiver) = broadcast::channel(1);
+        let (interrupt, interrupt_receiver) = watch::channel(false);
+        let session = tokio::spawn(async move {
+            connect_server(
+                addr,
+                "player-1",
+                "player-session-1",
+                input_receiver,
+                interrupt_receiver,
+            )
+            .await
+        });
+
+        assert_eq!(connected_receiver.await.unwrap(), "player-session-1");
+        interrupt.send_replace(true);
+
+        time::timeout(time::Duration::from_secs(5), session)
+            .await
+            .expect("the session kept running after Ctrl-C")
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn wait_for_placement_fulfills() {
+        let control = mock(MockOutcome::Completed);
+        let placement = control
+            .start_game_session_placement(
+                "echo-queue".to_owned(),
+                "placement-1".to_owned(),
+                10,
+                vec![player("player-1")],
+            )
+            .await
+            .unwrap();
+
+        let game_session_id = wait_for_placement(
+            &control,
+            &PlacementSource::Describe,
+            placement.placement_id,
+            poll_options(),
+        )
+        .await
+        .unwrap();
+        assert!(game_session_id.starts_with("mock-gsess-"));
+    }
 }
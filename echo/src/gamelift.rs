@@ -1,16 +1,852 @@
-use aws_sdk_gamelift::{config, Client, Endpoint, Region};
+#[cfg(test)]
+pub mod mock;
+pub mod search;
+
+use std::collections::HashMap;
+use std::future;
+use std::pin::Pin;
+
+use anyhow::anyhow;
+use aws_sdk_gamelift::{
+    config,
+    model::{
+        AcceptanceType, AttributeValue, DesiredPlayerSession, GameSessionConnectionInfo,
+        GameSessionPlacement, GameSessionPlacementState, MatchmakingConfigurationStatus,
+        MatchmakingTicket, Player, PlayerLatency,
+    },
+    Client, Endpoint, Region,
+};
+use futures_util::FutureExt;
 use http::Uri;
 
+use echo_protocol::{
+    placements::{PlacementState, PlacementStatus as PlacementStateStatus},
+    tickets::{TicketState, TicketStatus as TicketStateStatus},
+};
+
+/// Endpoint for GameLift local (GameLiftLocal.jar or echo gamelift-local)
+pub const LOCAL_ENDPOINT: &str = "http://localhost:8080";
+
+pub type ControlFuture<'a, T> =
+    Pin<Box<dyn future::Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+/// Where to connect a player to
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PlayerSessionInfo {
+    pub player_id: String,
+    pub player_session_id: String,
+    pub ip_address: String,
+    pub port: u16,
+}
+
+impl PlayerSessionInfo {
+    pub fn connect_addr(&self) -> String {
+        format!("{}:{}", self.ip_address, self.port)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PlacementStatus {
+    Pending,
+    Fulfilled { game_session_id: String },
+    Cancelled,
+    TimedOut,
+    Failed,
+    Unknown(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Placement {
+    pub placement_id: String,
+    pub status: PlacementStatus,
+}
+
+/// Player sessions created for a completed match
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MatchConnection {
+    pub game_session_arn: Option<String>,
+    pub player_sessions: Vec<PlayerSessionInfo>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TicketStatus {
+    Queued,
+    Searching,
+    RequiresAcceptance,
+    Placing,
+    Completed(MatchConnection),
+    Cancelled,
+    TimedOut,
+    Failed,
+    Unknown(String),
+}
+
+/// A player on a matchmaking ticket
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TicketPlayer {
+    pub player_id: String,
+    pub team: Option<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Ticket {
+    pub ticket_id: String,
+    pub status: TicketStatus,
+    pub players: Vec<TicketPlayer>,
+    pub status_reason: Option<String>,
+    pub status_message: Option<String>,
+    pub estimated_wait_time: Option<i32>,
+}
+
+/// A player attribute, for matchmaking rules
+#[derive(Debug, Clone, PartialEq)]
+pub enum PlayerAttribute {
+    String(String),
+    Number(f64),
+    StringList(Vec<String>),
+    StringNumberMap(HashMap<String, f64>),
+}
+
+/// A player to matchmake or place
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PlayerDetails {
+    pub player_id: String,
+    pub attributes: HashMap<String, PlayerAttribute>,
+
+    /// latency to each region the player can play in
+    pub latency_in_ms: HashMap<String, i32>,
+}
+
+/// A game session returned by a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GameSession {
+    pub game_session_id: String,
+    pub name: Option<String>,
+    pub status: String,
+    pub current_player_session_count: i32,
+    pub maximum_player_session_count: i32,
+    pub game_properties: HashMap<String, String>,
+    pub creation_time_millis: Option<i64>,
+    pub ip_address: Option<String>,
+    pub port: Option<u16>,
+}
+
+impl GameSession {
+    pub fn has_room(&self) -> bool {
+        self.current_player_session_count < self.maximum_player_session_count
+    }
+}
+
+/// Where to search for game sessions
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SearchTarget {
+    Fleet(String),
+    Alias(String),
+}
+
+/// The GameLift control-plane calls made by the client
+pub trait GameLiftControl: Send + Sync {
+    /// Creates a game session directly on a fleet, returns the game session id
+    fn create_game_session(&self, fleet_id: String, max_players: i32) -> ControlFuture<'_, String>;
+
+    fn start_game_session_placement(
+        &self,
+        queue_name: String,
+        placement_id: String,
+        max_players: i32,
+        players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Placement>;
+
+    fn describe_game_session_placement(&self, placement_id: String)
+        -> ControlFuture<'_, Placement>;
+
+    fn stop_game_session_placement(&self, placement_id: String) -> ControlFuture<'_, ()>;
+
+    fn create_player_session(
+        &self,
+        game_session_id: String,
+        player_id: String,
+    ) -> ControlFuture<'_, PlayerSessionInfo>;
+
+    fn start_matchmaking(
+        &self,
+        configuration_name: String,
+        players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Ticket>;
+
+    fn describe_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, Ticket>;
+
+    fn stop_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, ()>;
+
+    /// Accepts or rejects a proposed match on behalf of the given players
+    fn accept_match(
+        &self,
+        ticket_id: String,
+        player_ids: Vec<String>,
+        accept: bool,
+    ) -> ControlFuture<'_, ()>;
+
+    /// Searches active game sessions with SearchGameSessions filter and sort expressions
+    fn search_game_sessions(
+        &self,
+        target: SearchTarget,
+        filter: Option<String>,
+        sort: Option<String>,
+        limit: i32,
+    ) -> ControlFuture<'_, Vec<GameSession>>;
+}
+
+fn required<T>(value: Option<T>, name: &str) -> anyhow::Result<T> {
+    value.ok_or_else(|| anyhow!("GameLift response missing {}", name))
+}
+
+fn port(port: Option<i32>) -> anyhow::Result<u16> {
+    let port = required(port, "port")?;
+    u16::try_from(port).map_err(|_| anyhow!("GameLift response has invalid port {}", port))
+}
+
+fn attribute_value(attribute: PlayerAttribute) -> AttributeValue {
+    match attribute {
+        PlayerAttribute::String(value) => AttributeValue::builder().s(value).build(),
+        PlayerAttribute::Number(value) => AttributeValue::builder().n(value).build(),
+        PlayerAttribute::StringList(values) => {
+            AttributeValue::builder().set_sl(Some(values)).build()
+        }
+        PlayerAttribute::StringNumberMap(values) => {
+            AttributeValue::builder().set_sdm(Some(values)).build()
+        }
+    }
+}
+
+fn placement_from(placement: GameSessionPlacement) -> anyhow::Result<Placement> {
+    let status = match required(placement.status, "placement status")? {
+        GameSessionPlacementState::Pending => PlacementStatus::Pending,
+        GameSessionPlacementState::Fulfilled => PlacementStatus::Fulfilled {
+            game_session_id: required(placement.game_session_id, "game session id")?,
+        },
+        GameSessionPlacementState::Cancelled => PlacementStatus::Cancelled,
+        GameSessionPlacementState::TimedOut => PlacementStatus::TimedOut,
+        GameSessionPlacementState::Failed => PlacementStatus::Failed,
+        status => PlacementStatus::Unknown(format!("{:?}", status)),
+    };
+
+    Ok(Placement {
+        placement_id: required(placement.placement_id, "placement id")?,
+        status,
+    })
+}
+
+fn match_connection_from(
+    connection_info: GameSessionConnectionInfo,
+) -> anyhow::Result<MatchConnection> {
+    let ip_address = required(connection_info.ip_address, "ip address")?;
+    let port = port(connection_info.port)?;
+
+    let player_sessions = required(
+        connection_info.matched_player_sessions,
+        "matched player sessions",
+    )?
+    .into_iter()
+    .map(|player_session| {
+        Ok(PlayerSessionInfo {
+            player_id: required(player_session.player_id, "player id")?,
+            player_session_id: required(player_session.player_session_id, "player session id")?,
+            ip_address: ip_address.clone(),
+            port,
+        })
+    })
+    .collect::<anyhow::Result<_>>()?;
+
+    Ok(MatchConnection {
+        game_session_arn: connection_info.game_session_arn,
+        player_sessions,
+    })
+}
+
+fn ticket_from(ticket: MatchmakingTicket) -> anyhow::Result<Ticket> {
+    let status = match required(ticket.status, "ticket status")? {
+        MatchmakingConfigurationStatus::Queued => TicketStatus::Queued,
+        MatchmakingConfigurationStatus::Searching => TicketStatus::Searching,
+        MatchmakingConfigurationStatus::RequiresAcceptance => TicketStatus::RequiresAcceptance,
+        MatchmakingConfigurationStatus::Placing => TicketStatus::Placing,
+        MatchmakingConfigurationStatus::Completed => {
+            TicketStatus::Completed(match_connection_from(required(
+                ticket.game_session_connection_info,
+                "game session connection info",
+            )?)?)
+        }
+        MatchmakingConfigurationStatus::Cancelled => TicketStatus::Cancelled,
+        MatchmakingConfigurationStatus::TimedOut => TicketStatus::TimedOut,
+        MatchmakingConfigurationStatus::Failed => TicketStatus::Failed,
+        status => TicketStatus::Unknown(format!("{:?}", status)),
+    };
+
+    let players = ticket
+        .players
+        .unwrap_or_default()
+        .into_iter()
+        .map(|player| {
+            Ok(TicketPlayer {
+                player_id: required(player.player_id, "player id")?,
+                team: player.team,
+            })
+        })
+        .collect::<anyhow::Result<_>>()?;
+
+    Ok(Ticket {
+        ticket_id: required(ticket.ticket_id, "ticket id")?,
+        status,
+        players,
+        status_reason: ticket.status_reason,
+        status_message: ticket.status_message,
+        estimated_wait_time: ticket.estimated_wait_time,
+    })
+}
+
+fn game_session_from(
+    game_session: aws_sdk_gamelift::model::GameSession,
+) -> anyhow::Result<GameSession> {
+    Ok(GameSession {
+        game_session_id: required(game_session.game_session_id, "game session id")?,
+        name: game_session.name,
+        status: game_session
+            .status
+            .map(|status| status.as_str().to_owned())
+            .unwrap_or_default(),
+        current_player_session_count: game_session.current_player_session_count.unwrap_or(0),
+        maximum_player_session_count: game_session.maximum_player_session_count.unwrap_or(0),
+        game_properties: game_session
+            .game_properties
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|property| Some((property.key?, property.value?)))
+            .collect(),
+        creation_time_millis: game_session
+            .creation_time
+            .and_then(|creation_time| creation_time.to_millis().ok()),
+        ip_address: game_session.ip_address,
+        port: game_session
+            .port
+            .map(|value| port(Some(value)))
+            .transpose()?,
+    })
+}
+
+/// Converts ticket state kept by echo-notifications into a ticket
+pub fn ticket_from_state(state: TicketState) -> anyhow::Result<Ticket> {
+    let status = match state.status {
+        TicketStateStatus::Searching => TicketStatus::Searching,
+        TicketStateStatus::RequiresAcceptance => TicketStatus::RequiresAcceptance,
+        TicketStateStatus::Placing => TicketStatus::Placing,
+        TicketStateStatus::Completed => {
+            let ip_address = required(state.ip_address, "ip address")?;
+            let port = required(state.port, "port")?;
+
+            TicketStatus::Completed(MatchConnection {
+                game_session_arn: state.game_session_arn,
+                player_sessions: state
+                    .player_sessions
+                    .into_iter()
+                    .map(|player_session| PlayerSessionInfo {
+                        player_id: player_session.player_id,
+                        player_session_id: player_session.player_session_id,
+                        ip_address: ip_address.clone(),
+                        port,
+                    })
+                    .collect(),
+            })
+        }
+        TicketStateStatus::TimedOut => TicketStatus::TimedOut,
+        TicketStateStatus::Cancelled => TicketStatus::Cancelled,
+        TicketStateStatus::Failed => TicketStatus::Failed,
+    };
+
+    Ok(Ticket {
+        ticket_id: state.ticket_id,
+        status,
+        players: state
+            .player_ids
+            .into_iter()
+            .map(|player_id| TicketPlayer {
+                player_id,
+                team: None,
+            })
+            .collect(),
+        status_reason: state.status_reason,
+        status_message: state.status_message,
+        estimated_wait_time: None,
+    })
+}
+
+/// Converts placement state from a queue placement event into a placement
+pub fn placement_from_state(state: PlacementState) -> anyhow::Result<Placement> {
+    let status = match state.status {
+        // a game session's id is its ARN, which placement events only have once fulfilled
+        PlacementStateStatus::Fulfilled => PlacementStatus::Fulfilled {
+            game_session_id: state.game_session_arn.ok_or_else(|| {
+                anyhow!(
+                    "Placement {} fulfilled without a game session arn",
+                    state.placement_id
+                )
+            })?,
+        },
+        PlacementStateStatus::Cancelled => PlacementStatus::Cancelled,
+        PlacementStateStatus::TimedOut => PlacementStatus::TimedOut,
+        PlacementStateStatus::Failed => PlacementStatus::Failed,
+    };
+
+    Ok(Placement {
+        placement_id: state.placement_id,
+        status,
+    })
+}
+
+/// GameLift control-plane through the AWS SDK
+pub struct AwsGameLiftControl {
+    client: Client,
+}
+
+impl AwsGameLiftControl {
+    pub fn new(client: Client) -> Self {
+        Self { client }
+    }
+}
+
+impl GameLiftControl for AwsGameLiftControl {
+    fn create_game_session(&self, fleet_id: String, max_players: i32) -> ControlFuture<'_, String> {
+        async move {
+            let output = self
+                .client
+                .create_game_session()
+                .fleet_id(fleet_id)
+                .maximum_player_session_count(max_players)
+                .send()
+                .await?;
+
+            let game_session = required(output.game_session, "game session")?;
+            required(game_session.game_session_id, "game session id")
+        }
+        .boxed()
+    }
+
+    fn start_game_session_placement(
+        &self,
+        queue_name: String,
+        placement_id: String,
+        max_players: i32,
+        players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Placement> {
+        async move {
+            let mut request = self
+                .client
+                .start_game_session_placement()
+                .game_session_queue_name(queue_name)
+                .placement_id(placement_id)
+                .maximum_player_session_count(max_players);
+
+            for player in players {
+                for (region, latency_in_ms) in player.latency_in_ms {
+                    request = request.player_latencies(
+                        PlayerLatency::builder()
+                            .player_id(player.player_id.clone())
+                            .region_identifier(region)
+                            .latency_in_milliseconds(latency_in_ms as f32)
+                            .build(),
+                    );
+                }
+
+                request = request.desired_player_sessions(
+                    DesiredPlayerSession::builder()
+                        .player_id(player.player_id)
+                        .build(),
+                );
+            }
+
+            let output = request.send().await?;
+
+            placement_from(required(
+                output.game_session_placement,
+                "game session placement",
+            )?)
+        }
+        .boxed()
+    }
+
+    fn describe_game_session_placement(
+        &self,
+        placement_id: String,
+    ) -> ControlFuture<'_, Placement> {
+        async move {
+            let output = self
+                .client
+                .describe_game_session_placement()
+                .placement_id(placement_id)
+                .send()
+                .await?;
+
+            placement_from(required(
+                output.game_session_placement,
+                "game session placement",
+            )?)
+        }
+        .boxed()
+    }
+
+    fn stop_game_session_placement(&self, placement_id: String) -> ControlFuture<'_, ()> {
+        async move {
+            self.client
+                .stop_game_session_placement()
+                .placement_id(placement_id)
+                .send()
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn create_player_session(
+        &self,
+        game_session_id: String,
+        player_id: String,
+    ) -> ControlFuture<'_, PlayerSessionInfo> {
+        async move {
+            let output = self
+                .client
+                .create_player_session()
+                .game_session_id(game_session_id)
+                .player_id(player_id.clone())
+                .send()
+                .await?;
+
+            let player_session = required(output.player_session, "player session")?;
+
+            Ok(PlayerSessionInfo {
+                player_id,
+                player_session_id: required(player_session.player_session_id, "player session id")?,
+                ip_address: required(player_session.ip_address, "ip address")?,
+                port: port(player_session.port)?,
+            })
+        }
+        .boxed()
+    }
+
+    fn start_matchmaking(
+        &self,
+        configuration_name: String,
+        players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Ticket> {
+        async move {
+            let mut request = self
+                .client
+                .start_matchmaking()
+                .configuration_name(configuration_name);
+
+            for player in players {
+                request = request.players(
+                    Player::builder()
+                        .player_id(player.player_id)
+                        .set_player_attributes(Some(
+                            player
+                                .attributes
+                                .into_iter()
+                                .map(|(name, attribute)| (name, attribute_value(attribute)))
+                                .collect(),
+                        ))
+                        .set_latency_in_ms(Some(player.latency_in_ms))
+                        .build(),
+                );
+            }
+
+            let output = request.send().await?;
+
+            ticket_from(required(output.matchmaking_ticket, "matchmaking ticket")?)
+        }
+        .boxed()
+    }
+
+    fn describe_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, Ticket> {
+        async move {
+            let output = self
+                .client
+                .describe_matchmaking()
+                .ticket_ids(ticket_id)
+                .send()
+                .await?;
+
+            let ticket = required(output.ticket_list, "ticket list")?
+                .into_iter()
+                .next();
+
+            ticket_from(required(ticket, "ticket")?)
+        }
+        .boxed()
+    }
+
+    fn stop_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, ()> {
+        async move {
+            self.client
+                .stop_matchmaking()
+                .ticket_id(ticket_id)
+                .send()
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn accept_match(
+        &self,
+        ticket_id: String,
+        player_ids: Vec<String>,
+        accept: bool,
+    ) -> ControlFuture<'_, ()> {
+        async move {
+            let mut request = self
+                .client
+                .accept_match()
+                .ticket_id(ticket_id)
+                .acceptance_type(if accept {
+                    AcceptanceType::Accept
+                } else {
+                    AcceptanceType::Reject
+                });
+
+            for player_id in player_ids {
+                request = request.player_ids(player_id);
+            }
+
+            request.send().await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn search_game_sessions(
+        &self,
+        target: SearchTarget,
+        filter: Option<String>,
+        sort: Option<String>,
+        limit: i32,
+    ) -> ControlFuture<'_, Vec<GameSession>> {
+        async move {
+            let request = self
+                .client
+                .search_game_sessions()
+                .set_filter_expression(filter)
+                .set_sort_expression(sort)
+                .limit(limit);
+
+            let request = match target {
+                SearchTarget::Fleet(fleet_id) => request.fleet_id(fleet_id),
+                SearchTarget::Alias(alias_id) => request.alias_id(alias_id),
+            };
+
+            let output = request.send().await?;
+
+            output
+                .game_sessions
+                .unwrap_or_default()
+                .into_iter()
+                .map(game_session_from)
+                .collect()
+        }
+        .boxed()
+    }
+}
+
 pub async fn new_client(region: impl Into<String>, local: bool) -> Client {
     let shared_config = aws_config::from_env().load().await;
 
     let mut config = config::Builder::from(&shared_config).region(Region::new(region.into()));
     if local {
-        config = config.endpoint_resolver(Endpoint::immutable(Uri::from_static(
-            "http://localhost:8080",
-        )));
+        config = config.endpoint_resolver(Endpoint::immutable(Uri::from_static(LOCAL_ENDPOINT)));
     }
     let config = config.build();
 
     Client::from_conf(config)
 }
+
+/// Creates the control-plane client
+pub async fn new_control(region: impl Into<String>, local: bool) -> Box<dyn GameLiftControl> {
+    Box::new(AwsGameLiftControl::new(new_client(region, local).await))
+}
+
+#[cfg(test)]
+mod tests {
+    use aws_sdk_gamelift::{model, DateTime};
+
+    use super::*;
+
+    fn sdk_game_session(game_session_id: &str, creation_time: Option<DateTime>) -> GameSession {
+        let game_session = model::GameSession::builder()
+            .game_session_id(game_session_id)
+            .maximum_player_session_count(10)
+            .set_creation_time(creation_time)
+            .build();
+
+        game_session_from(game_session).unwrap()
+    }
+
+    #[test]
+    fn creation_time_in_millis() {
+        let game_session = sdk_game_session(
+            "gsess-1",
+            Some(DateTime::from_fractional_secs(1_600_000_000, 0.25)),
+        );
+        assert_eq!(game_session.creation_time_millis, Some(1_600_000_000_250));
+
+        let game_session = sdk_game_session("gsess-2", None);
+        assert_eq!(game_session.creation_time_millis, None);
+    }
+
+    #[test]
+    fn search_by_age() {
+        let game_sessions = [
+            ("gsess-new", Some(DateTime::from_millis(1_600_000_002_000))),
+            ("gsess-old", Some(DateTime::from_millis(1_600_000_000_500))),
+            ("gsess-mid", Some(DateTime::from_millis(1_600_000_001_000))),
+        ]
+        .into_iter()
+        .map(|(game_session_id, creation_time)| {
+            (sdk_game_session(game_session_id, creation_time), ())
+        })
+        .collect::<Vec<_>>();
+
+        let ids = |results: Vec<(GameSession, ())>| {
+            results
+                .into_iter()
+                .map(|(game_session, _)| game_session.game_session_id)
+                .collect::<Vec<_>>()
+        };
+
+        let oldest_first = search::search(
+            game_sessions.clone(),
+            None,
+            Some("creationTimeMillis ASC"),
+            10,
+        )
+        .unwrap();
+        assert_eq!(
+            ids(oldest_first),
+            vec!["gsess-old", "gsess-mid", "gsess-new"]
+        );
+
+        let older = search::search(
+            game_sessions,
+            Some("creationTimeMillis < 1600000001500"),
+            Some("creationTimeMillis DESC"),
+            10,
+        )
+        .unwrap();
+        assert_eq!(ids(older), vec!["gsess-mid", "gsess-old"]);
+    }
+
+    const GAME_SESSION_ARN: &str = "arn:aws:gamelift:us-west-2::gamesession/fleet-1/gsess-1";
+
+    fn sdk_placement(
+        status: GameSessionPlacementState,
+        game_session_id: Option<&str>,
+    ) -> GameSessionPlacement {
+        GameSessionPlacement::builder()
+            .placement_id("placement-1")
+            .status(status)
+            .set_game_session_id(game_session_id.map(str::to_owned))
+            .build()
+    }
+
+    fn placement_state(
+        status: PlacementStateStatus,
+        game_session_arn: Option<&str>,
+    ) -> PlacementState {
+        let time = "2022-06-01T12:00:00Z".parse().unwrap();
+
+        PlacementState {
+            placement_id: "placement-1".to_owned(),
+            status,
+            game_session_arn: game_session_arn.map(str::to_owned),
+            ip_address: None,
+            dns_name: None,
+            port: None,
+            player_sessions: Vec::new(),
+            start_time: time,
+            end_time: None,
+            updated_at: time,
+        }
+    }
+
+    #[test]
+    fn placements_from_describe() {
+        for (placement, status) in [
+            (
+                sdk_placement(GameSessionPlacementState::Pending, None),
+                PlacementStatus::Pending,
+            ),
+            (
+                sdk_placement(GameSessionPlacementState::Fulfilled, Some(GAME_SESSION_ARN)),
+                PlacementStatus::Fulfilled {
+                    game_session_id: GAME_SESSION_ARN.to_owned(),
+                },
+            ),
+            (
+                sdk_placement(GameSessionPlacementState::Failed, None),
+                PlacementStatus::Failed,
+            ),
+            (
+                sdk_placement(GameSessionPlacementState::TimedOut, None),
+                PlacementStatus::TimedOut,
+            ),
+        ] {
+            assert_eq!(
+                placement_from(placement).unwrap(),
+                Placement {
+                    placement_id: "placement-1".to_owned(),
+                    status
+                }
+            );
+        }
+
+        let fulfilled = sdk_placement(GameSessionPlacementState::Fulfilled, None);
+        assert!(placement_from(fulfilled).is_err());
+    }
+
+    #[test]
+    fn placements_from_events() {
+        for (state, status) in [
+            (
+                placement_state(PlacementStateStatus::Fulfilled, Some(GAME_SESSION_ARN)),
+                PlacementStatus::Fulfilled {
+                    game_session_id: GAME_SESSION_ARN.to_owned(),
+                },
+            ),
+            (
+                placement_state(PlacementStateStatus::Cancelled, None),
+                PlacementStatus::Cancelled,
+            ),
+            (
+                placement_state(PlacementStateStatus::TimedOut, None),
+                PlacementStatus::TimedOut,
+            ),
+            (
+                placement_state(PlacementStateStatus::Failed, None),
+                PlacementStatus::Failed,
+            ),
+        ] {
+            assert_eq!(
+                placement_from_state(state).unwrap(),
+                Placement {
+                    placement_id: "placement-1".to_owned(),
+                    status
+                }
+            );
+        }
+
+        let fulfilled = placement_state(PlacementStateStatus::Fulfilled, None);
+        assert_eq!(
+            placement_from_state(fulfilled).unwrap_err().to_string(),
+            "Placement placement-1 fulfilled without a game session arn"
+        );
+    }
+}
@@ -0,0 +1,383 @@
+use std::collections::HashMap;
+use std::future;
+use std::sync::Mutex;
+
+use anyhow::anyhow;
+use futures_util::FutureExt;
+use uuid::Uuid;
+
+use super::{
+    search, ControlFuture, GameLiftControl, GameSession, MatchConnection, Placement,
+    PlacementStatus, PlayerDetails, PlayerSessionInfo, SearchTarget, Ticket, TicketPlayer,
+    TicketStatus,
+};
+
+/// How mocked placements and matchmaking tickets end
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MockOutcome {
+    Completed,
+    Cancelled,
+    TimedOut,
+    Failed,
+}
+
+#[derive(Debug)]
+struct MockPlacement {
+    polls: usize,
+    stopped: bool,
+}
+
+#[derive(Debug)]
+struct MockTicket {
+    players: Vec<PlayerDetails>,
+    polls: usize,
+    stopped: bool,
+    accepted: Option<bool>,
+}
+
+impl MockTicket {
+    fn players(&self) -> Vec<TicketPlayer> {
+        self.players
+            .iter()
+            .map(|player| TicketPlayer {
+                player_id: player.player_id.clone(),
+                team: None,
+            })
+            .collect()
+    }
+}
+
+/// In-memory stand-in for the GameLift control-plane
+///
+/// Placements go Pending and then to the outcome, tickets go Queued, Searching,
+/// RequiresAcceptance (until the match is accepted) and then to the outcome,
+/// one step per describe call. Stopping either, or rejecting the match, cancels it. Every game session is hosted at the same address.
+///
+/// A casual and a full ranked game session exist from the start, so searches find something
+pub struct MockGameLiftControl {
+    outcome: MockOutcome,
+    ip_address: String,
+    port: u16,
+
+    game_sessions: Mutex<Vec<GameSession>>,
+    placements: Mutex<HashMap<String, MockPlacement>>,
+    tickets: Mutex<HashMap<String, MockTicket>>,
+}
+
+impl MockGameLiftControl {
+    pub fn new(outcome: MockOutcome, ip_address: impl Into<String>, port: u16) -> Self {
+        let mock = Self {
+            outcome,
+            ip_address: ip_address.into(),
+            port,
+            game_sessions: Mutex::new(Vec::new()),
+            placements: Mutex::new(HashMap::new()),
+            tickets: Mutex::new(HashMap::new()),
+        };
+
+        mock.add_game_session(Some("casual"), 1, 10);
+        mock.add_game_session(Some("ranked"), 10, 10);
+
+        mock
+    }
+
+    fn add_game_session(
+        &self,
+        game_mode: Option<&str>,
+        current_player_session_count: i32,
+        maximum_player_session_count: i32,
+    ) -> String {
+        let game_session_id = format!("mock-gsess-{}", Uuid::new_v4());
+
+        let mut game_sessions = self.game_sessions.lock().unwrap();
+        let creation_time_millis = 1_600_000_000_000 + game_sessions.len() as i64;
+        game_sessions.push(GameSession {
+            game_session_id: game_session_id.clone(),
+            name: game_mode.map(|game_mode| format!("mock {}", game_mode)),
+            status: "ACTIVE".to_owned(),
+            current_player_session_count,
+            maximum_player_session_count,
+            game_properties: game_mode
+                .map(|game_mode| ("gameMode".to_owned(), game_mode.to_owned()))
+                .into_iter()
+                .collect(),
+            creation_time_millis: Some(creation_time_millis),
+            ip_address: Some(self.ip_address.clone()),
+            port: Some(self.port),
+        });
+
+        game_session_id
+    }
+
+    fn new_game_session(&self) -> String {
+        self.add_game_session(None, 0, 10)
+    }
+
+    fn player_session(&self, player_id: String) -> PlayerSessionInfo {
+        PlayerSessionInfo {
+            player_id,
+            player_session_id: format!("mock-psess-{}", Uuid::new_v4()),
+            ip_address: self.ip_address.clone(),
+            port: self.port,
+        }
+    }
+
+    fn placement_status(&self, placement: &MockPlacement) -> PlacementStatus {
+        if placement.stopped {
+            return PlacementStatus::Cancelled;
+        }
+
+        if placement.polls == 0 {
+            return PlacementStatus::Pending;
+        }
+
+        match self.outcome {
+            MockOutcome::Completed => PlacementStatus::Fulfilled {
+                game_session_id: self.new_game_session(),
+            },
+            MockOutcome::Cancelled => PlacementStatus::Cancelled,
+            MockOutcome::TimedOut => PlacementStatus::TimedOut,
+            MockOutcome::Failed => PlacementStatus::Failed,
+        }
+    }
+
+    fn ticket_status(&self, ticket: &MockTicket) -> TicketStatus {
+        if ticket.stopped {
+            return TicketStatus::Cancelled;
+        }
+
+        match (ticket.polls, ticket.accepted) {
+            (0, _) => TicketStatus::Queued,
+            (1, _) => TicketStatus::Searching,
+            (_, None) => TicketStatus::RequiresAcceptance,
+            (_, Some(false)) => TicketStatus::Cancelled,
+            (_, Some(true)) => match self.outcome {
+                MockOutcome::Completed => {
+                    self.new_game_session();
+
+                    TicketStatus::Completed(MatchConnection {
+                        game_session_arn: None,
+                        player_sessions: ticket
+                            .players
+                            .iter()
+                            .map(|player| self.player_session(player.player_id.clone()))
+                            .collect(),
+                    })
+                }
+                MockOutcome::Cancelled => TicketStatus::Cancelled,
+                MockOutcome::TimedOut => TicketStatus::TimedOut,
+                MockOutcome::Failed => TicketStatus::Failed,
+            },
+        }
+    }
+}
+
+impl GameLiftControl for MockGameLiftControl {
+    fn create_game_session(
+        &self,
+        _fleet_id: String,
+        _max_players: i32,
+    ) -> ControlFuture<'_, String> {
+        future::ready(Ok(self.new_game_session())).boxed()
+    }
+
+    fn start_game_session_placement(
+        &self,
+        _queue_name: String,
+        placement_id: String,
+        _max_players: i32,
+        _players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Placement> {
+        self.placements.lock().unwrap().insert(
+            placement_id.clone(),
+            MockPlacement {
+                polls: 0,
+                stopped: false,
+            },
+        );
+
+        future::ready(Ok(Placement {
+            placement_id,
+            status: PlacementStatus::Pending,
+        }))
+        .boxed()
+    }
+
+    fn describe_game_session_placement(
+        &self,
+        placement_id: String,
+    ) -> ControlFuture<'_, Placement> {
+        let res = match self.placements.lock().unwrap().get_mut(&placement_id) {
+            Some(placement) => {
+                let status = self.placement_status(placement);
+                placement.polls += 1;
+
+                Ok(Placement {
+                    placement_id,
+                    status,
+                })
+            }
+            None => Err(anyhow!("Placement {} not found", placement_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn stop_game_session_placement(&self, placement_id: String) -> ControlFuture<'_, ()> {
+        let res = match self.placements.lock().unwrap().get_mut(&placement_id) {
+            Some(placement) => {
+                placement.stopped = true;
+
+                Ok(())
+            }
+            None => Err(anyhow!("Placement {} not found", placement_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn create_player_session(
+        &self,
+        game_session_id: String,
+        player_id: String,
+    ) -> ControlFuture<'_, PlayerSessionInfo> {
+        let res = match self
+            .game_sessions
+            .lock()
+            .unwrap()
+            .iter_mut()
+            .find(|game_session| game_session.game_session_id == game_session_id)
+        {
+            Some(game_session) if game_session.has_room() => {
+                game_session.current_player_session_count += 1;
+
+                Ok(self.player_session(player_id))
+            }
+            Some(_) => Err(anyhow!("Game session {} is full", game_session_id)),
+            None => Err(anyhow!("Game session {} not found", game_session_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn start_matchmaking(
+        &self,
+        _configuration_name: String,
+        players: Vec<PlayerDetails>,
+    ) -> ControlFuture<'_, Ticket> {
+        let ticket_id = Uuid::new_v4().to_string();
+
+        let res = if players.is_empty() {
+            Err(anyhow!("No players to matchmake"))
+        } else {
+            let ticket = MockTicket {
+                players,
+                polls: 0,
+                stopped: false,
+                accepted: None,
+            };
+            let players = ticket.players();
+
+            self.tickets
+                .lock()
+                .unwrap()
+                .insert(ticket_id.clone(), ticket);
+
+            Ok(Ticket {
+                ticket_id,
+                status: TicketStatus::Queued,
+                players,
+                status_reason: None,
+                status_message: None,
+                estimated_wait_time: None,
+            })
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn describe_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, Ticket> {
+        let res = match self.tickets.lock().unwrap().get_mut(&ticket_id) {
+            Some(ticket) => {
+                let status = self.ticket_status(ticket);
+                ticket.polls += 1;
+
+                Ok(Ticket {
+                    ticket_id,
+                    status,
+                    players: ticket.players(),
+                    status_reason: None,
+                    status_message: None,
+                    estimated_wait_time: None,
+                })
+            }
+            None => Err(anyhow!("Ticket {} not found", ticket_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn stop_matchmaking(&self, ticket_id: String) -> ControlFuture<'_, ()> {
+        let res = match self.tickets.lock().unwrap().get_mut(&ticket_id) {
+            Some(ticket) => {
+                ticket.stopped = true;
+
+                Ok(())
+            }
+            None => Err(anyhow!("Ticket {} not found", ticket_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn accept_match(
+        &self,
+        ticket_id: String,
+        _player_ids: Vec<String>,
+        accept: bool,
+    ) -> ControlFuture<'_, ()> {
+        let res = match self.tickets.lock().unwrap().get_mut(&ticket_id) {
+            Some(ticket) if ticket.polls >= 2 && ticket.accepted.is_none() => {
+                ticket.accepted = Some(accept);
+
+                Ok(())
+            }
+            Some(_) => Err(anyhow!("Ticket {} doesn't require acceptance", ticket_id)),
+            None => Err(anyhow!("Ticket {} not found", ticket_id)),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn search_game_sessions(
+        &self,
+        _target: SearchTarget,
+        filter: Option<String>,
+        sort: Option<String>,
+        limit: i32,
+    ) -> ControlFuture<'_, Vec<GameSession>> {
+        let game_sessions = self
+            .game_sessions
+            .lock()
+            .unwrap()
+            .iter()
+            .map(|game_session| (game_session.clone(), ()))
+            .collect::<Vec<_>>();
+
+        let res = search::search(
+            game_sessions,
+            filter.as_deref(),
+            sort.as_deref(),
+            limit.max(0) as usize,
+        )
+        .map(|results| {
+            results
+                .into_iter()
+                .map(|(game_session, _)| game_session)
+                .collect()
+        })
+        .map_err(|err| anyhow!("Invalid search: {}", err));
+
+        future::ready(res).boxed()
+    }
+}
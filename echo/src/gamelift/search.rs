@@ -0,0 +1,405 @@
+use std::cmp::Ordering;
+use std::str::FromStr;
+
+use super::GameSession;
+
+/// A game session field that can be searched or sorted on
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Operand {
+    GameSessionId,
+    GameSessionName,
+    GameSessionProperty(String),
+    MaximumSessions,
+    CreationTimeMillis,
+    PlayerSessionCount,
+    HasAvailablePlayerSessions,
+}
+
+impl FromStr for Operand {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        if let Some(key) = s.strip_prefix("gameSessionProperties.") {
+            return Ok(Self::GameSessionProperty(key.to_owned()));
+        }
+
+        match s {
+            "gameSessionId" => Ok(Self::GameSessionId),
+            "gameSessionName" => Ok(Self::GameSessionName),
+            "maximumSessions" => Ok(Self::MaximumSessions),
+            "creationTimeMillis" => Ok(Self::CreationTimeMillis),
+            "playerSessionCount" => Ok(Self::PlayerSessionCount),
+            "hasAvailablePlayerSessions" => Ok(Self::HasAvailablePlayerSessions),
+            _ => Err(format!("unknown search operand '{}'", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+    String(String),
+    Number(f64),
+    Bool(bool),
+}
+
+impl Value {
+    fn compare(&self, other: &Value) -> Option<Ordering> {
+        match (self, other) {
+            (Value::String(left), Value::String(right)) => Some(left.cmp(right)),
+            (Value::Number(left), Value::Number(right)) => left.partial_cmp(right),
+            (Value::Bool(left), Value::Bool(right)) => Some(left.cmp(right)),
+            _ => None,
+        }
+    }
+}
+
+impl Operand {
+    /// The field's value on a game session, None if it doesn't have one
+    fn value(&self, game_session: &GameSession) -> Option<Value> {
+        Some(match self {
+            Operand::GameSessionId => Value::String(game_session.game_session_id.clone()),
+            Operand::GameSessionName => Value::String(game_session.name.clone()?),
+            // property values are always searched as strings
+            Operand::GameSessionProperty(key) => {
+                Value::String(game_session.game_properties.get(key)?.clone())
+            }
+            Operand::MaximumSessions => {
+                Value::Number(game_session.maximum_player_session_count as f64)
+            }
+            Operand::CreationTimeMillis => Value::Number(game_session.creation_time_millis? as f64),
+            Operand::PlayerSessionCount => {
+                Value::Number(game_session.current_player_session_count as f64)
+            }
+            Operand::HasAvailablePlayerSessions => Value::Bool(game_session.has_room()),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Comparator {
+    Equal,
+    NotEqual,
+    Less,
+    LessOrEqual,
+    Greater,
+    GreaterOrEqual,
+}
+
+impl Comparator {
+    fn test(&self, ordering: Ordering) -> bool {
+        match self {
+            Comparator::Equal => ordering == Ordering::Equal,
+            Comparator::NotEqual => ordering != Ordering::Equal,
+            Comparator::Less => ordering == Ordering::Less,
+            Comparator::LessOrEqual => ordering != Ordering::Greater,
+            Comparator::Greater => ordering == Ordering::Greater,
+            Comparator::GreaterOrEqual => ordering != Ordering::Less,
+        }
+    }
+}
+
+/// A SearchGameSessions filter expression, eg.
+///
+/// gameSessionProperties.gameMode = 'brawl' AND hasAvailablePlayerSessions = true
+///
+/// NOT binds tighter than AND, which binds tighter than OR. Comparing values of different
+/// types, or a field the session doesn't have, never matches
+#[derive(Debug, Clone, PartialEq)]
+pub enum SearchFilter {
+    Compare(Operand, Comparator, Value),
+    And(Box<SearchFilter>, Box<SearchFilter>),
+    Or(Box<SearchFilter>, Box<SearchFilter>),
+    Not(Box<SearchFilter>),
+}
+
+impl SearchFilter {
+    pub fn matches(&self, game_session: &GameSession) -> bool {
+        match self {
+            SearchFilter::Compare(operand, comparator, value) => operand
+                .value(game_session)
+                .and_then(|field| field.compare(value))
+                .is_some_and(|ordering| comparator.test(ordering)),
+            SearchFilter::And(left, right) => {
+                left.matches(game_session) && right.matches(game_session)
+            }
+            SearchFilter::Or(left, right) => {
+                left.matches(game_session) || right.matches(game_session)
+            }
+            SearchFilter::Not(filter) => !filter.matches(game_session),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Word(String),
+    String(String),
+    Number(f64),
+    Comparator(Comparator),
+    OpenParen,
+    CloseParen,
+}
+
+fn tokenize(s: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = Vec::new();
+
+    let mut chars = s.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::OpenParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::CloseParen);
+            }
+            '\'' | '"' => {
+                chars.next();
+
+                let mut value = String::new();
+                loop {
+                    match chars.next() {
+                        Some('\\') => value.extend(chars.next()),
+                        Some(next) if next == c => break,
+                        Some(next) => value.push(next),
+                        None => return Err(format!("unterminated string in '{}'", s)),
+                    }
+                }
+                tokens.push(Token::String(value));
+            }
+            '=' | '<' | '>' => {
+                chars.next();
+
+                let comparator = match (c, chars.peek()) {
+                    ('<', Some('>')) => Comparator::NotEqual,
+                    ('<', Some('=')) => Comparator::LessOrEqual,
+                    ('>', Some('=')) => Comparator::GreaterOrEqual,
+                    ('=', _) => Comparator::Equal,
+                    ('<', _) => Comparator::Less,
+                    _ => Comparator::Greater,
+                };
+                if matches!(
+                    comparator,
+                    Comparator::NotEqual | Comparator::LessOrEqual | Comparator::GreaterOrEqual
+                ) {
+                    chars.next();
+                }
+                tokens.push(Token::Comparator(comparator));
+            }
+            c if c.is_ascii_digit() || c == '-' => {
+                let mut number = String::new();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_ascii_digit() || next == '.' || next == '-') {
+                        break;
+                    }
+                    number.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Number(
+                    number
+                        .parse()
+                        .map_err(|_| format!("invalid number '{}'", number))?,
+                ));
+            }
+            c if c.is_alphanumeric() || c == '_' => {
+                let mut word = String::new();
+                while let Some(&next) = chars.peek() {
+                    if !(next.is_alphanumeric() || matches!(next, '_' | '.' | '-')) {
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push(Token::Word(word));
+            }
+            c => return Err(format!("unexpected '{}' in '{}'", c, s)),
+        }
+    }
+
+    Ok(tokens)
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    position: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.position).cloned();
+        self.position += 1;
+        token
+    }
+
+    fn keyword(&mut self, keyword: &str) -> bool {
+        match self.peek() {
+            Some(Token::Word(word)) if word.eq_ignore_ascii_case(keyword) => {
+                self.position += 1;
+                true
+            }
+            _ => false,
+        }
+    }
+
+    fn or(&mut self) -> Result<SearchFilter, String> {
+        let mut filter = self.and()?;
+        while self.keyword("OR") {
+            filter = SearchFilter::Or(Box::new(filter), Box::new(self.and()?));
+        }
+        Ok(filter)
+    }
+
+    fn and(&mut self) -> Result<SearchFilter, String> {
+        let mut filter = self.unary()?;
+        while self.keyword("AND") {
+            filter = SearchFilter::And(Box::new(filter), Box::new(self.unary()?));
+        }
+        Ok(filter)
+    }
+
+    fn unary(&mut self) -> Result<SearchFilter, String> {
+        if self.keyword("NOT") {
+            return Ok(SearchFilter::Not(Box::new(self.unary()?)));
+        }
+
+        match self.next() {
+            Some(Token::OpenParen) => {
+                let filter = self.or()?;
+                match self.next() {
+                    Some(Token::CloseParen) => Ok(filter),
+                    _ => Err("expected ')'".to_owned()),
+                }
+            }
+            Some(Token::Word(operand)) => {
+                let operand = operand.parse()?;
+                let comparator = match self.next() {
+                    Some(Token::Comparator(comparator)) => comparator,
+                    _ => return Err(format!("expected a comparison after {:?}", operand)),
+                };
+                let value = match self.next() {
+                    Some(Token::String(value)) => Value::String(value),
+                    Some(Token::Number(value)) => Value::Number(value),
+                    Some(Token::Word(word)) if word.eq_ignore_ascii_case("true") => {
+                        Value::Bool(true)
+                    }
+                    Some(Token::Word(word)) if word.eq_ignore_ascii_case("false") => {
+                        Value::Bool(false)
+                    }
+                    _ => return Err(format!("expected a value to compare {:?} with", operand)),
+                };
+
+                Ok(SearchFilter::Compare(operand, comparator, value))
+            }
+            _ => Err("expected a comparison".to_owned()),
+        }
+    }
+}
+
+impl FromStr for SearchFilter {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parser = Parser {
+            tokens: tokenize(s)?,
+            position: 0,
+        };
+
+        let filter = parser
+            .or()
+            .map_err(|err| format!("invalid filter '{}': {}", s, err))?;
+        if parser.peek().is_some() {
+            return Err(format!("invalid filter '{}': unexpected trailing input", s));
+        }
+
+        Ok(filter)
+    }
+}
+
+/// A SearchGameSessions sort expression (operand ASC|DESC), eg. playerSessionCount DESC
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchSort {
+    pub operand: Operand,
+    pub descending: bool,
+}
+
+impl SearchSort {
+    /// Orders two game sessions, sessions without the field go last
+    pub fn compare(&self, left: &GameSession, right: &GameSession) -> Ordering {
+        match (self.operand.value(left), self.operand.value(right)) {
+            (Some(left), Some(right)) => {
+                let ordering = left.compare(&right).unwrap_or(Ordering::Equal);
+                if self.descending {
+                    ordering.reverse()
+                } else {
+                    ordering
+                }
+            }
+            (Some(_), None) => Ordering::Less,
+            (None, Some(_)) => Ordering::Greater,
+            (None, None) => Ordering::Equal,
+        }
+    }
+}
+
+impl FromStr for SearchSort {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut words = s.split_whitespace();
+
+        let operand = words
+            .next()
+            .ok_or_else(|| format!("invalid sort '{}', expected operand ASC|DESC", s))?
+            .parse()?;
+        let descending = match words.next() {
+            None => false,
+            Some(order) if order.eq_ignore_ascii_case("ASC") => false,
+            Some(order) if order.eq_ignore_ascii_case("DESC") => true,
+            Some(_) => return Err(format!("invalid sort '{}', expected operand ASC|DESC", s)),
+        };
+
+        if words.next().is_some() {
+            return Err(format!("invalid sort '{}', expected operand ASC|DESC", s));
+        }
+
+        Ok(Self {
+            operand,
+            descending,
+        })
+    }
+}
+
+/// Applies a search to game sessions the way SearchGameSessions does, for the mock and
+/// GameLift local
+pub fn search<T>(
+    game_sessions: impl IntoIterator<Item = (GameSession, T)>,
+    filter: Option<&str>,
+    sort: Option<&str>,
+    limit: usize,
+) -> Result<Vec<(GameSession, T)>, String> {
+    let filter = filter.map(SearchFilter::from_str).transpose()?;
+    let sort = sort.map(SearchSort::from_str).transpose()?;
+
+    let mut results = game_sessions
+        .into_iter()
+        .filter(|(game_session, _)| match &filter {
+            Some(filter) => filter.matches(game_session),
+            None => true,
+        })
+        .collect::<Vec<_>>();
+
+    if let Some(sort) = &sort {
+        results.sort_by(|(left, _), (right, _)| sort.compare(left, right));
+    }
+    results.truncate(limit);
+
+    Ok(results)
+}
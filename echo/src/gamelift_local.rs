@@ -0,0 +1,1900 @@
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use chrono::Utc;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use crate::gamelift::{self, search};
+
+/// Header carrying the operation for AWS JSON protocol requests
+pub const TARGET_HEADER: &str = "X-Amz-Target";
+
+/// Content type for AWS JSON protocol requests and responses
+pub const JSON_CONTENT_TYPE: &str = "application/x-amz-json-1.1";
+
+/// Operation prefix for the GameLift control-plane API
+const GAMELIFT_TARGET_PREFIX: &str = "GameLift.";
+
+/// Operation prefix for the API used by local server processes
+pub const LOCAL_TARGET_PREFIX: &str = "EchoLocal.";
+
+/// Processes that haven't polled in this long are considered gone
+const PROCESS_TIMEOUT_SECONDS: i64 = 30;
+
+/// How long a placement can stay pending before it times out
+const PLACEMENT_TIMEOUT_SECONDS: i64 = 60;
+
+/// How long a matchmaking ticket can search before it times out
+const MATCHMAKING_TIMEOUT_SECONDS: i64 = 120;
+
+/// How long a terminating process has to end its session
+const TERMINATION_SECONDS: i64 = 30;
+
+const LOCAL_IP_ADDRESS: &str = "127.0.0.1";
+
+const GAME_SESSION_ACTIVATING: &str = "ACTIVATING";
+const GAME_SESSION_ACTIVE: &str = "ACTIVE";
+const GAME_SESSION_TERMINATED: &str = "TERMINATED";
+
+const PLAYER_SESSION_RESERVED: &str = "RESERVED";
+const PLAYER_SESSION_ACTIVE: &str = "ACTIVE";
+const PLAYER_SESSION_COMPLETED: &str = "COMPLETED";
+
+const PLACEMENT_PENDING: &str = "PENDING";
+const PLACEMENT_FULFILLED: &str = "FULFILLED";
+const PLACEMENT_TIMED_OUT: &str = "TIMED_OUT";
+const PLACEMENT_CANCELLED: &str = "CANCELLED";
+
+const MATCHMAKING_QUEUED: &str = "QUEUED";
+const MATCHMAKING_SEARCHING: &str = "SEARCHING";
+const MATCHMAKING_REQUIRES_ACCEPTANCE: &str = "REQUIRES_ACCEPTANCE";
+const MATCHMAKING_PLACING: &str = "PLACING";
+const MATCHMAKING_COMPLETED: &str = "COMPLETED";
+const MATCHMAKING_TIMED_OUT: &str = "TIMED_OUT";
+const MATCHMAKING_CANCELLED: &str = "CANCELLED";
+
+fn now() -> f64 {
+    Utc::now().timestamp_millis() as f64 / 1000.0
+}
+
+/// Error returned in the AWS JSON protocol error shape
+#[derive(Debug)]
+struct LocalError {
+    error_type: &'static str,
+    message: String,
+}
+
+impl LocalError {
+    fn new(error_type: &'static str, message: impl Into<String>) -> Self {
+        Self {
+            error_type,
+            message: message.into(),
+        }
+    }
+
+    fn invalid_request(message: impl Into<String>) -> Self {
+        Self::new("InvalidRequestException", message)
+    }
+
+    fn not_found(message: impl Into<String>) -> Self {
+        Self::new("NotFoundException", message)
+    }
+}
+
+#[derive(Debug, Serialize)]
+struct ErrorResponse<'a> {
+    #[serde(rename = "__type")]
+    error_type: &'a str,
+    message: &'a str,
+}
+
+type LocalResult<T> = Result<T, LocalError>;
+
+// control-plane model
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameProperty {
+    key: String,
+    value: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameSession {
+    game_session_id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    fleet_id: String,
+    status: String,
+    maximum_player_session_count: i32,
+    current_player_session_count: i32,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    game_properties: Vec<GameProperty>,
+    ip_address: String,
+    port: i32,
+    creation_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    termination_time: Option<f64>,
+}
+
+impl GameSession {
+    /// The session as the client sees it, to run searches against
+    fn searched(&self) -> gamelift::GameSession {
+        gamelift::GameSession {
+            game_session_id: self.game_session_id.clone(),
+            name: self.name.clone(),
+            status: self.status.clone(),
+            current_player_session_count: self.current_player_session_count,
+            maximum_player_session_count: self.maximum_player_session_count,
+            game_properties: self
+                .game_properties
+                .iter()
+                .map(|property| (property.key.clone(), property.value.clone()))
+                .collect(),
+            creation_time_millis: Some((self.creation_time * 1000.0) as i64),
+            ip_address: Some(self.ip_address.clone()),
+            port: u16::try_from(self.port).ok(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlayerSession {
+    player_session_id: String,
+    player_id: String,
+    game_session_id: String,
+    fleet_id: String,
+    status: String,
+    ip_address: String,
+    port: i32,
+    creation_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    termination_time: Option<f64>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct PlacedPlayerSession {
+    player_id: String,
+    player_session_id: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameSessionPlacement {
+    placement_id: String,
+    game_session_queue_name: String,
+    status: String,
+    maximum_player_session_count: i32,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_session_id: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_session_arn: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    ip_address: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    port: Option<i32>,
+    placed_player_sessions: Vec<PlacedPlayerSession>,
+    start_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_time: Option<f64>,
+
+    #[serde(skip)]
+    player_ids: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct Player {
+    player_id: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    player_attributes: Option<serde_json::Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    team: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    latency_in_ms: Option<HashMap<String, i32>>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameSessionConnectionInfo {
+    game_session_arn: String,
+    ip_address: String,
+    port: i32,
+    matched_player_sessions: Vec<PlacedPlayerSession>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct MatchmakingTicket {
+    ticket_id: String,
+    configuration_name: String,
+    status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_reason: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    status_message: Option<String>,
+    start_time: f64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    end_time: Option<f64>,
+    players: Vec<Player>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    game_session_connection_info: Option<GameSessionConnectionInfo>,
+
+    #[serde(skip)]
+    acceptance_start: Option<f64>,
+    #[serde(skip)]
+    accepted_player_ids: HashSet<String>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct Instance {
+    fleet_id: String,
+    instance_id: String,
+    ip_address: String,
+    operating_system: String,
+    r#type: String,
+    status: String,
+    creation_time: f64,
+}
+
+// local process model
+
+/// A game session started on a local process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProcessGameSession {
+    pub game_session_id: String,
+    pub fleet_id: String,
+    pub maximum_player_session_count: i32,
+    pub ip_address: String,
+    pub port: i32,
+}
+
+/// Termination of a local process
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProcessTermination {
+    pub termination_time: i64,
+}
+
+/// Events delivered to local processes when they poll
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "Type")]
+pub enum ProcessEvent {
+    StartGameSession(ProcessGameSession),
+    TerminateProcess(ProcessTermination),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProcessReadyRequest {
+    pub process_id: String,
+    pub port: i32,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PollProcessRequest {
+    pub process_id: String,
+    pub healthy: bool,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PollProcessResponse {
+    pub events: Vec<ProcessEvent>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct ProcessRequest {
+    pub process_id: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+pub struct PlayerSessionRequest {
+    pub process_id: String,
+    pub player_session_id: String,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct EmptyResponse {}
+
+struct LocalProcess {
+    port: i32,
+    game_session_id: Option<String>,
+    terminating: bool,
+    healthy: bool,
+    last_seen: i64,
+    events: VecDeque<ProcessEvent>,
+}
+
+impl LocalProcess {
+    fn idle(&self, now: i64) -> bool {
+        self.game_session_id.is_none()
+            && !self.terminating
+            && self.healthy
+            && now - self.last_seen < PROCESS_TIMEOUT_SECONDS
+    }
+}
+
+struct LocalState {
+    fleet_id: String,
+    creation_time: f64,
+
+    /// matches have to be accepted within this many seconds, if set
+    acceptance_timeout: Option<i64>,
+
+    processes: HashMap<String, LocalProcess>,
+    game_sessions: HashMap<String, GameSession>,
+    player_sessions: HashMap<String, PlayerSession>,
+    placements: HashMap<String, GameSessionPlacement>,
+    tickets: HashMap<String, MatchmakingTicket>,
+}
+
+impl LocalState {
+    fn new(fleet_id: String, acceptance_timeout: Option<i64>) -> Self {
+        Self {
+            fleet_id,
+            creation_time: now(),
+            acceptance_timeout,
+            processes: HashMap::new(),
+            game_sessions: HashMap::new(),
+            player_sessions: HashMap::new(),
+            placements: HashMap::new(),
+            tickets: HashMap::new(),
+        }
+    }
+
+    fn check_fleet(&self, fleet_id: &str) -> LocalResult<()> {
+        if fleet_id != self.fleet_id {
+            return Err(LocalError::not_found(format!(
+                "Fleet {} not found",
+                fleet_id
+            )));
+        }
+        Ok(())
+    }
+
+    fn process(&mut self, process_id: &str) -> LocalResult<&mut LocalProcess> {
+        self.processes
+            .get_mut(process_id)
+            .ok_or_else(|| LocalError::not_found(format!("Process {} not found", process_id)))
+    }
+
+    fn player_count(&self, game_session_id: &str) -> i32 {
+        self.player_sessions
+            .values()
+            .filter(|player_session| {
+                player_session.game_session_id == game_session_id
+                    && player_session.status != PLAYER_SESSION_COMPLETED
+            })
+            .count() as i32
+    }
+
+    fn game_session(&self, game_session_id: &str) -> LocalResult<GameSession> {
+        let mut game_session = self
+            .game_sessions
+            .get(game_session_id)
+            .cloned()
+            .ok_or_else(|| {
+                LocalError::not_found(format!("Game session {} not found", game_session_id))
+            })?;
+        game_session.current_player_session_count = self.player_count(game_session_id);
+
+        Ok(game_session)
+    }
+
+    /// Starts a new game session on an idle process, if there is one
+    fn place_game_session(
+        &mut self,
+        name: Option<String>,
+        maximum_player_session_count: i32,
+        game_properties: Vec<GameProperty>,
+    ) -> Option<GameSession> {
+        let now_seconds = Utc::now().timestamp();
+        let process_id = self
+            .processes
+            .iter()
+            .find(|(_, process)| process.idle(now_seconds))
+            .map(|(process_id, _)| process_id.clone())?;
+
+        let process = self.processes.get_mut(&process_id).unwrap();
+
+        let game_session = GameSession {
+            game_session_id: format!(
+                "arn:aws:gamelift:local::gamesession/{}/gsess-{}",
+                self.fleet_id,
+                Uuid::new_v4()
+            ),
+            name,
+            fleet_id: self.fleet_id.clone(),
+            status: GAME_SESSION_ACTIVATING.to_string(),
+            maximum_player_session_count,
+            current_player_session_count: 0,
+            game_properties,
+            ip_address: LOCAL_IP_ADDRESS.to_string(),
+            port: process.port,
+            creation_time: now(),
+            termination_time: None,
+        };
+
+        info!(
+            "Placing game session {} on process {} (port {})",
+            game_session.game_session_id, process_id, process.port
+        );
+
+        process.game_session_id = Some(game_session.game_session_id.clone());
+        process
+            .events
+            .push_back(ProcessEvent::StartGameSession(ProcessGameSession {
+                game_session_id: game_session.game_session_id.clone(),
+                fleet_id: game_session.fleet_id.clone(),
+                maximum_player_session_count,
+                ip_address: game_session.ip_address.clone(),
+                port: game_session.port,
+            }));
+
+        self.game_sessions
+            .insert(game_session.game_session_id.clone(), game_session.clone());
+
+        Some(game_session)
+    }
+
+    fn create_player_session(
+        &mut self,
+        game_session_id: &str,
+        player_id: String,
+    ) -> LocalResult<PlayerSession> {
+        let game_session = self.game_session(game_session_id)?;
+        if game_session.status != GAME_SESSION_ACTIVATING
+            && game_session.status != GAME_SESSION_ACTIVE
+        {
+            return Err(LocalError::new(
+                "InvalidGameSessionStatusException",
+                format!(
+                    "Game session {} is {}",
+                    game_session_id, game_session.status
+                ),
+            ));
+        }
+
+        if game_session.current_player_session_count >= game_session.maximum_player_session_count {
+            return Err(LocalError::new(
+                "GameSessionFullException",
+                format!("Game session {} is full", game_session_id),
+            ));
+        }
+
+        let player_session = PlayerSession {
+            player_session_id: format!("psess-{}", Uuid::new_v4()),
+            player_id,
+            game_session_id: game_session.game_session_id,
+            fleet_id: game_session.fleet_id,
+            status: PLAYER_SESSION_RESERVED.to_string(),
+            ip_address: game_session.ip_address,
+            port: game_session.port,
+            creation_time: now(),
+            termination_time: None,
+        };
+
+        self.player_sessions.insert(
+            player_session.player_session_id.clone(),
+            player_session.clone(),
+        );
+
+        Ok(player_session)
+    }
+
+    /// Places a game session for the given players, if there's an idle process
+    fn place_players(
+        &mut self,
+        maximum_player_session_count: i32,
+        player_ids: &[String],
+    ) -> LocalResult<Option<(GameSession, Vec<PlacedPlayerSession>)>> {
+        // checked up front, a game session placed for players that don't fit would be left behind
+        if player_ids.len() > maximum_player_session_count.max(0) as usize {
+            return Err(LocalError::invalid_request(format!(
+                "{} players don't fit in a game session of {}",
+                player_ids.len(),
+                maximum_player_session_count
+            )));
+        }
+
+        let game_session =
+            match self.place_game_session(None, maximum_player_session_count, Vec::new()) {
+                Some(game_session) => game_session,
+                None => return Ok(None),
+            };
+
+        let mut placed_player_sessions = Vec::with_capacity(player_ids.len());
+        for player_id in player_ids {
+            let player_session =
+                self.create_player_session(&game_session.game_session_id, player_id.clone())?;
+
+            placed_player_sessions.push(PlacedPlayerSession {
+                player_id: player_session.player_id,
+                player_session_id: player_session.player_session_id,
+            });
+        }
+
+        Ok(Some((game_session, placed_player_sessions)))
+    }
+
+    fn end_process(&mut self, process_id: &str) {
+        let process = match self.processes.get_mut(process_id) {
+            Some(process) => process,
+            None => return,
+        };
+
+        if let Some(game_session_id) = process.game_session_id.take() {
+            if let Some(game_session) = self.game_sessions.get_mut(&game_session_id) {
+                game_session.status = GAME_SESSION_TERMINATED.to_string();
+                game_session.termination_time = Some(now());
+            }
+
+            for player_session in self.player_sessions.values_mut() {
+                if player_session.game_session_id == game_session_id
+                    && player_session.status != PLAYER_SESSION_COMPLETED
+                {
+                    player_session.status = PLAYER_SESSION_COMPLETED.to_string();
+                    player_session.termination_time = Some(now());
+                }
+            }
+        }
+
+        // GameLift shuts down processes that end, let it know it's time to go
+        if !process.terminating {
+            process.terminating = true;
+            process
+                .events
+                .push_back(ProcessEvent::TerminateProcess(ProcessTermination {
+                    termination_time: Utc::now().timestamp() + TERMINATION_SECONDS,
+                }));
+        }
+    }
+
+    /// Drops processes that have stopped polling
+    fn prune_processes(&mut self) {
+        let now_seconds = Utc::now().timestamp();
+        let stale = self
+            .processes
+            .iter()
+            .filter(|(_, process)| now_seconds - process.last_seen >= PROCESS_TIMEOUT_SECONDS)
+            .map(|(process_id, _)| process_id.clone())
+            .collect::<Vec<_>>();
+
+        for process_id in stale {
+            warn!("Process {} stopped polling, removing", process_id);
+
+            self.end_process(&process_id);
+            self.processes.remove(&process_id);
+        }
+    }
+}
+
+// control-plane operations
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateGameSessionRequest {
+    fleet_id: Option<String>,
+    #[serde(default)]
+    name: Option<String>,
+    maximum_player_session_count: i32,
+    #[serde(default)]
+    game_properties: Vec<GameProperty>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreateGameSessionResponse {
+    game_session: GameSession,
+}
+
+fn create_game_session(
+    state: &mut LocalState,
+    request: CreateGameSessionRequest,
+) -> LocalResult<CreateGameSessionResponse> {
+    let fleet_id = request
+        .fleet_id
+        .ok_or_else(|| LocalError::invalid_request("FleetId is required"))?;
+    state.check_fleet(&fleet_id)?;
+
+    let game_session = state
+        .place_game_session(
+            request.name,
+            request.maximum_player_session_count,
+            request.game_properties,
+        )
+        .ok_or_else(|| {
+            LocalError::new(
+                "FleetCapacityExceededException",
+                "No available processes, start one with echo gamelift --local",
+            )
+        })?;
+
+    Ok(CreateGameSessionResponse { game_session })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeGameSessionsRequest {
+    #[serde(default)]
+    fleet_id: Option<String>,
+    #[serde(default)]
+    game_session_id: Option<String>,
+    #[serde(default)]
+    status_filter: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeGameSessionsResponse {
+    game_sessions: Vec<GameSession>,
+}
+
+fn describe_game_sessions(
+    state: &mut LocalState,
+    request: DescribeGameSessionsRequest,
+) -> LocalResult<DescribeGameSessionsResponse> {
+    if let Some(fleet_id) = &request.fleet_id {
+        state.check_fleet(fleet_id)?;
+    }
+
+    let game_sessions = match request.game_session_id {
+        Some(game_session_id) => vec![state.game_session(&game_session_id)?],
+        None => {
+            let mut game_sessions = Vec::with_capacity(state.game_sessions.len());
+            for game_session_id in state.game_sessions.keys() {
+                game_sessions.push(state.game_session(game_session_id)?);
+            }
+            game_sessions
+        }
+    };
+
+    let game_sessions = game_sessions
+        .into_iter()
+        .filter(|game_session| match &request.status_filter {
+            Some(status) => game_session.status == *status,
+            None => true,
+        })
+        .collect();
+
+    Ok(DescribeGameSessionsResponse { game_sessions })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SearchGameSessionsRequest {
+    #[serde(default)]
+    fleet_id: Option<String>,
+    #[serde(default)]
+    alias_id: Option<String>,
+    #[serde(default)]
+    filter_expression: Option<String>,
+    #[serde(default)]
+    sort_expression: Option<String>,
+    #[serde(default)]
+    limit: Option<i32>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct SearchGameSessionsResponse {
+    game_sessions: Vec<GameSession>,
+}
+
+/// Searches active game sessions, there are no aliases locally
+fn search_game_sessions(
+    state: &mut LocalState,
+    request: SearchGameSessionsRequest,
+) -> LocalResult<SearchGameSessionsResponse> {
+    match (&request.fleet_id, &request.alias_id) {
+        (Some(fleet_id), None) => state.check_fleet(fleet_id)?,
+        (None, Some(alias_id)) => {
+            return Err(LocalError::not_found(format!(
+                "Alias {} not found",
+                alias_id
+            )))
+        }
+        _ => {
+            return Err(LocalError::invalid_request(
+                "Exactly one of FleetId or AliasId is required",
+            ))
+        }
+    }
+
+    let mut game_sessions = Vec::with_capacity(state.game_sessions.len());
+    for game_session_id in state.game_sessions.keys() {
+        let game_session = state.game_session(game_session_id)?;
+        if game_session.status == GAME_SESSION_ACTIVE {
+            game_sessions.push((game_session.searched(), game_session));
+        }
+    }
+
+    let game_sessions = search::search(
+        game_sessions,
+        request.filter_expression.as_deref(),
+        request.sort_expression.as_deref(),
+        request.limit.unwrap_or(20).max(0) as usize,
+    )
+    .map_err(LocalError::invalid_request)?
+    .into_iter()
+    .map(|(_, game_session)| game_session)
+    .collect();
+
+    Ok(SearchGameSessionsResponse { game_sessions })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreatePlayerSessionRequest {
+    game_session_id: String,
+    player_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct CreatePlayerSessionResponse {
+    player_session: PlayerSession,
+}
+
+fn create_player_session(
+    state: &mut LocalState,
+    request: CreatePlayerSessionRequest,
+) -> LocalResult<CreatePlayerSessionResponse> {
+    let player_session =
+        state.create_player_session(&request.game_session_id, request.player_id)?;
+
+    Ok(CreatePlayerSessionResponse { player_session })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeInstancesRequest {
+    fleet_id: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeInstancesResponse {
+    instances: Vec<Instance>,
+}
+
+fn describe_instances(
+    state: &mut LocalState,
+    request: DescribeInstancesRequest,
+) -> LocalResult<DescribeInstancesResponse> {
+    state.check_fleet(&request.fleet_id)?;
+
+    Ok(DescribeInstancesResponse {
+        instances: vec![Instance {
+            fleet_id: state.fleet_id.clone(),
+            instance_id: "i-local".to_string(),
+            ip_address: LOCAL_IP_ADDRESS.to_string(),
+            operating_system: "AMAZON_LINUX_2".to_string(),
+            r#type: "local".to_string(),
+            status: "Active".to_string(),
+            creation_time: state.creation_time,
+        }],
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DesiredPlayerSession {
+    player_id: String,
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StartGameSessionPlacementRequest {
+    placement_id: String,
+    game_session_queue_name: String,
+    maximum_player_session_count: i32,
+    #[serde(default)]
+    desired_player_sessions: Vec<DesiredPlayerSession>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct GameSessionPlacementResponse {
+    game_session_placement: GameSessionPlacement,
+}
+
+/// Tries to fulfill (or time out) a pending placement
+fn update_placement(state: &mut LocalState, placement_id: &str) -> LocalResult<()> {
+    let placement = match state.placements.get(placement_id) {
+        Some(placement) if placement.status == PLACEMENT_PENDING => placement.clone(),
+        _ => return Ok(()),
+    };
+
+    let placed = state.place_players(
+        placement.maximum_player_session_count,
+        &placement.player_ids,
+    )?;
+
+    let placement = state.placements.get_mut(placement_id).unwrap();
+    match placed {
+        Some((game_session, placed_player_sessions)) => {
+            info!(
+                "Placement {} fulfilled: {}",
+                placement_id, game_session.game_session_id
+            );
+
+            placement.status = PLACEMENT_FULFILLED.to_string();
+            placement.game_session_id = Some(game_session.game_session_id.clone());
+            placement.game_session_arn = Some(game_session.game_session_id);
+            placement.ip_address = Some(game_session.ip_address);
+            placement.port = Some(game_session.port);
+            placement.placed_player_sessions = placed_player_sessions;
+            placement.end_time = Some(now());
+        }
+        None => {
+            if now() - placement.start_time >= PLACEMENT_TIMEOUT_SECONDS as f64 {
+                info!("Placement {} timed out", placement_id);
+
+                placement.status = PLACEMENT_TIMED_OUT.to_string();
+                placement.end_time = Some(now());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+fn start_game_session_placement(
+    state: &mut LocalState,
+    request: StartGameSessionPlacementRequest,
+) -> LocalResult<GameSessionPlacementResponse> {
+    if state.placements.contains_key(&request.placement_id) {
+        return Err(LocalError::invalid_request(format!(
+            "Placement {} already exists",
+            request.placement_id
+        )));
+    }
+
+    if request.desired_player_sessions.len() > request.maximum_player_session_count.max(0) as usize
+    {
+        return Err(LocalError::invalid_request(format!(
+            "{} desired player sessions don't fit in MaximumPlayerSessionCount {}",
+            request.desired_player_sessions.len(),
+            request.maximum_player_session_count
+        )));
+    }
+
+    let placement = GameSessionPlacement {
+        placement_id: request.placement_id.clone(),
+        game_session_queue_name: request.game_session_queue_name,
+        status: PLACEMENT_PENDING.to_string(),
+        maximum_player_session_count: request.maximum_player_session_count,
+        game_session_id: None,
+        game_session_arn: None,
+        ip_address: None,
+        port: None,
+        placed_player_sessions: Vec::new(),
+        start_time: now(),
+        end_time: None,
+        player_ids: request
+            .desired_player_sessions
+            .into_iter()
+            .map(|desired_player_session| desired_player_session.player_id)
+            .collect(),
+    };
+
+    state
+        .placements
+        .insert(request.placement_id.clone(), placement.clone());
+
+    Ok(GameSessionPlacementResponse {
+        game_session_placement: placement,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeGameSessionPlacementRequest {
+    placement_id: String,
+}
+
+fn describe_game_session_placement(
+    state: &mut LocalState,
+    request: DescribeGameSessionPlacementRequest,
+) -> LocalResult<GameSessionPlacementResponse> {
+    update_placement(state, &request.placement_id)?;
+
+    let placement = state
+        .placements
+        .get(&request.placement_id)
+        .cloned()
+        .ok_or_else(|| {
+            LocalError::not_found(format!("Placement {} not found", request.placement_id))
+        })?;
+
+    Ok(GameSessionPlacementResponse {
+        game_session_placement: placement,
+    })
+}
+
+fn stop_game_session_placement(
+    state: &mut LocalState,
+    request: DescribeGameSessionPlacementRequest,
+) -> LocalResult<GameSessionPlacementResponse> {
+    let placement = state
+        .placements
+        .get_mut(&request.placement_id)
+        .ok_or_else(|| {
+            LocalError::not_found(format!("Placement {} not found", request.placement_id))
+        })?;
+
+    if placement.status == PLACEMENT_PENDING {
+        info!("Placement {} cancelled", request.placement_id);
+
+        placement.status = PLACEMENT_CANCELLED.to_string();
+        placement.end_time = Some(now());
+    }
+
+    Ok(GameSessionPlacementResponse {
+        game_session_placement: placement.clone(),
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StartMatchmakingRequest {
+    #[serde(default)]
+    ticket_id: Option<String>,
+    configuration_name: String,
+    players: Vec<Player>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct StartMatchmakingResponse {
+    matchmaking_ticket: MatchmakingTicket,
+}
+
+/// Places all of a ticket's players together, or times the ticket out
+fn place_ticket(state: &mut LocalState, ticket: &MatchmakingTicket) -> LocalResult<()> {
+    let player_ids = ticket
+        .players
+        .iter()
+        .map(|player| player.player_id.clone())
+        .collect::<Vec<_>>();
+
+    let placed = state.place_players(player_ids.len().max(1) as i32, &player_ids)?;
+
+    let ticket = state.tickets.get_mut(&ticket.ticket_id).unwrap();
+    match placed {
+        Some((game_session, placed_player_sessions)) => {
+            info!(
+                "Ticket {} completed: {}",
+                ticket.ticket_id, game_session.game_session_id
+            );
+
+            ticket.status = MATCHMAKING_COMPLETED.to_string();
+            ticket.end_time = Some(now());
+            ticket.game_session_connection_info = Some(GameSessionConnectionInfo {
+                game_session_arn: game_session.game_session_id,
+                ip_address: game_session.ip_address,
+                port: game_session.port,
+                matched_player_sessions: placed_player_sessions,
+            });
+        }
+        None => {
+            if now() - ticket.start_time >= MATCHMAKING_TIMEOUT_SECONDS as f64 {
+                info!("Ticket {} timed out", ticket.ticket_id);
+
+                ticket.status = MATCHMAKING_TIMED_OUT.to_string();
+                ticket.end_time = Some(now());
+            }
+        }
+    }
+
+    Ok(())
+}
+
+/// Moves a ticket along, matching is just placing all of the ticket's players together
+///
+/// With acceptance required, the match has to be accepted by every player before it's placed
+fn update_ticket(state: &mut LocalState, ticket_id: &str) -> LocalResult<()> {
+    let ticket = match state.tickets.get(ticket_id) {
+        Some(ticket) => ticket.clone(),
+        None => return Ok(()),
+    };
+
+    match ticket.status.as_str() {
+        MATCHMAKING_QUEUED => {
+            state.tickets.get_mut(ticket_id).unwrap().status = MATCHMAKING_SEARCHING.to_string();
+        }
+        MATCHMAKING_SEARCHING if state.acceptance_timeout.is_some() => {
+            info!("Ticket {} requires acceptance", ticket_id);
+
+            let ticket = state.tickets.get_mut(ticket_id).unwrap();
+            ticket.status = MATCHMAKING_REQUIRES_ACCEPTANCE.to_string();
+            ticket.acceptance_start = Some(now());
+        }
+        MATCHMAKING_SEARCHING | MATCHMAKING_PLACING => place_ticket(state, &ticket)?,
+        MATCHMAKING_REQUIRES_ACCEPTANCE => {
+            let acceptance_timeout = state.acceptance_timeout.unwrap_or_default();
+
+            let ticket = state.tickets.get_mut(ticket_id).unwrap();
+            if ticket
+                .players
+                .iter()
+                .all(|player| ticket.accepted_player_ids.contains(&player.player_id))
+            {
+                info!("Ticket {} accepted", ticket_id);
+
+                ticket.status = MATCHMAKING_PLACING.to_string();
+            } else if now() - ticket.acceptance_start.unwrap_or_default()
+                >= acceptance_timeout as f64
+            {
+                info!("Ticket {} acceptance timed out", ticket_id);
+
+                ticket.status = MATCHMAKING_CANCELLED.to_string();
+                ticket.status_reason = Some("ACCEPTANCE_TIMEOUT".to_string());
+                ticket.end_time = Some(now());
+            }
+        }
+        _ => (),
+    }
+
+    Ok(())
+}
+
+fn start_matchmaking(
+    state: &mut LocalState,
+    request: StartMatchmakingRequest,
+) -> LocalResult<StartMatchmakingResponse> {
+    if request.players.is_empty() {
+        return Err(LocalError::invalid_request("Players is required"));
+    }
+
+    let ticket_id = request
+        .ticket_id
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    if state.tickets.contains_key(&ticket_id) {
+        return Err(LocalError::invalid_request(format!(
+            "Ticket {} already exists",
+            ticket_id
+        )));
+    }
+
+    let ticket = MatchmakingTicket {
+        ticket_id: ticket_id.clone(),
+        configuration_name: request.configuration_name,
+        status: MATCHMAKING_QUEUED.to_string(),
+        status_reason: None,
+        status_message: None,
+        start_time: now(),
+        end_time: None,
+        players: request.players,
+        game_session_connection_info: None,
+        acceptance_start: None,
+        accepted_player_ids: HashSet::new(),
+    };
+
+    state.tickets.insert(ticket_id, ticket.clone());
+
+    Ok(StartMatchmakingResponse {
+        matchmaking_ticket: ticket,
+    })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeMatchmakingRequest {
+    ticket_ids: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "PascalCase")]
+struct DescribeMatchmakingResponse {
+    ticket_list: Vec<MatchmakingTicket>,
+}
+
+fn describe_matchmaking(
+    state: &mut LocalState,
+    request: DescribeMatchmakingRequest,
+) -> LocalResult<DescribeMatchmakingResponse> {
+    let mut ticket_list = Vec::with_capacity(request.ticket_ids.len());
+    for ticket_id in request.ticket_ids {
+        update_ticket(state, &ticket_id)?;
+
+        if let Some(ticket) = state.tickets.get(&ticket_id) {
+            ticket_list.push(ticket.clone());
+        }
+    }
+
+    Ok(DescribeMatchmakingResponse { ticket_list })
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct StopMatchmakingRequest {
+    ticket_id: String,
+}
+
+fn stop_matchmaking(
+    state: &mut LocalState,
+    request: StopMatchmakingRequest,
+) -> LocalResult<EmptyResponse> {
+    let ticket = state
+        .tickets
+        .get_mut(&request.ticket_id)
+        .ok_or_else(|| LocalError::not_found(format!("Ticket {} not found", request.ticket_id)))?;
+
+    match ticket.status.as_str() {
+        MATCHMAKING_QUEUED | MATCHMAKING_SEARCHING | MATCHMAKING_REQUIRES_ACCEPTANCE => {
+            info!("Ticket {} cancelled", request.ticket_id);
+
+            ticket.status = MATCHMAKING_CANCELLED.to_string();
+            ticket.end_time = Some(now());
+
+            Ok(EmptyResponse {})
+        }
+        status => Err(LocalError::invalid_request(format!(
+            "Ticket {} can't be stopped while {}",
+            request.ticket_id, status
+        ))),
+    }
+}
+
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct AcceptMatchRequest {
+    ticket_id: String,
+    player_ids: Vec<String>,
+    acceptance_type: String,
+}
+
+fn accept_match(state: &mut LocalState, request: AcceptMatchRequest) -> LocalResult<EmptyResponse> {
+    let ticket = state
+        .tickets
+        .get_mut(&request.ticket_id)
+        .ok_or_else(|| LocalError::not_found(format!("Ticket {} not found", request.ticket_id)))?;
+
+    if ticket.status != MATCHMAKING_REQUIRES_ACCEPTANCE {
+        return Err(LocalError::invalid_request(format!(
+            "Ticket {} doesn't require acceptance ({})",
+            request.ticket_id, ticket.status
+        )));
+    }
+
+    if let Some(player_id) = request.player_ids.iter().find(|player_id| {
+        !ticket
+            .players
+            .iter()
+            .any(|player| player.player_id == **player_id)
+    }) {
+        return Err(LocalError::invalid_request(format!(
+            "Player {} is not on ticket {}",
+            player_id, request.ticket_id
+        )));
+    }
+
+    match request.acceptance_type.as_str() {
+        "ACCEPT" => {
+            info!(
+                "Ticket {} accepted by {:?}",
+                request.ticket_id, request.player_ids
+            );
+
+            ticket.accepted_player_ids.extend(request.player_ids);
+        }
+        "REJECT" => {
+            info!(
+                "Ticket {} rejected by {:?}",
+                request.ticket_id, request.player_ids
+            );
+
+            ticket.status = MATCHMAKING_CANCELLED.to_string();
+            ticket.status_reason = Some("REJECTED".to_string());
+            ticket.end_time = Some(now());
+        }
+        acceptance_type => {
+            return Err(LocalError::invalid_request(format!(
+                "Invalid acceptance type {}",
+                acceptance_type
+            )))
+        }
+    }
+
+    Ok(EmptyResponse {})
+}
+
+// local process operations
+
+fn process_ready(
+    state: &mut LocalState,
+    request: ProcessReadyRequest,
+) -> LocalResult<EmptyResponse> {
+    info!(
+        "Process {} ready on port {}",
+        request.process_id, request.port
+    );
+
+    state.processes.insert(
+        request.process_id,
+        LocalProcess {
+            port: request.port,
+            game_session_id: None,
+            terminating: false,
+            healthy: true,
+            last_seen: Utc::now().timestamp(),
+            events: VecDeque::new(),
+        },
+    );
+
+    Ok(EmptyResponse {})
+}
+
+fn poll_process(
+    state: &mut LocalState,
+    request: PollProcessRequest,
+) -> LocalResult<PollProcessResponse> {
+    let process = state.process(&request.process_id)?;
+    process.healthy = request.healthy;
+    process.last_seen = Utc::now().timestamp();
+
+    Ok(PollProcessResponse {
+        events: process.events.drain(..).collect(),
+    })
+}
+
+fn activate_game_session(
+    state: &mut LocalState,
+    request: ProcessRequest,
+) -> LocalResult<EmptyResponse> {
+    let game_session_id = state
+        .process(&request.process_id)?
+        .game_session_id
+        .clone()
+        .ok_or_else(|| LocalError::invalid_request("No game session to activate"))?;
+
+    if let Some(game_session) = state.game_sessions.get_mut(&game_session_id) {
+        info!("Game session {} active", game_session_id);
+        game_session.status = GAME_SESSION_ACTIVE.to_string();
+    }
+
+    Ok(EmptyResponse {})
+}
+
+fn update_player_session(
+    state: &mut LocalState,
+    request: PlayerSessionRequest,
+    status: &str,
+) -> LocalResult<EmptyResponse> {
+    let game_session_id = state.process(&request.process_id)?.game_session_id.clone();
+
+    let player_session = state
+        .player_sessions
+        .get_mut(&request.player_session_id)
+        .filter(|player_session| Some(&player_session.game_session_id) == game_session_id.as_ref())
+        .ok_or_else(|| {
+            LocalError::not_found(format!(
+                "Player session {} not found",
+                request.player_session_id
+            ))
+        })?;
+
+    debug!(
+        "Player session {} {}",
+        player_session.player_session_id, status
+    );
+    player_session.status = status.to_string();
+    if status == PLAYER_SESSION_COMPLETED {
+        player_session.termination_time = Some(now());
+    }
+
+    Ok(EmptyResponse {})
+}
+
+fn accept_player_session(
+    state: &mut LocalState,
+    request: PlayerSessionRequest,
+) -> LocalResult<EmptyResponse> {
+    update_player_session(state, request, PLAYER_SESSION_ACTIVE)
+}
+
+fn remove_player_session(
+    state: &mut LocalState,
+    request: PlayerSessionRequest,
+) -> LocalResult<EmptyResponse> {
+    update_player_session(state, request, PLAYER_SESSION_COMPLETED)
+}
+
+fn process_ending(state: &mut LocalState, request: ProcessRequest) -> LocalResult<EmptyResponse> {
+    info!("Process {} ending", request.process_id);
+
+    state.process(&request.process_id)?;
+    state.end_process(&request.process_id);
+
+    Ok(EmptyResponse {})
+}
+
+fn call<Req, Resp>(
+    state: &mut LocalState,
+    body: &[u8],
+    operation: impl FnOnce(&mut LocalState, Req) -> LocalResult<Resp>,
+) -> LocalResult<Vec<u8>>
+where
+    Req: DeserializeOwned,
+    Resp: Serialize,
+{
+    let request = serde_json::from_slice(body)
+        .map_err(|err| LocalError::new("SerializationException", err.to_string()))?;
+
+    let response = operation(state, request)?;
+
+    serde_json::to_vec(&response)
+        .map_err(|err| LocalError::new("InternalServiceException", err.to_string()))
+}
+
+fn dispatch(state: &mut LocalState, target: &str, body: &[u8]) -> LocalResult<Vec<u8>> {
+    state.prune_processes();
+
+    if let Some(operation) = target.strip_prefix(GAMELIFT_TARGET_PREFIX) {
+        return match operation {
+            "CreateGameSession" => call(state, body, create_game_session),
+            "DescribeGameSessions" => call(state, body, describe_game_sessions),
+            "SearchGameSessions" => call(state, body, search_game_sessions),
+            "CreatePlayerSession" => call(state, body, create_player_session),
+            "DescribeInstances" => call(state, body, describe_instances),
+            "StartGameSessionPlacement" => call(state, body, start_game_session_placement),
+            "DescribeGameSessionPlacement" => call(state, body, describe_game_session_placement),
+            "StopGameSessionPlacement" => call(state, body, stop_game_session_placement),
+            "StartMatchmaking" => call(state, body, start_matchmaking),
+            "DescribeMatchmaking" => call(state, body, describe_matchmaking),
+            "StopMatchmaking" => call(state, body, stop_matchmaking),
+            "AcceptMatch" => call(state, body, accept_match),
+            _ => Err(LocalError::new(
+                "UnknownOperationException",
+                format!("Unsupported operation {}", operation),
+            )),
+        };
+    }
+
+    if let Some(operation) = target.strip_prefix(LOCAL_TARGET_PREFIX) {
+        return match operation {
+            "ProcessReady" => call(state, body, process_ready),
+            "PollProcess" => call(state, body, poll_process),
+            "ActivateGameSession" => call(state, body, activate_game_session),
+            "AcceptPlayerSession" => call(state, body, accept_player_session),
+            "RemovePlayerSession" => call(state, body, remove_player_session),
+            "ProcessEnding" => call(state, body, process_ending),
+            _ => Err(LocalError::new(
+                "UnknownOperationException",
+                format!("Unsupported operation {}", operation),
+            )),
+        };
+    }
+
+    Err(LocalError::new(
+        "UnknownOperationException",
+        format!("Unsupported target {}", target),
+    ))
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    state: Arc<Mutex<LocalState>>,
+) -> Result<Response<Body>, Infallible> {
+    let target = request
+        .headers()
+        .get(TARGET_HEADER)
+        .and_then(|target| target.to_str().ok())
+        .map(str::to_owned);
+
+    let res = match (request.method(), target) {
+        (&Method::POST, Some(target)) => {
+            let body = hyper::body::to_bytes(request.into_body()).await;
+            match body {
+                Ok(body) => {
+                    debug!("{}: {}", target, String::from_utf8_lossy(&body));
+
+                    let mut state = state.lock().unwrap();
+                    dispatch(&mut state, &target, &body)
+                }
+                Err(err) => Err(LocalError::new("SerializationException", err.to_string())),
+            }
+        }
+        _ => Err(LocalError::new(
+            "UnknownOperationException",
+            format!("Missing {} header", TARGET_HEADER),
+        )),
+    };
+
+    let (status, body) = match res {
+        Ok(body) => (StatusCode::OK, body),
+        Err(err) => {
+            warn!("{}: {}", err.error_type, err.message);
+
+            let body = serde_json::to_vec(&ErrorResponse {
+                error_type: err.error_type,
+                message: &err.message,
+            })
+            .unwrap_or_default();
+
+            (StatusCode::BAD_REQUEST, body)
+        }
+    };
+
+    Ok(Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, JSON_CONTENT_TYPE)
+        .body(Body::from(body))
+        .unwrap())
+}
+
+/// Runs a local stand-in for the GameLift control-plane API
+///
+/// Game sessions are placed on local server processes (echo gamelift --local)
+pub async fn run(
+    port: u16,
+    fleet_id: impl Into<String>,
+    acceptance_timeout: Option<u64>,
+) -> anyhow::Result<()> {
+    let state = Arc::new(Mutex::new(LocalState::new(
+        fleet_id.into(),
+        acceptance_timeout.map(|seconds| seconds as i64),
+    )));
+
+    let addr = SocketAddr::from(([127, 0, 0, 1], port));
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, state.clone())
+            }))
+        }
+    });
+
+    info!("GameLift local listening on {}", addr);
+
+    Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    info!("GameLift local stopped");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use super::*;
+
+    const FLEET_ID: &str = "fleet-123";
+
+    fn gamelift(state: &mut LocalState, operation: &str, request: Value) -> LocalResult<Value> {
+        let target = format!("{}{}", GAMELIFT_TARGET_PREFIX, operation);
+        let response = dispatch(state, &target, request.to_string().as_bytes())?;
+
+        Ok(serde_json::from_slice(&response).unwrap())
+    }
+
+    fn ready_process(state: &mut LocalState, process_id: &str, port: i32) {
+        let target = format!("{}ProcessReady", LOCAL_TARGET_PREFIX);
+        let request = json!({ "ProcessId": process_id, "Port": port });
+        dispatch(state, &target, request.to_string().as_bytes()).unwrap();
+    }
+
+    fn start_placement(
+        state: &mut LocalState,
+        placement_id: &str,
+        maximum_player_session_count: i32,
+        player_ids: &[&str],
+    ) -> LocalResult<Value> {
+        let desired_player_sessions = player_ids
+            .iter()
+            .map(|player_id| json!({ "PlayerId": player_id }))
+            .collect::<Vec<_>>();
+
+        gamelift(
+            state,
+            "StartGameSessionPlacement",
+            json!({
+                "PlacementId": placement_id,
+                "GameSessionQueueName": "echo-queue",
+                "MaximumPlayerSessionCount": maximum_player_session_count,
+                "DesiredPlayerSessions": desired_player_sessions,
+            }),
+        )
+    }
+
+    #[test]
+    fn oversized_placement_rejected() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+        ready_process(&mut state, "process-1", 8065);
+
+        let err = start_placement(&mut state, "placement-1", 2, &["a", "b", "c"]).unwrap_err();
+        assert_eq!(err.error_type, "InvalidRequestException");
+        assert!(state.placements.is_empty());
+        assert!(state.game_sessions.is_empty());
+
+        // the process is still free for a placement that fits
+        start_placement(&mut state, "placement-2", 2, &["a", "b"]).unwrap();
+        let response = gamelift(
+            &mut state,
+            "DescribeGameSessionPlacement",
+            json!({ "PlacementId": "placement-2" }),
+        )
+        .unwrap();
+
+        let placement = &response["GameSessionPlacement"];
+        assert_eq!(placement["Status"], PLACEMENT_FULFILLED);
+        assert_eq!(placement["Port"], 8065);
+        assert_eq!(
+            placement["PlacedPlayerSessions"].as_array().unwrap().len(),
+            2
+        );
+    }
+
+    #[test]
+    fn place_players_leaves_nothing_behind() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+        ready_process(&mut state, "process-1", 8065);
+
+        let player_ids = ["a".to_owned(), "b".to_owned()];
+        let err = state.place_players(1, &player_ids).unwrap_err();
+        assert_eq!(err.error_type, "InvalidRequestException");
+
+        assert!(state.game_sessions.is_empty());
+        assert!(state.player_sessions.is_empty());
+
+        let process = &state.processes["process-1"];
+        assert!(process.idle(Utc::now().timestamp()));
+        assert!(process.events.is_empty());
+
+        let (game_session, placed_player_sessions) =
+            state.place_players(2, &player_ids).unwrap().unwrap();
+        assert_eq!(game_session.maximum_player_session_count, 2);
+        assert_eq!(placed_player_sessions.len(), 2);
+        assert_eq!(state.player_count(&game_session.game_session_id), 2);
+    }
+
+    fn start_ticket(state: &mut LocalState, ticket_id: &str, player_ids: &[&str]) {
+        let players = player_ids
+            .iter()
+            .map(|player_id| json!({ "PlayerId": player_id }))
+            .collect::<Vec<_>>();
+
+        gamelift(
+            state,
+            "StartMatchmaking",
+            json!({
+                "TicketId": ticket_id,
+                "ConfigurationName": "echo",
+                "Players": players,
+            }),
+        )
+        .unwrap();
+    }
+
+    /// Describes a ticket, moving it along a step
+    fn describe_ticket(state: &mut LocalState, ticket_id: &str) -> Value {
+        let mut response = gamelift(
+            state,
+            "DescribeMatchmaking",
+            json!({ "TicketIds": [ticket_id] }),
+        )
+        .unwrap();
+
+        response["TicketList"][0].take()
+    }
+
+    fn accept_match(
+        state: &mut LocalState,
+        ticket_id: &str,
+        player_ids: &[&str],
+        acceptance_type: &str,
+    ) -> LocalResult<Value> {
+        gamelift(
+            state,
+            "AcceptMatch",
+            json!({
+                "TicketId": ticket_id,
+                "PlayerIds": player_ids,
+                "AcceptanceType": acceptance_type,
+            }),
+        )
+    }
+
+    fn describe_placement(state: &mut LocalState, placement_id: &str) -> Value {
+        let mut response = gamelift(
+            state,
+            "DescribeGameSessionPlacement",
+            json!({ "PlacementId": placement_id }),
+        )
+        .unwrap();
+
+        response["GameSessionPlacement"].take()
+    }
+
+    #[test]
+    fn ticket_lifecycle() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), Some(60));
+        ready_process(&mut state, "process-1", 8065);
+        start_ticket(&mut state, "ticket-1", &["a", "b"]);
+
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_SEARCHING
+        );
+
+        // too early to accept
+        let err = accept_match(&mut state, "ticket-1", &["a"], "ACCEPT").unwrap_err();
+        assert_eq!(err.error_type, "InvalidRequestException");
+
+        let ticket = describe_ticket(&mut state, "ticket-1");
+        assert_eq!(ticket["Status"], MATCHMAKING_REQUIRES_ACCEPTANCE);
+
+        let err = accept_match(&mut state, "ticket-1", &["c"], "ACCEPT").unwrap_err();
+        assert_eq!(err.message, "Player c is not on ticket ticket-1");
+        let err = accept_match(&mut state, "ticket-1", &["a"], "MAYBE").unwrap_err();
+        assert_eq!(err.message, "Invalid acceptance type MAYBE");
+
+        // waits for every player
+        accept_match(&mut state, "ticket-1", &["a"], "ACCEPT").unwrap();
+        let ticket = describe_ticket(&mut state, "ticket-1");
+        assert_eq!(ticket["Status"], MATCHMAKING_REQUIRES_ACCEPTANCE);
+
+        accept_match(&mut state, "ticket-1", &["b"], "ACCEPT").unwrap();
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_PLACING
+        );
+
+        let ticket = describe_ticket(&mut state, "ticket-1");
+        assert_eq!(ticket["Status"], MATCHMAKING_COMPLETED);
+        let connection_info = &ticket["GameSessionConnectionInfo"];
+        assert_eq!(connection_info["IpAddress"], LOCAL_IP_ADDRESS);
+        assert_eq!(connection_info["Port"], 8065);
+        let matched_player_ids = connection_info["MatchedPlayerSessions"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|player_session| player_session["PlayerId"].as_str().unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(matched_player_ids, ["a", "b"]);
+
+        let game_session_id = connection_info["GameSessionArn"].as_str().unwrap();
+        assert_eq!(state.player_count(game_session_id), 2);
+
+        // done tickets stay done
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_COMPLETED
+        );
+        let err = gamelift(
+            &mut state,
+            "StopMatchmaking",
+            json!({ "TicketId": "ticket-1" }),
+        )
+        .unwrap_err();
+        assert_eq!(err.error_type, "InvalidRequestException");
+    }
+
+    #[test]
+    fn tickets_without_acceptance_complete() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+        start_ticket(&mut state, "ticket-1", &["a"]);
+
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_SEARCHING
+        );
+        // keeps searching until there's a process to place on
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_SEARCHING
+        );
+
+        ready_process(&mut state, "process-1", 8065);
+        let ticket = describe_ticket(&mut state, "ticket-1");
+        assert_eq!(ticket["Status"], MATCHMAKING_COMPLETED);
+        assert_eq!(ticket["GameSessionConnectionInfo"]["Port"], 8065);
+    }
+
+    #[test]
+    fn tickets_time_out() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), Some(0));
+        ready_process(&mut state, "process-1", 8065);
+
+        // nobody accepts in time
+        start_ticket(&mut state, "ticket-1", &["a", "b"]);
+        describe_ticket(&mut state, "ticket-1");
+        assert_eq!(
+            describe_ticket(&mut state, "ticket-1")["Status"],
+            MATCHMAKING_REQUIRES_ACCEPTANCE
+        );
+        accept_match(&mut state, "ticket-1", &["a"], "ACCEPT").unwrap();
+        let ticket = describe_ticket(&mut state, "ticket-1");
+        assert_eq!(ticket["Status"], MATCHMAKING_CANCELLED);
+        assert_eq!(ticket["StatusReason"], "ACCEPTANCE_TIMEOUT");
+
+        // a player rejects
+        start_ticket(&mut state, "ticket-2", &["a", "b"]);
+        describe_ticket(&mut state, "ticket-2");
+        describe_ticket(&mut state, "ticket-2");
+        accept_match(&mut state, "ticket-2", &["b"], "REJECT").unwrap();
+        let ticket = describe_ticket(&mut state, "ticket-2");
+        assert_eq!(ticket["Status"], MATCHMAKING_CANCELLED);
+        assert_eq!(ticket["StatusReason"], "REJECTED");
+
+        // searching too long with nowhere to place
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+        start_ticket(&mut state, "ticket-3", &["a"]);
+        describe_ticket(&mut state, "ticket-3");
+        state.tickets.get_mut("ticket-3").unwrap().start_time -= MATCHMAKING_TIMEOUT_SECONDS as f64;
+        let ticket = describe_ticket(&mut state, "ticket-3");
+        assert_eq!(ticket["Status"], MATCHMAKING_TIMED_OUT);
+        assert!(ticket.get("GameSessionConnectionInfo").is_none());
+    }
+
+    #[test]
+    fn placement_fulfilled() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+        let placement = start_placement(&mut state, "placement-1", 4, &["a", "b"]).unwrap();
+        assert_eq!(
+            placement["GameSessionPlacement"]["Status"],
+            PLACEMENT_PENDING
+        );
+
+        // pending until a process is ready
+        let placement = describe_placement(&mut state, "placement-1");
+        assert_eq!(placement["Status"], PLACEMENT_PENDING);
+        assert!(placement.get("GameSessionArn").is_none());
+
+        ready_process(&mut state, "process-1", 8065);
+        let placement = describe_placement(&mut state, "placement-1");
+        assert_eq!(placement["Status"], PLACEMENT_FULFILLED);
+        assert_eq!(placement["IpAddress"], LOCAL_IP_ADDRESS);
+        assert_eq!(placement["Port"], 8065);
+        assert_eq!(placement["GameSessionId"], placement["GameSessionArn"]);
+        assert!(placement.get("EndTime").is_some());
+
+        let game_session_id = placement["GameSessionArn"].as_str().unwrap();
+        let game_session = state.game_session(game_session_id).unwrap();
+        assert_eq!(game_session.maximum_player_session_count, 4);
+        assert_eq!(game_session.current_player_session_count, 2);
+        assert!(state.processes["process-1"]
+            .events
+            .iter()
+            .any(|event| matches!(event, ProcessEvent::StartGameSession(_))));
+
+        // fulfilled placements can't be cancelled
+        let response = gamelift(
+            &mut state,
+            "StopGameSessionPlacement",
+            json!({ "PlacementId": "placement-1" }),
+        )
+        .unwrap();
+        assert_eq!(
+            response["GameSessionPlacement"]["Status"],
+            PLACEMENT_FULFILLED
+        );
+
+        let err = start_placement(&mut state, "placement-1", 4, &["c"]).unwrap_err();
+        assert_eq!(err.message, "Placement placement-1 already exists");
+    }
+
+    #[test]
+    fn placement_ends_without_a_process() {
+        let mut state = LocalState::new(FLEET_ID.to_owned(), None);
+
+        start_placement(&mut state, "placement-1", 2, &["a"]).unwrap();
+        state.placements.get_mut("placement-1").unwrap().start_time -=
+            PLACEMENT_TIMEOUT_SECONDS as f64;
+        assert_eq!(
+            describe_placement(&mut state, "placement-1")["Status"],
+            PLACEMENT_TIMED_OUT
+        );
+
+        start_placement(&mut state, "placement-2", 2, &["a"]).unwrap();
+        gamelift(
+            &mut state,
+            "StopGameSessionPlacement",
+            json!({ "PlacementId": "placement-2" }),
+        )
+        .unwrap();
+        // a process showing up later doesn't revive it
+        ready_process(&mut state, "process-1", 8065);
+        assert_eq!(
+            describe_placement(&mut state, "placement-2")["Status"],
+            PLACEMENT_CANCELLED
+        );
+        assert!(state.game_sessions.is_empty());
+    }
+
+    #[tokio::test]
+    async fn errors_in_the_gamelift_shape() {
+        let state = Arc::new(Mutex::new(LocalState::new(FLEET_ID.to_owned(), None)));
+
+        let request = |target: Option<&str>, body: &str| {
+            let mut request = Request::builder().method(Method::POST).uri("/");
+            if let Some(target) = target {
+                request = request.header(TARGET_HEADER, target);
+            }
+            request.body(Body::from(body.to_owned())).unwrap()
+        };
+
+        for (target, body, error_type, message) in [
+            (
+                Some("GameLift.DeleteFleet"),
+                "{}",
+                "UnknownOperationException",
+                "Unsupported operation DeleteFleet",
+            ),
+            (
+                Some("EchoLocal.Restart"),
+                "{}",
+                "UnknownOperationException",
+                "Unsupported operation Restart",
+            ),
+            (
+                Some("Lambda.Invoke"),
+                "{}",
+                "UnknownOperationException",
+                "Unsupported target Lambda.Invoke",
+            ),
+            (
+                None,
+                "{}",
+                "UnknownOperationException",
+                "Missing X-Amz-Target header",
+            ),
+            (
+                Some("GameLift.DescribeGameSessionPlacement"),
+                r#"{"PlacementId": "placement-1"}"#,
+                "NotFoundException",
+                "Placement placement-1 not found",
+            ),
+        ] {
+            let response = handle_request(request(target, body), state.clone())
+                .await
+                .unwrap();
+            assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+            assert_eq!(response.headers()[CONTENT_TYPE], JSON_CONTENT_TYPE);
+
+            let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+            assert_eq!(
+                serde_json::from_slice::<Value>(&body).unwrap(),
+                json!({ "__type": error_type, "message": message })
+            );
+        }
+
+        let response = handle_request(
+            request(Some("GameLift.DescribeMatchmaking"), "not json"),
+            state,
+        )
+        .await
+        .unwrap();
+        let body = hyper::body::to_bytes(response.into_body()).await.unwrap();
+        let body = serde_json::from_slice::<Value>(&body).unwrap();
+        assert_eq!(body["__type"], "SerializationException");
+    }
+}
@@ -0,0 +1,64 @@
+pub mod agones;
+#[cfg(test)]
+pub mod fake;
+#[cfg(test)]
+pub mod fake_gamelift;
+pub mod gamelift;
+pub mod gamelift_local;
+pub mod standalone;
+
+use std::future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+use tokio::sync::mpsc;
+
+pub type HostingFuture<'a, T> =
+    Pin<Box<dyn future::Future<Output = anyhow::Result<T>> + Send + 'a>>;
+
+type HealthCheckOutput = Pin<Box<dyn future::Future<Output = bool> + Send>>;
+pub type HealthCheck = Arc<dyn Fn() -> HealthCheckOutput + Send + Sync>;
+
+pub use echo_protocol::session::SessionInfo;
+
+/// Events raised by the hosting service
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostingEvent {
+    /// A game session should be started
+    StartSession(SessionInfo),
+
+    /// The running game session was updated
+    UpdateSession { description: String },
+
+    /// The process is being terminated
+    Terminate,
+}
+
+/// The service (GameLift, etc) that places sessions on this process
+///
+/// Session events are delivered through the sender passed to ready()
+pub trait HostingBackend: Send + Sync {
+    fn name(&self) -> &'static str;
+
+    /// Reports the process as ready to host sessions on the given port
+    fn ready(
+        &self,
+        port: u16,
+        log_paths: Vec<String>,
+        events: mpsc::UnboundedSender<HostingEvent>,
+        health_check: HealthCheck,
+    ) -> HostingFuture<'_, ()>;
+
+    /// Reports the session as started and ready for players
+    fn activate_session(&self) -> HostingFuture<'_, ()>;
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()>;
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()>;
+
+    /// Returns the time (in seconds since the epoch) the process will be terminated, if known
+    fn termination_time(&self) -> HostingFuture<'_, Option<i64>>;
+
+    /// Reports the process as ending, no further sessions will be hosted
+    fn process_ending(&self) -> HostingFuture<'_, ()>;
+}
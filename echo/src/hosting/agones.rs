@@ -0,0 +1,485 @@
+use std::future;
+
+use futures_util::{FutureExt, StreamExt};
+use serde::{Deserialize, Serialize};
+use tokio::{sync::mpsc, time};
+use tracing::{debug, info, warn};
+use uuid::Uuid;
+
+use super::{HealthCheck, HostingBackend, HostingEvent, HostingFuture, SessionInfo};
+
+/// How often to ping the SDK server for health
+const HEALTH_INTERVAL: time::Duration = time::Duration::from_secs(5);
+
+/// How long to wait before re-establishing a dropped watch
+const WATCH_RETRY_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+const STATE_ALLOCATED: &str = "Allocated";
+const STATE_SHUTDOWN: &str = "Shutdown";
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct ObjectMeta {
+    name: String,
+    uid: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GameServerStatus {
+    state: String,
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct GameServer {
+    object_meta: ObjectMeta,
+    status: GameServerStatus,
+}
+
+#[derive(Debug, Deserialize)]
+struct WatchResult {
+    result: GameServer,
+}
+
+#[derive(Debug, Serialize)]
+struct PlayerId {
+    #[serde(rename = "playerID")]
+    player_id: String,
+}
+
+#[derive(Debug, Serialize)]
+struct Empty {}
+
+/// Hosting on Agones through the SDK sidecar's local HTTP API
+///
+/// Player tracking uses the alpha player tracking API,
+/// which requires the PlayerTracking feature gate
+pub struct AgonesBackend {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl AgonesBackend {
+    pub fn new(sdk_port: u16) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            base_url: format!("http://localhost:{}", sdk_port),
+        }
+    }
+
+    async fn post(&self, path: &str, body: &impl Serialize) -> anyhow::Result<()> {
+        self.client
+            .post(format!("{}{}", self.base_url, path))
+            .json(body)
+            .send()
+            .await?
+            .error_for_status()?;
+
+        Ok(())
+    }
+}
+
+async fn health_loop(client: reqwest::Client, base_url: String, health_check: HealthCheck) {
+    let mut interval = time::interval(HEALTH_INTERVAL);
+    loop {
+        interval.tick().await;
+
+        debug!("health check");
+        if !health_check().await {
+            warn!("Unhealthy, skipping health ping");
+            continue;
+        }
+
+        let res = client
+            .post(format!("{}/health", base_url))
+            .json(&Empty {})
+            .send()
+            .await
+            .and_then(|response| response.error_for_status());
+        if let Err(err) = res {
+            warn!("Health ping failed: {}", err);
+        }
+    }
+}
+
+/// Watches the GameServer and raises session events as its state changes
+async fn watch_loop(
+    client: reqwest::Client,
+    base_url: String,
+    events: mpsc::UnboundedSender<HostingEvent>,
+) {
+    let mut state = String::new();
+    loop {
+        if let Err(err) = watch(&client, &base_url, &events, &mut state).await {
+            warn!("GameServer watch failed: {}", err);
+        }
+
+        if events.is_closed() {
+            return;
+        }
+
+        time::sleep(WATCH_RETRY_INTERVAL).await;
+    }
+}
+
+async fn watch(
+    client: &reqwest::Client,
+    base_url: &str,
+    events: &mpsc::UnboundedSender<HostingEvent>,
+    state: &mut String,
+) -> anyhow::Result<()> {
+    let response = client
+        .get(format!("{}/watch/gameserver", base_url))
+        .send()
+        .await?
+        .error_for_status()?;
+
+    // the watch is a stream of newline delimited results
+    let mut buf = Vec::new();
+    let mut stream = response.bytes_stream();
+    while let Some(chunk) = stream.next().await {
+        buf.extend_from_slice(&chunk?);
+
+        while let Some(idx) = buf.iter().position(|b| *b == b'\n') {
+            let line = buf.drain(..=idx).collect::<Vec<_>>();
+            if line.iter().all(u8::is_ascii_whitespace) {
+                continue;
+            }
+
+            let game_server = serde_json::from_slice::<WatchResult>(&line)?.result;
+            if game_server.status.state == *state {
+                continue;
+            }
+
+            info!(
+                "GameServer {} state: {}",
+                game_server.object_meta.name, game_server.status.state
+            );
+            *state = game_server.status.state.clone();
+
+            match state.as_str() {
+                STATE_ALLOCATED => {
+                    let session_id = if game_server.object_meta.uid.is_empty() {
+                        Uuid::new_v4().to_string()
+                    } else {
+                        game_server.object_meta.uid
+                    };
+
+                    events.send(HostingEvent::StartSession(SessionInfo {
+                        session_id,
+                        max_players: None,
+                    }))?;
+                }
+                STATE_SHUTDOWN => events.send(HostingEvent::Terminate)?,
+                _ => (),
+            }
+        }
+    }
+
+    Ok(())
+}
+
+impl HostingBackend for AgonesBackend {
+    fn name(&self) -> &'static str {
+        "agones"
+    }
+
+    fn ready(
+        &self,
+        port: u16,
+        _log_paths: Vec<String>,
+        events: mpsc::UnboundedSender<HostingEvent>,
+        health_check: HealthCheck,
+    ) -> HostingFuture<'_, ()> {
+        async move {
+            // health pings are required before ready
+            tokio::spawn(health_loop(
+                self.client.clone(),
+                self.base_url.clone(),
+                health_check,
+            ));
+
+            tokio::spawn(watch_loop(
+                self.client.clone(),
+                self.base_url.clone(),
+                events,
+            ));
+
+            info!("Ready on port {}", port);
+            self.post("/ready", &Empty {}).await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn activate_session(&self) -> HostingFuture<'_, ()> {
+        // allocation already marks the session as active
+        future::ready(Ok(())).boxed()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.post(
+                "/alpha/player/connect",
+                &PlayerId {
+                    player_id: player_session_id,
+                },
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.post(
+                "/alpha/player/disconnect",
+                &PlayerId {
+                    player_id: player_session_id,
+                },
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn termination_time(&self) -> HostingFuture<'_, Option<i64>> {
+        // Agones doesn't provide a termination time
+        future::ready(Ok(None)).boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        async move { self.post("/shutdown", &Empty {}).await }.boxed()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::convert::Infallible;
+    use std::net::SocketAddr;
+    use std::sync::{Arc, Mutex};
+
+    use hyper::{
+        body,
+        service::{make_service_fn, service_fn},
+        Body, Request, Response, Server,
+    };
+    use serde_json::{json, Value};
+    use tokio::sync::watch;
+
+    use super::*;
+
+    /// Local stand-in for the Agones SDK sidecar, recording the requests made to it
+    ///
+    /// Each GameServer watch gets a body the test writes watch results to
+    struct SdkServer {
+        requests: Mutex<Vec<(String, String, Value)>>,
+        requests_sender: watch::Sender<usize>,
+        watches: mpsc::UnboundedSender<body::Sender>,
+    }
+
+    impl SdkServer {
+        fn start() -> (Arc<Self>, u16, mpsc::UnboundedReceiver<body::Sender>) {
+            let (watches, watch_receiver) = mpsc::unbounded_channel();
+            let sdk = Arc::new(Self {
+                requests: Mutex::new(Vec::new()),
+                requests_sender: watch::channel(0).0,
+                watches,
+            });
+
+            let make_service = make_service_fn({
+                let sdk = sdk.clone();
+                move |_| {
+                    let sdk = sdk.clone();
+                    async move {
+                        Ok::<_, Infallible>(service_fn(move |request| {
+                            sdk.clone().handle_request(request)
+                        }))
+                    }
+                }
+            });
+
+            let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+            let port = server.local_addr().port();
+            tokio::spawn(server);
+
+            (sdk, port, watch_receiver)
+        }
+
+        async fn handle_request(
+            self: Arc<Self>,
+            request: Request<Body>,
+        ) -> Result<Response<Body>, Infallible> {
+            let method = request.method().to_string();
+            let path = request.uri().path().to_owned();
+            let body = body::to_bytes(request.into_body())
+                .await
+                .unwrap_or_default();
+            let body = serde_json::from_slice(&body).unwrap_or(Value::Null);
+
+            {
+                let mut requests = self.requests.lock().unwrap();
+                requests.push((method, path.clone(), body));
+                self.requests_sender.send_replace(requests.len());
+            }
+
+            if path == "/watch/gameserver" {
+                let (sender, body) = Body::channel();
+                let _ = self.watches.send(sender);
+
+                return Ok(Response::new(body));
+            }
+
+            Ok(Response::new(Body::from("{}")))
+        }
+
+        fn requests(&self) -> Vec<(String, String, Value)> {
+            self.requests.lock().unwrap().clone()
+        }
+
+        async fn wait_for_request(&self, method: &str, path: &str) -> Value {
+            let mut receiver = self.requests_sender.subscribe();
+            let find = || {
+                self.requests()
+                    .into_iter()
+                    .find(|request| request.0 == method && request.1 == path)
+                    .map(|request| request.2)
+            };
+
+            time::timeout(time::Duration::from_secs(5), async {
+                loop {
+                    if let Some(body) = find() {
+                        return body;
+                    }
+
+                    receiver.changed().await.unwrap();
+                }
+            })
+            .await
+            .unwrap_or_else(|_| panic!("no {} {} in {:?}", method, path, self.requests()))
+        }
+    }
+
+    async fn send_state(watch: &mut body::Sender, uid: &str, state: &str) {
+        let result = json!({
+            "result": {
+                "object_meta": { "name": "echo-gameserver", "uid": uid },
+                "status": { "state": state },
+            }
+        });
+
+        watch
+            .send_data(format!("{}\n", result).into())
+            .await
+            .unwrap();
+    }
+
+    async fn next_event(events: &mut mpsc::UnboundedReceiver<HostingEvent>) -> HostingEvent {
+        time::timeout(time::Duration::from_secs(5), events.recv())
+            .await
+            .expect("no hosting event")
+            .expect("hosting events closed")
+    }
+
+    fn healthy() -> HealthCheck {
+        Arc::new(|| future::ready(true).boxed())
+    }
+
+    #[tokio::test]
+    async fn agones_lifecycle() {
+        let (sdk, sdk_port, mut watches) = SdkServer::start();
+        let backend = AgonesBackend::new(sdk_port);
+
+        let (event_sender, mut events) = mpsc::unbounded_channel();
+        backend
+            .ready(7000, vec![], event_sender, healthy())
+            .await
+            .unwrap();
+
+        sdk.wait_for_request("POST", "/ready").await;
+        sdk.wait_for_request("POST", "/health").await;
+
+        let mut watch = time::timeout(time::Duration::from_secs(5), watches.recv())
+            .await
+            .unwrap()
+            .unwrap();
+
+        // repeated states are ignored, only allocation starts a session
+        send_state(&mut watch, "uid-1", "Ready").await;
+        send_state(&mut watch, "uid-1", "Ready").await;
+        send_state(&mut watch, "uid-1", "Allocated").await;
+        assert_eq!(
+            next_event(&mut events).await,
+            HostingEvent::StartSession(SessionInfo {
+                session_id: "uid-1".to_owned(),
+                max_players: None,
+            })
+        );
+
+        backend.activate_session().await.unwrap();
+        backend
+            .accept_player_session("player-session-1".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            sdk.wait_for_request("POST", "/alpha/player/connect").await,
+            json!({ "playerID": "player-session-1" })
+        );
+        backend
+            .remove_player_session("player-session-1".to_owned())
+            .await
+            .unwrap();
+        assert_eq!(
+            sdk.wait_for_request("POST", "/alpha/player/disconnect")
+                .await,
+            json!({ "playerID": "player-session-1" })
+        );
+
+        send_state(&mut watch, "uid-1", "Shutdown").await;
+        assert_eq!(next_event(&mut events).await, HostingEvent::Terminate);
+
+        assert_eq!(backend.termination_time().await.unwrap(), None);
+        backend.process_ending().await.unwrap();
+        sdk.wait_for_request("POST", "/shutdown").await;
+
+        assert!(events.try_recv().is_err());
+    }
+
+    #[tokio::test]
+    async fn agones_watch_reconnects() {
+        let (sdk, sdk_port, mut watches) = SdkServer::start();
+        let backend = AgonesBackend::new(sdk_port);
+
+        let (event_sender, mut events) = mpsc::unbounded_channel();
+        backend
+            .ready(7000, vec![], event_sender, healthy())
+            .await
+            .unwrap();
+
+        let mut watch = watches.recv().await.unwrap();
+        send_state(&mut watch, "uid-1", "Ready").await;
+        drop(watch);
+
+        // the state carries over, so the new watch's Ready isn't a change
+        let mut watch = time::timeout(time::Duration::from_secs(5), watches.recv())
+            .await
+            .unwrap()
+            .unwrap();
+        send_state(&mut watch, "uid-1", "Ready").await;
+        send_state(&mut watch, "", "Allocated").await;
+
+        match next_event(&mut events).await {
+            HostingEvent::StartSession(session) => assert!(!session.session_id.is_empty()),
+            event => panic!("expected a session start, got {:?}", event),
+        }
+        assert_eq!(
+            sdk.requests()
+                .iter()
+                .filter(|request| request.1 == "/watch/gameserver")
+                .count(),
+            2
+        );
+    }
+}
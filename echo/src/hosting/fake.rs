@@ -0,0 +1,159 @@
+use std::future;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use futures_util::FutureExt;
+use tokio::sync::{mpsc, watch};
+use tracing::info;
+
+use super::{HealthCheck, HostingBackend, HostingEvent, HostingFuture, SessionInfo};
+
+/// Calls made by the server into the fake backend
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum HostingCall {
+    Ready(u16),
+    ActivateSession,
+    AcceptPlayerSession(String),
+    RemovePlayerSession(String),
+    TerminationTime,
+    ProcessEnding,
+}
+
+/// In-memory hosting that records the calls made into it
+///
+/// Session events are raised by calling start_session() and terminate()
+pub struct FakeBackend {
+    events: Mutex<Option<mpsc::UnboundedSender<HostingEvent>>>,
+    health_check: Mutex<Option<HealthCheck>>,
+
+    termination_time: Mutex<Option<i64>>,
+
+    calls: Mutex<Vec<HostingCall>>,
+    calls_sender: watch::Sender<usize>,
+}
+
+impl Default for FakeBackend {
+    fn default() -> Self {
+        Self {
+            events: Mutex::new(None),
+            health_check: Mutex::new(None),
+            termination_time: Mutex::new(None),
+            calls: Mutex::new(Vec::new()),
+            calls_sender: watch::channel(0).0,
+        }
+    }
+}
+
+impl FakeBackend {
+    fn record(&self, call: HostingCall) {
+        let mut calls = self.calls.lock().unwrap();
+        calls.push(call);
+
+        self.calls_sender.send_replace(calls.len());
+    }
+
+    fn send(&self, event: HostingEvent) -> anyhow::Result<()> {
+        match self.events.lock().unwrap().as_ref() {
+            Some(events) => events.send(event)?,
+            None => bail!("Process not ready"),
+        }
+
+        Ok(())
+    }
+
+    /// Returns every call made into the backend so far
+    pub fn calls(&self) -> Vec<HostingCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Waits until a call matching the predicate has been made
+    pub async fn wait_for_call<F>(&self, predicate: F) -> anyhow::Result<()>
+    where
+        F: Fn(&HostingCall) -> bool,
+    {
+        let mut receiver = self.calls_sender.subscribe();
+        loop {
+            if self.calls.lock().unwrap().iter().any(&predicate) {
+                return Ok(());
+            }
+
+            receiver.changed().await?;
+        }
+    }
+
+    pub fn start_session(&self, session: SessionInfo) -> anyhow::Result<()> {
+        info!("Fake starting session: {:?}", session);
+
+        self.send(HostingEvent::StartSession(session))
+    }
+
+    /// Terminates the process, with an optional termination time
+    pub fn terminate(&self, termination_time: Option<i64>) -> anyhow::Result<()> {
+        info!("Fake terminating process ({:?})", termination_time);
+
+        *self.termination_time.lock().unwrap() = termination_time;
+
+        self.send(HostingEvent::Terminate)
+    }
+
+    /// Runs the server health check
+    pub async fn check_health(&self) -> anyhow::Result<bool> {
+        let health_check = match self.health_check.lock().unwrap().as_ref() {
+            Some(health_check) => health_check.clone(),
+            None => bail!("Process not ready"),
+        };
+
+        Ok(health_check().await)
+    }
+}
+
+impl HostingBackend for FakeBackend {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    fn ready(
+        &self,
+        port: u16,
+        _log_paths: Vec<String>,
+        events: mpsc::UnboundedSender<HostingEvent>,
+        health_check: HealthCheck,
+    ) -> HostingFuture<'_, ()> {
+        *self.events.lock().unwrap() = Some(events);
+        *self.health_check.lock().unwrap() = Some(health_check);
+
+        self.record(HostingCall::Ready(port));
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn activate_session(&self) -> HostingFuture<'_, ()> {
+        self.record(HostingCall::ActivateSession);
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.record(HostingCall::AcceptPlayerSession(player_session_id));
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.record(HostingCall::RemovePlayerSession(player_session_id));
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn termination_time(&self) -> HostingFuture<'_, Option<i64>> {
+        self.record(HostingCall::TerminationTime);
+
+        future::ready(Ok(*self.termination_time.lock().unwrap())).boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        self.record(HostingCall::ProcessEnding);
+
+        future::ready(Ok(())).boxed()
+    }
+}
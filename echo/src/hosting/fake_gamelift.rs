@@ -0,0 +1,176 @@
+use std::future;
+use std::sync::Mutex;
+
+use anyhow::bail;
+use aws_gamelift_server_sdk_rs::{
+    entity::{GameSession, UpdateGameSession},
+    process_parameters::ProcessParameters,
+};
+use futures_util::FutureExt;
+use tokio::sync::watch;
+use tracing::info;
+
+use super::gamelift::GameLiftServerApi;
+use super::HostingFuture;
+
+/// Calls made into the fake GameLift server SDK
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GameLiftCall {
+    ProcessReady(i32),
+    ActivateGameSession,
+    AcceptPlayerSession(String),
+    RemovePlayerSession(String),
+    GetTerminationTime,
+    ProcessEnding,
+}
+
+/// In-process stand-in for the GameLift server SDK (and the GameLift agent behind it)
+///
+/// The process parameters callbacks are driven by calling start_game_session(),
+/// update_game_session(), terminate_process() and health_check()
+pub struct FakeGameLiftApi {
+    parameters: Mutex<Option<ProcessParameters>>,
+    termination_time: Mutex<Option<i64>>,
+
+    calls: Mutex<Vec<GameLiftCall>>,
+    calls_sender: watch::Sender<usize>,
+}
+
+impl Default for FakeGameLiftApi {
+    fn default() -> Self {
+        Self {
+            parameters: Mutex::new(None),
+            termination_time: Mutex::new(None),
+            calls: Mutex::new(Vec::new()),
+            calls_sender: watch::channel(0).0,
+        }
+    }
+}
+
+impl FakeGameLiftApi {
+    fn record(&self, call: GameLiftCall) {
+        let mut calls = self.calls.lock().unwrap();
+        calls.push(call);
+
+        self.calls_sender.send_replace(calls.len());
+    }
+
+    /// Returns every call made into the SDK so far
+    pub fn calls(&self) -> Vec<GameLiftCall> {
+        self.calls.lock().unwrap().clone()
+    }
+
+    /// Waits until a call matching the predicate has been made
+    pub async fn wait_for_call<F>(&self, predicate: F) -> anyhow::Result<()>
+    where
+        F: Fn(&GameLiftCall) -> bool,
+    {
+        let mut receiver = self.calls_sender.subscribe();
+        loop {
+            if self.calls.lock().unwrap().iter().any(&predicate) {
+                return Ok(());
+            }
+
+            receiver.changed().await?;
+        }
+    }
+
+    pub async fn start_game_session(&self, game_session: GameSession) -> anyhow::Result<()> {
+        info!("Fake GameLift starting game session: {:?}", game_session);
+
+        let callback = match self.parameters.lock().unwrap().as_ref() {
+            Some(parameters) => (parameters.on_start_game_session)(game_session),
+            None => bail!("Process not ready"),
+        };
+        callback.await;
+
+        Ok(())
+    }
+
+    pub async fn update_game_session(
+        &self,
+        update_game_session: UpdateGameSession,
+    ) -> anyhow::Result<()> {
+        info!(
+            "Fake GameLift updating game session: {:?}",
+            update_game_session
+        );
+
+        let callback = match self.parameters.lock().unwrap().as_ref() {
+            Some(parameters) => (parameters.on_update_game_session)(update_game_session),
+            None => bail!("Process not ready"),
+        };
+        callback.await;
+
+        Ok(())
+    }
+
+    /// Terminates the process, with an optional termination time
+    pub async fn terminate_process(&self, termination_time: Option<i64>) -> anyhow::Result<()> {
+        info!("Fake GameLift terminating process ({:?})", termination_time);
+
+        *self.termination_time.lock().unwrap() = termination_time;
+
+        let callback = match self.parameters.lock().unwrap().as_ref() {
+            Some(parameters) => (parameters.on_process_terminate)(),
+            None => bail!("Process not ready"),
+        };
+        callback.await;
+
+        Ok(())
+    }
+
+    pub async fn health_check(&self) -> anyhow::Result<bool> {
+        let callback = match self.parameters.lock().unwrap().as_ref() {
+            Some(parameters) => (parameters.on_health_check)(),
+            None => bail!("Process not ready"),
+        };
+
+        Ok(callback.await)
+    }
+}
+
+impl GameLiftServerApi for FakeGameLiftApi {
+    fn process_ready(&self, parameters: ProcessParameters) -> HostingFuture<'_, ()> {
+        self.record(GameLiftCall::ProcessReady(parameters.port));
+
+        *self.parameters.lock().unwrap() = Some(parameters);
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn activate_game_session(&self) -> HostingFuture<'_, ()> {
+        self.record(GameLiftCall::ActivateGameSession);
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.record(GameLiftCall::AcceptPlayerSession(player_session_id));
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.record(GameLiftCall::RemovePlayerSession(player_session_id));
+
+        future::ready(Ok(())).boxed()
+    }
+
+    fn get_termination_time(&self) -> HostingFuture<'_, i64> {
+        self.record(GameLiftCall::GetTerminationTime);
+
+        let res = match *self.termination_time.lock().unwrap() {
+            Some(termination_time) => Ok(termination_time),
+            None => Err(anyhow::anyhow!("No termination time set")),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        self.record(GameLiftCall::ProcessEnding);
+
+        future::ready(Ok(())).boxed()
+    }
+}
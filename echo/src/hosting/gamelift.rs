@@ -0,0 +1,243 @@
+use std::future;
+use std::sync::Arc;
+
+use aws_gamelift_server_sdk_rs::{
+    api::Api, log_parameters::LogParameters, process_parameters::ProcessParameters,
+};
+use futures_util::FutureExt;
+use tokio::sync::{mpsc, RwLock};
+use tracing::{debug, error, info, warn};
+
+use super::{HealthCheck, HostingBackend, HostingEvent, HostingFuture, SessionInfo};
+
+/// The GameLift server SDK calls used by the backend
+///
+/// This allows the SDK to be swapped out for a fake when running without a GameLift agent
+pub trait GameLiftServerApi: Send + Sync {
+    fn process_ready(&self, parameters: ProcessParameters) -> HostingFuture<'_, ()>;
+
+    fn activate_game_session(&self) -> HostingFuture<'_, ()>;
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()>;
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()>;
+
+    fn get_termination_time(&self) -> HostingFuture<'_, i64>;
+
+    fn process_ending(&self) -> HostingFuture<'_, ()>;
+}
+
+impl GameLiftServerApi for RwLock<Api> {
+    fn process_ready(&self, parameters: ProcessParameters) -> HostingFuture<'_, ()> {
+        async move {
+            self.write().await.process_ready(parameters).await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn activate_game_session(&self) -> HostingFuture<'_, ()> {
+        async move {
+            self.write().await.activate_game_session().await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.write()
+                .await
+                .accept_player_session(player_session_id)
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.write()
+                .await
+                .remove_player_session(player_session_id)
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn get_termination_time(&self) -> HostingFuture<'_, i64> {
+        async move { Ok(self.write().await.get_termination_time().await?) }.boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        async move {
+            self.write().await.process_ending().await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+}
+
+impl<T> GameLiftServerApi for Arc<T>
+where
+    T: GameLiftServerApi,
+{
+    fn process_ready(&self, parameters: ProcessParameters) -> HostingFuture<'_, ()> {
+        self.as_ref().process_ready(parameters)
+    }
+
+    fn activate_game_session(&self) -> HostingFuture<'_, ()> {
+        self.as_ref().activate_game_session()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.as_ref().accept_player_session(player_session_id)
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.as_ref().remove_player_session(player_session_id)
+    }
+
+    fn get_termination_time(&self) -> HostingFuture<'_, i64> {
+        self.as_ref().get_termination_time()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        self.as_ref().process_ending()
+    }
+}
+
+/// Hosting on AWS GameLift through the GameLift server SDK
+pub struct GameLiftBackend<A = RwLock<Api>> {
+    api: A,
+}
+
+impl GameLiftBackend {
+    pub async fn new() -> anyhow::Result<Self> {
+        let mut api = Api::default();
+        api.init_sdk().await?;
+
+        Ok(Self::with_api(RwLock::new(api)))
+    }
+}
+
+impl<A> GameLiftBackend<A>
+where
+    A: GameLiftServerApi,
+{
+    pub fn with_api(api: A) -> Self {
+        Self { api }
+    }
+}
+
+impl<A> HostingBackend for GameLiftBackend<A>
+where
+    A: GameLiftServerApi,
+{
+    fn name(&self) -> &'static str {
+        "gamelift"
+    }
+
+    fn ready(
+        &self,
+        port: u16,
+        log_paths: Vec<String>,
+        events: mpsc::UnboundedSender<HostingEvent>,
+        health_check: HealthCheck,
+    ) -> HostingFuture<'_, ()> {
+        async move {
+            self.api
+                .process_ready(ProcessParameters {
+                    on_start_game_session: Box::new({
+                        let events = events.clone();
+                        move |game_session| {
+                            info!("Starting game session: {:?}", game_session);
+
+                            let session_id = match &game_session.game_session_id {
+                                Some(session_id) => session_id.clone(),
+                                None => {
+                                    error!("Failed to start game session: GameLift gave no id");
+                                    return future::ready(()).boxed();
+                                }
+                            };
+
+                            if let Err(err) = events.send(HostingEvent::StartSession(SessionInfo {
+                                session_id,
+                                max_players: u32::try_from(game_session.max_players).ok(),
+                            })) {
+                                error!("Failed to start game session: {}", err);
+                            }
+
+                            future::ready(()).boxed()
+                        }
+                    }),
+                    on_update_game_session: Box::new({
+                        let events = events.clone();
+                        move |update_game_session| {
+                            warn!("Update game session: {:?}", update_game_session);
+
+                            if let Err(err) = events.send(HostingEvent::UpdateSession {
+                                description: format!("{:?}", update_game_session),
+                            }) {
+                                error!("Failed to update game session: {}", err);
+                            }
+
+                            future::ready(()).boxed()
+                        }
+                    }),
+                    on_process_terminate: Box::new(move || {
+                        info!("Process terminating ...");
+
+                        if let Err(err) = events.send(HostingEvent::Terminate) {
+                            error!("Failed to terminate process: {}", err);
+                        }
+
+                        future::ready(()).boxed()
+                    }),
+                    on_health_check: Box::new(move || {
+                        let health_check = health_check.clone();
+                        async move {
+                            debug!("health check");
+                            health_check().await
+                        }
+                        .boxed()
+                    }),
+                    port: port as i32,
+                    log_parameters: LogParameters { log_paths },
+                })
+                .await?;
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn activate_session(&self) -> HostingFuture<'_, ()> {
+        self.api.activate_game_session()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.api.accept_player_session(player_session_id)
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        self.api.remove_player_session(player_session_id)
+    }
+
+    fn termination_time(&self) -> HostingFuture<'_, Option<i64>> {
+        self.api
+            .get_termination_time()
+            .map(|res| res.map(Some))
+            .boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        self.api.process_ending()
+    }
+}
@@ -0,0 +1,228 @@
+use std::future;
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use aws_gamelift_server_sdk_rs::{entity::GameSession, process_parameters::ProcessParameters};
+use futures_util::FutureExt;
+use serde::{de::DeserializeOwned, Serialize};
+use tokio::time;
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::gamelift::GameLiftServerApi;
+use super::HostingFuture;
+use crate::gamelift_local::{
+    EmptyResponse, PlayerSessionRequest, PollProcessRequest, PollProcessResponse, ProcessEvent,
+    ProcessReadyRequest, ProcessRequest, JSON_CONTENT_TYPE, LOCAL_TARGET_PREFIX, TARGET_HEADER,
+};
+
+/// How often to poll the local control plane for events
+const POLL_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+#[derive(Clone)]
+struct LocalClient {
+    client: reqwest::Client,
+    endpoint: String,
+    process_id: String,
+}
+
+impl LocalClient {
+    async fn call<Req, Resp>(&self, operation: &str, request: &Req) -> anyhow::Result<Resp>
+    where
+        Req: Serialize,
+        Resp: DeserializeOwned,
+    {
+        let response = self
+            .client
+            .post(&self.endpoint)
+            .header(
+                TARGET_HEADER,
+                format!("{}{}", LOCAL_TARGET_PREFIX, operation),
+            )
+            .header(reqwest::header::CONTENT_TYPE, JSON_CONTENT_TYPE)
+            .json(request)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            bail!(
+                "{} failed ({}): {}",
+                operation,
+                status,
+                response.text().await?
+            );
+        }
+
+        Ok(response.json().await?)
+    }
+
+    /// Polls for events and runs them, returns false once the process is terminated
+    async fn poll(
+        &self,
+        parameters: &ProcessParameters,
+        termination_time: &Mutex<Option<i64>>,
+    ) -> anyhow::Result<bool> {
+        let healthy = (parameters.on_health_check)().await;
+
+        let response: PollProcessResponse = self
+            .call(
+                "PollProcess",
+                &PollProcessRequest {
+                    process_id: self.process_id.clone(),
+                    healthy,
+                },
+            )
+            .await?;
+
+        for event in response.events {
+            match event {
+                ProcessEvent::StartGameSession(game_session) => {
+                    (parameters.on_start_game_session)(GameSession {
+                        game_session_id: Some(game_session.game_session_id),
+                        max_players: game_session.maximum_player_session_count,
+                        port: game_session.port,
+                        ..Default::default()
+                    })
+                    .await;
+                }
+                ProcessEvent::TerminateProcess(termination) => {
+                    *termination_time.lock().unwrap() = Some(termination.termination_time);
+
+                    (parameters.on_process_terminate)().await;
+                    return Ok(false);
+                }
+            }
+        }
+
+        Ok(true)
+    }
+}
+
+/// GameLift server SDK stand-in that talks to the local control plane (echo gamelift-local)
+pub struct GameLiftLocalApi {
+    client: LocalClient,
+    termination_time: Arc<Mutex<Option<i64>>>,
+}
+
+impl GameLiftLocalApi {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            client: LocalClient {
+                client: reqwest::Client::new(),
+                endpoint: endpoint.into(),
+                process_id: format!("process-{}", Uuid::new_v4()),
+            },
+            termination_time: Arc::new(Mutex::new(None)),
+        }
+    }
+
+    async fn process_call<Req>(&self, operation: &str, request: &Req) -> anyhow::Result<()>
+    where
+        Req: Serialize,
+    {
+        let _: EmptyResponse = self.client.call(operation, request).await?;
+
+        Ok(())
+    }
+
+    fn process_request(&self) -> ProcessRequest {
+        ProcessRequest {
+            process_id: self.client.process_id.clone(),
+        }
+    }
+
+    fn player_session_request(&self, player_session_id: String) -> PlayerSessionRequest {
+        PlayerSessionRequest {
+            process_id: self.client.process_id.clone(),
+            player_session_id,
+        }
+    }
+}
+
+impl GameLiftServerApi for GameLiftLocalApi {
+    fn process_ready(&self, parameters: ProcessParameters) -> HostingFuture<'_, ()> {
+        async move {
+            info!(
+                "Registering process {} with {} ...",
+                self.client.process_id, self.client.endpoint
+            );
+
+            self.process_call(
+                "ProcessReady",
+                &ProcessReadyRequest {
+                    process_id: self.client.process_id.clone(),
+                    port: parameters.port,
+                },
+            )
+            .await?;
+
+            let client = self.client.clone();
+            let termination_time = self.termination_time.clone();
+            tokio::spawn(async move {
+                let mut interval = time::interval(POLL_INTERVAL);
+                loop {
+                    interval.tick().await;
+
+                    match client.poll(&parameters, &termination_time).await {
+                        Ok(true) => (),
+                        Ok(false) => break,
+                        Err(err) => warn!("Failed to poll GameLift local: {}", err),
+                    }
+                }
+
+                info!("Stopped polling GameLift local");
+            });
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn activate_game_session(&self) -> HostingFuture<'_, ()> {
+        async move {
+            self.process_call("ActivateGameSession", &self.process_request())
+                .await
+        }
+        .boxed()
+    }
+
+    fn accept_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.process_call(
+                "AcceptPlayerSession",
+                &self.player_session_request(player_session_id),
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn remove_player_session(&self, player_session_id: String) -> HostingFuture<'_, ()> {
+        async move {
+            self.process_call(
+                "RemovePlayerSession",
+                &self.player_session_request(player_session_id),
+            )
+            .await
+        }
+        .boxed()
+    }
+
+    fn get_termination_time(&self) -> HostingFuture<'_, i64> {
+        let res = match *self.termination_time.lock().unwrap() {
+            Some(termination_time) => Ok(termination_time),
+            None => Err(anyhow::anyhow!("No termination time set")),
+        };
+
+        future::ready(res).boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        async move {
+            self.process_call("ProcessEnding", &self.process_request())
+                .await
+        }
+        .boxed()
+    }
+}
@@ -0,0 +1,63 @@
+use std::future;
+use std::sync::Mutex;
+
+use futures_util::FutureExt;
+use tokio::sync::mpsc;
+use tracing::info;
+use uuid::Uuid;
+
+use super::{HealthCheck, HostingBackend, HostingEvent, HostingFuture, SessionInfo};
+
+/// Hosting without any orchestration, a session is started as soon as the process is ready
+#[derive(Debug, Default)]
+pub struct StandaloneBackend {
+    /// kept for the life of the backend, closing it would look like the backend went away
+    events: Mutex<Option<mpsc::UnboundedSender<HostingEvent>>>,
+}
+
+impl HostingBackend for StandaloneBackend {
+    fn name(&self) -> &'static str {
+        "standalone"
+    }
+
+    fn ready(
+        &self,
+        port: u16,
+        _log_paths: Vec<String>,
+        events: mpsc::UnboundedSender<HostingEvent>,
+        _health_check: HealthCheck,
+    ) -> HostingFuture<'_, ()> {
+        async move {
+            info!("Ready on port {}", port);
+
+            events.send(HostingEvent::StartSession(SessionInfo {
+                session_id: Uuid::new_v4().to_string(),
+                max_players: None,
+            }))?;
+            *self.events.lock().unwrap() = Some(events);
+
+            Ok(())
+        }
+        .boxed()
+    }
+
+    fn activate_session(&self) -> HostingFuture<'_, ()> {
+        future::ready(Ok(())).boxed()
+    }
+
+    fn accept_player_session(&self, _player_session_id: String) -> HostingFuture<'_, ()> {
+        future::ready(Ok(())).boxed()
+    }
+
+    fn remove_player_session(&self, _player_session_id: String) -> HostingFuture<'_, ()> {
+        future::ready(Ok(())).boxed()
+    }
+
+    fn termination_time(&self) -> HostingFuture<'_, Option<i64>> {
+        future::ready(Ok(None)).boxed()
+    }
+
+    fn process_ending(&self) -> HostingFuture<'_, ()> {
+        future::ready(Ok(())).boxed()
+    }
+}
@@ -1,19 +1,36 @@
+mod beacon;
 mod client;
 mod gamelift;
+mod gamelift_local;
+mod hosting;
 mod options;
+mod player;
+mod relay;
 mod server;
+mod state;
+mod supervisor;
 mod util;
 
+use std::future;
 use std::sync::Arc;
 
 use futures_util::FutureExt;
-use tokio::sync::{watch, Mutex};
-use tracing::info;
+use tokio::sync::watch;
+use tracing::{info, warn};
 use tracing_subscriber::{filter, prelude::*};
 use uuid::Uuid;
 
-fn init_logging() -> anyhow::Result<tracing_appender::non_blocking::WorkerGuard> {
-    let file_appender = tracing_appender::rolling::daily("logs", "echo.log");
+use echo_protocol::store::TicketStore;
+
+use hosting::{
+    agones::AgonesBackend, gamelift::GameLiftBackend, gamelift_local::GameLiftLocalApi,
+    standalone::StandaloneBackend,
+};
+
+fn init_logging(
+    log_dir: impl AsRef<std::path::Path>,
+) -> anyhow::Result<tracing_appender::non_blocking::WorkerGuard> {
+    let file_appender = tracing_appender::rolling::daily(log_dir, "echo.log");
     let (non_blocking_appender, guard) = tracing_appender::non_blocking(file_appender);
 
     tracing_subscriber::registry()
@@ -27,6 +44,24 @@ fn init_logging() -> anyhow::Result<tracing_appender::non_blocking::WorkerGuard>
     Ok(guard)
 }
 
+fn placement_source(relay: Option<&relay::RelayClient>) -> client::PlacementSource<'_> {
+    match relay {
+        Some(relay) => client::PlacementSource::Notify(relay),
+        None => client::PlacementSource::Describe,
+    }
+}
+
+fn ticket_source<'a>(
+    store: Option<&'a dyn TicketStore>,
+    relay: Option<&'a relay::RelayClient>,
+) -> client::TicketSource<'a> {
+    match (store, relay) {
+        (Some(store), _) => client::TicketSource::Store(store),
+        (None, Some(relay)) => client::TicketSource::Notify(relay),
+        (None, None) => client::TicketSource::Describe,
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     let options: options::Options = argh::from_env();
@@ -36,13 +71,17 @@ async fn main() -> anyhow::Result<()> {
         console_subscriber::init();
         None
     } else {
-        Some(init_logging()?)
+        Some(init_logging(&options.log_dir)?)
     };
 
-    // TODO: make this configurable
-    let region = String::from("us-west-2");
+    let region_set = options.region.is_some();
+    let region = options.region.unwrap_or_else(options::default_region);
+    let state = state::StateFile::new(&options.state_file);
 
-    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    // polling, and the sessions connected afterwards, stop on Ctrl-C even if it's pressed between polls
+    if options.mode.polls() {
+        util::listen_for_interrupt();
+    }
 
     match options.mode {
         options::Mode::Connect(cmd) => {
@@ -50,39 +89,136 @@ async fn main() -> anyhow::Result<()> {
             client::connect(cmd.connect_addr(), &player_id).await?;
         }
         options::Mode::CreateGameLiftLocal(cmd) => {
+            let control = gamelift::new_control(region, true).await;
             let player_id = Uuid::new_v4().to_string();
-            client::create_gamelift_local(region, cmd.fleet_id, &player_id).await?;
+            client::create_gamelift_local(control.as_ref(), cmd.fleet_id, player_id).await?;
+        }
+        options::Mode::CreateGameLift(cmd) if cmd.cancel => {
+            let placement = state.pending_placement()?;
+            let control = gamelift::new_control(placement.region.clone(), cmd.local).await;
+            client::cancel_create_gamelift(control.as_ref(), &state, placement).await?;
+        }
+        options::Mode::CreateGameLift(cmd) if cmd.resume => {
+            let placement = state.pending_placement()?;
+            let control = gamelift::new_control(placement.region.clone(), cmd.local).await;
+            let relay = cmd.relay();
+            client::resume_create_gamelift(
+                control.as_ref(),
+                &state,
+                placement,
+                cmd.poll_options(),
+                &placement_source(relay.as_ref()),
+            )
+            .await?;
         }
         options::Mode::CreateGameLift(cmd) => {
-            let player_id = Uuid::new_v4().to_string();
-            client::create_gamelift(region, cmd.queue_name, &player_id).await?;
+            let mut player = cmd.player(Uuid::new_v4().to_string())?;
+            let region = if cmd.probes_regions(region_set) {
+                beacon::choose_region(
+                    &cmd.beacons,
+                    beacon::DEFAULT_PING_SAMPLES,
+                    std::slice::from_mut(&mut player),
+                )
+                .await?
+            } else {
+                region
+            };
+
+            let control = gamelift::new_control(region.clone(), cmd.local).await;
+            let relay = cmd.relay();
+            client::create_gamelift(
+                control.as_ref(),
+                &state,
+                region,
+                &cmd.queue_name,
+                player,
+                cmd.poll_options(),
+                &placement_source(relay.as_ref()),
+            )
+            .await?;
         }
         options::Mode::ConnectGameLift(cmd) => {
+            let control = gamelift::new_control(region, cmd.local).await;
             let player_id = Uuid::new_v4().to_string();
-            client::connect_gamelift(region, &player_id, cmd.session_id, cmd.local).await?;
+            client::connect_gamelift(control.as_ref(), player_id, cmd.session_id).await?;
+        }
+        options::Mode::Find(cmd) if cmd.cancel => {
+            let ticket = state.pending_ticket()?;
+            let control = gamelift::new_control(ticket.region.clone(), cmd.local).await;
+            client::cancel_find(control.as_ref(), &state, ticket).await?;
+        }
+        options::Mode::Find(cmd) if cmd.resume => {
+            let ticket = state.pending_ticket()?;
+            let control = gamelift::new_control(ticket.region.clone(), cmd.local).await;
+            let relay = cmd.relay()?;
+            let store = cmd.ticket_store().await?;
+            client::resume_find(
+                control.as_ref(),
+                &state,
+                ticket,
+                cmd.poll_options(),
+                cmd.auto_accept,
+                &ticket_source(store.as_deref(), relay.as_ref()),
+            )
+            .await?;
+        }
+        options::Mode::Find(cmd) => {
+            let mut party = cmd.party()?;
+            let region = if cmd.probes_regions(region_set) {
+                beacon::choose_region(&cmd.beacons, beacon::DEFAULT_PING_SAMPLES, &mut party)
+                    .await?
+            } else {
+                region
+            };
+
+            let control = gamelift::new_control(region.clone(), cmd.local).await;
+            let relay = cmd.relay()?;
+            let store = cmd.ticket_store().await?;
+            client::find(
+                control.as_ref(),
+                &state,
+                region,
+                party,
+                cmd.poll_options(),
+                cmd.auto_accept,
+                &ticket_source(store.as_deref(), relay.as_ref()),
+            )
+            .await?;
+        }
+        options::Mode::SearchGameLift(cmd) => {
+            let target = cmd.target()?;
+            let control = gamelift::new_control(region, cmd.local).await;
+            let player_id = cmd.join.then(|| Uuid::new_v4().to_string());
+            client::search(
+                control.as_ref(),
+                target,
+                cmd.filter,
+                cmd.sort,
+                cmd.limit,
+                player_id,
+            )
+            .await?;
         }
-        options::Mode::Find(_) => {
-            client::find(region).await?;
+        options::Mode::PingRegions(cmd) => {
+            let results = beacon::probe_regions(&cmd.beacons, cmd.samples).await;
+            beacon::print_regions(&results);
         }
         options::Mode::Server(cmd) => {
-            let (ready_sender, ready_receiver) = watch::channel(false);
-            let ready_sender = Arc::new(Mutex::new(ready_sender));
+            let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+            let (_drain_sender, drain_receiver) = watch::channel(None);
+            let (ready_sender, mut ready_receiver) = watch::channel(false);
+            let ready_sender = Arc::new(ready_sender);
 
             // spawn the server process
             let server_handle = tokio::spawn(server::run(
                 cmd.server_addr(),
                 true,
                 shutdown_receiver,
+                drain_receiver,
                 server::ServerCallbacks {
-                    begin_session: Box::new({
-                        let ready_sender = ready_sender.clone();
-                        move || {
-                            let ready_sender = ready_sender.clone();
-                            async move {
-                                ready_sender.lock().await.send(true).unwrap();
-                            }
-                            .boxed()
-                        }
+                    begin_session: Box::new(move || {
+                        ready_sender.send_replace(true);
+                        future::ready(()).boxed()
                     }),
                     ..Default::default()
                 },
@@ -92,7 +228,9 @@ async fn main() -> anyhow::Result<()> {
             info!("Waiting for ready ...");
 
             // wait for the server to be ready
-            util::wait_for_signal(ready_receiver).await?;
+            while !*ready_receiver.borrow_and_update() {
+                ready_receiver.changed().await?;
+            }
 
             // run the client
             // TODO: allow optional CLI arg for the player id
@@ -104,17 +242,67 @@ async fn main() -> anyhow::Result<()> {
             server_handle.await??;
         }
         options::Mode::Dedicated(cmd) => {
-            server::run(
-                cmd.server_addr(),
+            // answer pings on the same port, so this can stand in for a region's ping beacon
+            let beacon_addr = cmd.server_addr();
+            tokio::spawn(async move {
+                if let Err(err) = beacon::run(beacon_addr).await {
+                    warn!("Ping beacon failed: {}", err);
+                }
+            });
+
+            server::run_hosted(
+                Arc::new(StandaloneBackend::default()),
+                "0.0.0.0",
+                cmd.port,
                 false,
-                shutdown_receiver,
-                server::ServerCallbacks::default(),
                 None,
+                options.log_dir,
+            )
+            .await?;
+        }
+        options::Mode::GameLift(cmd) if cmd.local => {
+            server::run_hosted(
+                Arc::new(GameLiftBackend::with_api(GameLiftLocalApi::new(
+                    gamelift::LOCAL_ENDPOINT,
+                ))),
+                "0.0.0.0",
+                cmd.port,
+                false,
+                Some(60),
+                options.log_dir,
             )
             .await?;
         }
         options::Mode::GameLift(cmd) => {
-            server::run_gamelift(cmd.port).await?;
+            server::run_hosted(
+                Arc::new(GameLiftBackend::new().await?),
+                "0.0.0.0",
+                cmd.port,
+                false,
+                Some(60),
+                options.log_dir,
+            )
+            .await?;
+        }
+        options::Mode::GameLiftLocal(cmd) => {
+            gamelift_local::run(cmd.port, cmd.fleet_id, cmd.acceptance_timeout).await?;
+        }
+        options::Mode::NotifyRelay(cmd) => {
+            relay::run(cmd.port).await?;
+        }
+        options::Mode::Agones(cmd) => {
+            server::run_hosted(
+                Arc::new(AgonesBackend::new(cmd.sdk_port)),
+                "0.0.0.0",
+                cmd.port,
+                false,
+                Some(60),
+                options.log_dir,
+            )
+            .await?;
+        }
+        options::Mode::Supervise(cmd) => {
+            supervisor::run(cmd.mode, cmd.count, cmd.base_port, options.log_dir).await?;
         }
     };
 
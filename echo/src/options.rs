@@ -1,6 +1,17 @@
+use std::time::Duration;
+
 use argh::FromArgs;
 use derive_more::Display;
 
+use echo_protocol::store::{StoreConfig, TicketStore};
+
+use crate::beacon::{BeaconArg, DEFAULT_PING_SAMPLES};
+use crate::client::PollOptions;
+use crate::gamelift::{PlayerDetails, SearchTarget};
+use crate::player::{self, AttributeArg, LatencyArg};
+use crate::relay::RelayClient;
+use crate::supervisor::ChildMode;
+
 #[derive(FromArgs, PartialEq, Eq, Debug, Display)]
 #[argh(subcommand)]
 pub enum Mode {
@@ -19,6 +30,12 @@ pub enum Mode {
     #[display(fmt = "Find")]
     Find(FindCommand),
 
+    #[display(fmt = "SearchGameLift")]
+    SearchGameLift(SearchGameLiftCommand),
+
+    #[display(fmt = "PingRegions")]
+    PingRegions(PingRegionsCommand),
+
     #[display(fmt = "Server")]
     Server(ServerCommand),
 
@@ -27,6 +44,29 @@ pub enum Mode {
 
     #[display(fmt = "GameLift")]
     GameLift(GameLiftCommand),
+
+    #[display(fmt = "GameLiftLocal")]
+    GameLiftLocal(GameLiftLocalCommand),
+
+    #[display(fmt = "NotifyRelay")]
+    NotifyRelay(NotifyRelayCommand),
+
+    #[display(fmt = "Agones")]
+    Agones(AgonesCommand),
+
+    #[display(fmt = "Supervise")]
+    Supervise(SuperviseCommand),
+}
+
+impl Mode {
+    /// Whether the mode polls a placement or matchmaking ticket
+    pub fn polls(&self) -> bool {
+        match self {
+            Mode::CreateGameLift(cmd) => !cmd.cancel,
+            Mode::Find(cmd) => !cmd.cancel,
+            _ => false,
+        }
+    }
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -68,6 +108,80 @@ pub struct CreateGameLiftCommand {
     /// the gamelift queue to create the session on
     #[argh(option)]
     pub queue_name: String,
+
+    /// use GameLift local
+    #[argh(switch)]
+    pub local: bool,
+
+    /// resume polling the pending placement from an earlier run
+    #[argh(switch)]
+    pub resume: bool,
+
+    /// stop the pending placement from an earlier run
+    #[argh(switch)]
+    pub cancel: bool,
+
+    /// wait for the placement event pushed to a notification relay (echo notify-relay) at this
+    /// url instead of polling
+    #[argh(option)]
+    pub notify: Option<String>,
+
+    /// player attribute for matchmaking rules (name=value, [a,b] for string lists), may be repeated
+    #[argh(option, long = "attribute")]
+    pub attributes: Vec<AttributeArg>,
+
+    /// latency to a region (region=ms), may be repeated
+    #[argh(option, long = "latency")]
+    pub latencies: Vec<LatencyArg>,
+
+    /// json file with player attributes and latencies
+    #[argh(option)]
+    pub player_config: Option<String>,
+
+    /// ping each region and use the closest one, also sending the latencies
+    #[argh(switch)]
+    pub probe_regions: bool,
+
+    /// region ping beacon (region=host:port) to probe instead of the GameLift beacons, may be
+    /// repeated, the beacons are probed unless --region is given
+    #[argh(option, long = "beacon")]
+    pub beacons: Vec<BeaconArg>,
+
+    /// seconds to wait before the first poll
+    #[argh(option, default = "default_poll_interval()")]
+    pub poll_interval: u64,
+
+    /// maximum seconds between polls
+    #[argh(option, default = "default_max_poll_interval()")]
+    pub max_poll_interval: u64,
+
+    /// seconds to wait before giving up
+    #[argh(option, default = "default_poll_deadline()")]
+    pub deadline: u64,
+}
+
+impl CreateGameLiftCommand {
+    /// Whether to pick the region by pinging, given beacons are probed unless the region is set
+    pub fn probes_regions(&self, region_set: bool) -> bool {
+        self.probe_regions || (!self.beacons.is_empty() && !region_set)
+    }
+
+    pub fn poll_options(&self) -> PollOptions {
+        poll_options(self.poll_interval, self.max_poll_interval, self.deadline)
+    }
+
+    pub fn relay(&self) -> Option<RelayClient> {
+        self.notify.as_deref().map(RelayClient::new)
+    }
+
+    pub fn player(&self, player_id: impl Into<String>) -> anyhow::Result<PlayerDetails> {
+        player::load_player(
+            player_id,
+            self.player_config.as_ref(),
+            &self.attributes,
+            &self.latencies,
+        )
+    }
 }
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
@@ -86,7 +200,181 @@ pub struct ConnectGameLiftCommand {
 #[derive(FromArgs, PartialEq, Eq, Debug)]
 /// Search for a GameLift server to connect to
 #[argh(subcommand, name = "find")]
-pub struct FindCommand {}
+pub struct FindCommand {
+    /// use GameLift local
+    #[argh(switch)]
+    pub local: bool,
+
+    /// accept proposed matches without asking
+    #[argh(switch)]
+    pub auto_accept: bool,
+
+    /// resume polling the pending matchmaking ticket from an earlier run
+    #[argh(switch)]
+    pub resume: bool,
+
+    /// stop the pending matchmaking ticket from an earlier run
+    #[argh(switch)]
+    pub cancel: bool,
+
+    /// read ticket updates from the echo-notifications ticket store instead of DescribeMatchmaking
+    /// (file:<dir> or dynamodb:<table>[@<endpoint>])
+    #[argh(option)]
+    pub ticket_store: Option<StoreConfig>,
+
+    /// wait for ticket updates pushed to a notification relay (echo notify-relay) at this url
+    /// instead of polling
+    #[argh(option)]
+    pub notify: Option<String>,
+
+    /// id of a player in the party, may be repeated (defaults to one random player)
+    #[argh(option, long = "player-id")]
+    pub player_ids: Vec<String>,
+
+    /// player attribute for matchmaking rules (name=value, [a,b] for string lists), may be repeated
+    #[argh(option, long = "attribute")]
+    pub attributes: Vec<AttributeArg>,
+
+    /// latency to a region (region=ms), may be repeated
+    #[argh(option, long = "latency")]
+    pub latencies: Vec<LatencyArg>,
+
+    /// json file with player attributes and latencies
+    #[argh(option)]
+    pub player_config: Option<String>,
+
+    /// ping each region and use the closest one, also sending the latencies
+    #[argh(switch)]
+    pub probe_regions: bool,
+
+    /// region ping beacon (region=host:port) to probe instead of the GameLift beacons, may be
+    /// repeated, the beacons are probed unless --region is given
+    #[argh(option, long = "beacon")]
+    pub beacons: Vec<BeaconArg>,
+
+    /// seconds to wait before the first poll
+    #[argh(option, default = "default_poll_interval()")]
+    pub poll_interval: u64,
+
+    /// maximum seconds between polls
+    #[argh(option, default = "default_max_poll_interval()")]
+    pub max_poll_interval: u64,
+
+    /// seconds to wait before giving up
+    #[argh(option, default = "default_poll_deadline()")]
+    pub deadline: u64,
+}
+
+impl FindCommand {
+    /// Whether to pick the region by pinging, given beacons are probed unless the region is set
+    pub fn probes_regions(&self, region_set: bool) -> bool {
+        self.probe_regions || (!self.beacons.is_empty() && !region_set)
+    }
+
+    pub fn poll_options(&self) -> PollOptions {
+        poll_options(self.poll_interval, self.max_poll_interval, self.deadline)
+    }
+
+    pub async fn ticket_store(&self) -> anyhow::Result<Option<Box<dyn TicketStore>>> {
+        match &self.ticket_store {
+            Some(config) => Ok(Some(config.open_tickets().await?)),
+            None => Ok(None),
+        }
+    }
+
+    pub fn relay(&self) -> anyhow::Result<Option<RelayClient>> {
+        match (&self.notify, &self.ticket_store) {
+            (Some(_), Some(_)) => anyhow::bail!("Use either --notify or --ticket-store, not both"),
+            (Some(endpoint), None) => Ok(Some(RelayClient::new(endpoint))),
+            (None, _) => Ok(None),
+        }
+    }
+
+    pub fn party(&self) -> anyhow::Result<Vec<PlayerDetails>> {
+        player::load_party(
+            &self.player_ids,
+            self.player_config.as_ref(),
+            &self.attributes,
+            &self.latencies,
+        )
+    }
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Search for GameLift game sessions and optionally join one
+#[argh(subcommand, name = "gamelift-search")]
+pub struct SearchGameLiftCommand {
+    /// the gamelift fleet to search
+    #[argh(option)]
+    pub fleet_id: Option<String>,
+
+    /// the gamelift alias to search
+    #[argh(option)]
+    pub alias_id: Option<String>,
+
+    /// use GameLift local
+    #[argh(switch)]
+    pub local: bool,
+
+    /// filter expression, eg. "gameSessionProperties.gameMode = 'casual' AND hasAvailablePlayerSessions = true"
+    #[argh(option)]
+    pub filter: Option<String>,
+
+    /// sort expression, eg. "playerSessionCount DESC"
+    #[argh(option)]
+    pub sort: Option<String>,
+
+    /// maximum number of game sessions to list
+    #[argh(option, default = "20")]
+    pub limit: i32,
+
+    /// join the first listed game session with room
+    #[argh(switch)]
+    pub join: bool,
+}
+
+impl SearchGameLiftCommand {
+    pub fn target(&self) -> anyhow::Result<SearchTarget> {
+        match (&self.fleet_id, &self.alias_id) {
+            (Some(fleet_id), None) => Ok(SearchTarget::Fleet(fleet_id.clone())),
+            (None, Some(alias_id)) => Ok(SearchTarget::Alias(alias_id.clone())),
+            _ => anyhow::bail!("Exactly one of --fleet-id or --alias-id is required"),
+        }
+    }
+}
+
+fn default_poll_interval() -> u64 {
+    5
+}
+
+fn default_max_poll_interval() -> u64 {
+    30
+}
+
+fn default_poll_deadline() -> u64 {
+    300
+}
+
+fn poll_options(poll_interval: u64, max_poll_interval: u64, deadline: u64) -> PollOptions {
+    PollOptions {
+        initial_interval: Duration::from_secs(poll_interval),
+        max_interval: Duration::from_secs(max_poll_interval.max(poll_interval)),
+        deadline: Duration::from_secs(deadline),
+    }
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Print the latency to each region
+#[argh(subcommand, name = "ping-regions")]
+pub struct PingRegionsCommand {
+    /// region ping beacon (region=host:port) to probe instead of the GameLift beacons, may be repeated
+    #[argh(option, long = "beacon")]
+    pub beacons: Vec<BeaconArg>,
+
+    /// pings to send to each region
+    #[argh(option, default = "DEFAULT_PING_SAMPLES")]
+    pub samples: u32,
+}
 
 #[derive(FromArgs, PartialEq, Eq, Debug)]
 /// Run as combined client and server
@@ -129,12 +417,91 @@ pub struct GameLiftCommand {
     /// port to connect to
     #[argh(option, default = "default_port()")]
     pub port: u16,
+
+    /// use GameLift local (echo gamelift-local)
+    #[argh(switch)]
+    pub local: bool,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Run a local stand-in for the GameLift service
+#[argh(subcommand, name = "gamelift-local")]
+pub struct GameLiftLocalCommand {
+    /// port to listen on
+    #[argh(option, default = "8080")]
+    pub port: u16,
+
+    /// the fleet id local processes belong to
+    #[argh(option, default = "String::from(\"fleet-123\")")]
+    pub fleet_id: String,
+
+    /// require players to accept matches within this many seconds
+    #[argh(option)]
+    pub acceptance_timeout: Option<u64>,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Run a relay that pushes GameLift notifications (SNS messages) to waiting clients
+#[argh(subcommand, name = "notify-relay")]
+pub struct NotifyRelayCommand {
+    /// port to listen on
+    #[argh(option, default = "8090")]
+    pub port: u16,
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Run as dedicated server on Agones
+#[argh(subcommand, name = "agones")]
+pub struct AgonesCommand {
+    /// port to connect to
+    #[argh(option, default = "default_port()")]
+    pub port: u16,
+
+    /// port the Agones SDK server is listening on
+    #[argh(option, default = "default_agones_sdk_port()")]
+    pub sdk_port: u16,
+}
+
+fn default_agones_sdk_port() -> u16 {
+    std::env::var("AGONES_SDK_HTTP_PORT")
+        .ok()
+        .and_then(|port| port.parse().ok())
+        .unwrap_or(9358)
+}
+
+#[derive(FromArgs, PartialEq, Eq, Debug)]
+/// Run and monitor multiple dedicated servers
+#[argh(subcommand, name = "supervise")]
+pub struct SuperviseCommand {
+    /// the mode to run children as (gamelift or dedicated)
+    #[argh(option, default = "ChildMode::GameLift")]
+    pub mode: ChildMode,
+
+    /// number of children to run
+    #[argh(option, default = "1")]
+    pub count: u16,
+
+    /// first port in the range to run children on
+    #[argh(option, default = "default_port()")]
+    pub base_port: u16,
 }
 
 fn default_port() -> u16 {
     8065
 }
 
+fn default_log_dir() -> String {
+    "logs".to_string()
+}
+
+pub fn default_region() -> String {
+    "us-west-2".to_string()
+}
+
+fn default_state_file() -> String {
+    "echo-client.json".to_string()
+}
+
 /// Echo client / server
 #[derive(FromArgs, Debug)]
 pub struct Options {
@@ -145,4 +512,78 @@ pub struct Options {
     /// enable tokio tracing
     #[argh(switch)]
     pub tracing: bool,
+
+    /// directory to write logs to
+    #[argh(option, default = "default_log_dir()")]
+    pub log_dir: String,
+
+    /// the AWS region to use (defaults to us-west-2, or the closest beacon given to find or
+    /// gamelift-create)
+    #[argh(option)]
+    pub region: Option<String>,
+
+    /// file to keep pending placements and matchmaking tickets in, so they can be resumed
+    #[argh(option, default = "default_state_file()")]
+    pub state_file: String,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(args: &[&str]) -> Options {
+        Options::from_args(&["echo"], args).unwrap()
+    }
+
+    /// Whether the find or gamelift-create command would probe, and the region it would use
+    fn probes(args: &[&str]) -> (bool, String) {
+        let options = parse(args);
+        let region_set = options.region.is_some();
+        let probes = match &options.mode {
+            Mode::Find(cmd) => cmd.probes_regions(region_set),
+            Mode::CreateGameLift(cmd) => cmd.probes_regions(region_set),
+            mode => panic!("unexpected mode {}", mode),
+        };
+
+        (probes, options.region.unwrap_or_else(default_region))
+    }
+
+    #[test]
+    fn beacons_probe_by_default() {
+        assert_eq!(probes(&["find"]), (false, "us-west-2".to_owned()));
+        assert_eq!(
+            probes(&["find", "--beacon", "local-a=127.0.0.1:8065"]),
+            (true, "us-west-2".to_owned())
+        );
+        assert_eq!(
+            probes(&[
+                "gamelift-create",
+                "--beacon",
+                "local-a=127.0.0.1:8065",
+                "--queue-name",
+                "echo-queue"
+            ]),
+            (true, "us-west-2".to_owned())
+        );
+    }
+
+    #[test]
+    fn region_overrides_beacons() {
+        assert_eq!(
+            probes(&[
+                "--region",
+                "eu-west-1",
+                "find",
+                "--beacon",
+                "local-a=127.0.0.1:8065"
+            ]),
+            (false, "eu-west-1".to_owned())
+        );
+
+        // unless probing is asked for
+        assert_eq!(
+            probes(&["--region", "eu-west-1", "find", "--probe-regions"]),
+            (true, "eu-west-1".to_owned())
+        );
+    }
 }
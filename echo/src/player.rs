@@ -0,0 +1,525 @@
+use std::collections::{HashMap, HashSet};
+use std::path::Path;
+use std::str::FromStr;
+
+use anyhow::{anyhow, bail};
+use serde::Deserialize;
+use uuid::Uuid;
+
+use crate::gamelift::{PlayerAttribute, PlayerDetails};
+
+/// A player attribute from the command line (name=value)
+///
+/// Values in brackets are string lists ([a,b]), values that parse as numbers
+/// are numbers (NaN and infinity are rejected), anything else is a string
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AttributeArg {
+    pub name: String,
+    pub value: String,
+}
+
+impl FromStr for AttributeArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, value) = match s.split_once('=') {
+            Some((name, value)) if !name.is_empty() => (name, value),
+            _ => return Err(format!("invalid attribute '{}', expected name=value", s)),
+        };
+
+        // GameLift numbers are doubles, but not NaN or infinite ones
+        if value.parse::<f64>().is_ok_and(|value| !value.is_finite()) {
+            return Err(format!("invalid attribute '{}', numbers must be finite", s));
+        }
+
+        Ok(Self {
+            name: name.to_owned(),
+            value: value.to_owned(),
+        })
+    }
+}
+
+impl AttributeArg {
+    fn attribute(&self) -> PlayerAttribute {
+        if let Some(values) = self
+            .value
+            .strip_prefix('[')
+            .and_then(|value| value.strip_suffix(']'))
+        {
+            return PlayerAttribute::StringList(
+                values
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|value| !value.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            );
+        }
+
+        match self.value.parse() {
+            Ok(value) => PlayerAttribute::Number(value),
+            Err(_) => PlayerAttribute::String(self.value.clone()),
+        }
+    }
+}
+
+/// Latency to a region from the command line (region=ms)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LatencyArg {
+    pub region: String,
+    pub latency_in_ms: i32,
+}
+
+impl FromStr for LatencyArg {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (region, latency_in_ms) = s
+            .split_once('=')
+            .ok_or_else(|| format!("invalid latency '{}', expected region=ms", s))?;
+
+        Ok(Self {
+            region: region.to_owned(),
+            latency_in_ms: latency_in_ms
+                .parse()
+                .map_err(|_| format!("invalid latency '{}', expected region=ms", s))?,
+        })
+    }
+}
+
+/// A party member in a player config file
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "camelCase")]
+struct PartyMemberConfig {
+    player_id: String,
+
+    #[serde(default)]
+    attributes: HashMap<String, serde_json::Value>,
+
+    #[serde(default)]
+    latencies: HashMap<String, i32>,
+}
+
+/// Player config file, for example:
+///
+/// { "attributes": { "skill": 20, "roles": ["tank"] }, "latencies": { "us-west-2": 40 } }
+///
+/// The top level attributes and latencies apply to every party member
+#[derive(Debug, Default, Deserialize)]
+struct PlayerConfig {
+    #[serde(default)]
+    attributes: HashMap<String, serde_json::Value>,
+
+    #[serde(default)]
+    latencies: HashMap<String, i32>,
+
+    #[serde(default)]
+    party: Vec<PartyMemberConfig>,
+}
+
+fn attribute_from_json(name: &str, value: serde_json::Value) -> anyhow::Result<PlayerAttribute> {
+    let invalid = || anyhow!("Invalid value for player attribute {}", name);
+
+    Ok(match value {
+        serde_json::Value::String(value) => PlayerAttribute::String(value),
+        serde_json::Value::Number(value) => {
+            PlayerAttribute::Number(value.as_f64().ok_or_else(invalid)?)
+        }
+        serde_json::Value::Array(values) => PlayerAttribute::StringList(
+            values
+                .into_iter()
+                .map(|value| match value {
+                    serde_json::Value::String(value) => Ok(value),
+                    _ => Err(invalid()),
+                })
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        serde_json::Value::Object(values) => PlayerAttribute::StringNumberMap(
+            values
+                .into_iter()
+                .map(|(key, value)| Ok((key, value.as_f64().ok_or_else(invalid)?)))
+                .collect::<anyhow::Result<_>>()?,
+        ),
+        _ => bail!("Unsupported value for player attribute {}", name),
+    })
+}
+
+fn attributes_from_json(
+    attributes: HashMap<String, serde_json::Value>,
+) -> anyhow::Result<HashMap<String, PlayerAttribute>> {
+    attributes
+        .into_iter()
+        .map(|(name, value)| Ok((name.clone(), attribute_from_json(&name, value)?)))
+        .collect()
+}
+
+fn read_config(config: Option<impl AsRef<Path>>) -> anyhow::Result<PlayerConfig> {
+    let path = match config {
+        Some(path) => path,
+        None => return Ok(PlayerConfig::default()),
+    };
+    let path = path.as_ref();
+
+    let config = std::fs::read_to_string(path)
+        .map_err(|err| anyhow!("Failed to read {}: {}", path.display(), err))?;
+
+    serde_json::from_str(&config)
+        .map_err(|err| anyhow!("Invalid player config {}: {}", path.display(), err))
+}
+
+/// A player with the config file's shared attributes and latencies
+fn config_player(
+    player_id: impl Into<String>,
+    config: &PlayerConfig,
+) -> anyhow::Result<PlayerDetails> {
+    Ok(PlayerDetails {
+        player_id: player_id.into(),
+        attributes: attributes_from_json(config.attributes.clone())?,
+        latency_in_ms: config.latencies.clone(),
+    })
+}
+
+fn apply_args(player: &mut PlayerDetails, attributes: &[AttributeArg], latencies: &[LatencyArg]) {
+    for attribute in attributes {
+        player
+            .attributes
+            .insert(attribute.name.clone(), attribute.attribute());
+    }
+
+    for latency in latencies {
+        player
+            .latency_in_ms
+            .insert(latency.region.clone(), latency.latency_in_ms);
+    }
+}
+
+/// Builds a player from an optional config file, with command line values overriding it
+pub fn load_player(
+    player_id: impl Into<String>,
+    config: Option<impl AsRef<Path>>,
+    attributes: &[AttributeArg],
+    latencies: &[LatencyArg],
+) -> anyhow::Result<PlayerDetails> {
+    let config = read_config(config)?;
+
+    let mut player = config_player(player_id, &config)?;
+    apply_args(&mut player, attributes, latencies);
+
+    Ok(player)
+}
+
+/// Builds a party from the config file's party and the given player ids
+///
+/// With no party members at all, the party is a single player with a random id.
+/// Command line values apply to every member
+pub fn load_party(
+    player_ids: &[String],
+    config: Option<impl AsRef<Path>>,
+    attributes: &[AttributeArg],
+    latencies: &[LatencyArg],
+) -> anyhow::Result<Vec<PlayerDetails>> {
+    let config = read_config(config)?;
+
+    let mut party = Vec::new();
+    for member in &config.party {
+        let mut player = config_player(&member.player_id, &config)?;
+        player
+            .attributes
+            .extend(attributes_from_json(member.attributes.clone())?);
+        player.latency_in_ms.extend(member.latencies.clone());
+
+        party.push(player);
+    }
+
+    for player_id in player_ids {
+        party.push(config_player(player_id, &config)?);
+    }
+
+    if party.is_empty() {
+        party.push(config_player(Uuid::new_v4().to_string(), &config)?);
+    }
+
+    for player in &mut party {
+        apply_args(player, attributes, latencies);
+    }
+
+    let mut player_ids = HashSet::new();
+    for player in &party {
+        if !player_ids.insert(&player.player_id) {
+            bail!("Player {} is in the party more than once", player.player_id);
+        }
+    }
+
+    Ok(party)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use serde_json::json;
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn attribute(arg: &str) -> Result<(String, PlayerAttribute), String> {
+        let arg = arg.parse::<AttributeArg>()?;
+        Ok((arg.name.clone(), arg.attribute()))
+    }
+
+    /// A player config file holding `config`
+    fn config_file(config: serde_json::Value) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("echo-player-{}.json", Uuid::new_v4()));
+        std::fs::write(&path, config.to_string()).unwrap();
+        path
+    }
+
+    #[test]
+    fn parses_attribute_args() {
+        let string = |value: &str| PlayerAttribute::String(value.to_owned());
+        let list = |values: &[&str]| {
+            PlayerAttribute::StringList(values.iter().map(|value| value.to_string()).collect())
+        };
+
+        for (arg, name, value) in [
+            ("skill=20", "skill", PlayerAttribute::Number(20.0)),
+            ("skill=-1.5", "skill", PlayerAttribute::Number(-1.5)),
+            ("skill=1e3", "skill", PlayerAttribute::Number(1000.0)),
+            ("role=tank", "role", string("tank")),
+            ("role=", "role", string("")),
+            ("motd=a=b", "motd", string("a=b")),
+            ("roles=[tank, healer]", "roles", list(&["tank", "healer"])),
+            ("roles=[tank,,]", "roles", list(&["tank"])),
+            ("roles=[]", "roles", list(&[])),
+            ("roles=[tank", "roles", string("[tank")),
+        ] {
+            assert_eq!(attribute(arg), Ok((name.to_owned(), value)), "{}", arg);
+        }
+    }
+
+    #[test]
+    fn rejects_bad_attribute_args() {
+        for arg in [
+            "skill",
+            "=20",
+            "",
+            "skill=nan",
+            "skill=NaN",
+            "skill=inf",
+            "skill=-inf",
+            "skill=infinity",
+        ] {
+            assert!(attribute(arg).is_err(), "{} parsed", arg);
+        }
+    }
+
+    #[test]
+    fn parses_latency_args() {
+        for (arg, region, latency_in_ms) in [
+            ("us-west-2=40", "us-west-2", 40),
+            ("eu-west-1=0", "eu-west-1", 0),
+        ] {
+            assert_eq!(
+                arg.parse(),
+                Ok(LatencyArg {
+                    region: region.to_owned(),
+                    latency_in_ms
+                }),
+                "{}",
+                arg
+            );
+        }
+
+        for arg in ["us-west-2", "us-west-2=", "us-west-2=4.5", "us-west-2=fast"] {
+            assert!(arg.parse::<LatencyArg>().is_err(), "{} parsed", arg);
+        }
+    }
+
+    #[test]
+    fn parses_config_attributes() {
+        let attributes = attributes_from_json(
+            serde_json::from_value(json!({
+                "skill": 20,
+                "role": "tank",
+                "roles": ["tank", "healer"],
+                "ranks": {"solo": 3, "duo": 2.5},
+            }))
+            .unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(attributes["skill"], PlayerAttribute::Number(20.0));
+        assert_eq!(
+            attributes["role"],
+            PlayerAttribute::String("tank".to_owned())
+        );
+        assert_eq!(
+            attributes["roles"],
+            PlayerAttribute::StringList(vec!["tank".to_owned(), "healer".to_owned()])
+        );
+        assert_eq!(
+            attributes["ranks"],
+            PlayerAttribute::StringNumberMap(
+                [("solo".to_owned(), 3.0), ("duo".to_owned(), 2.5)].into()
+            )
+        );
+
+        for value in [
+            json!(null),
+            json!(true),
+            json!(["tank", 1]),
+            json!({"solo": "3"}),
+        ] {
+            assert!(
+                attribute_from_json("bad", value.clone()).is_err(),
+                "{} parsed",
+                value
+            );
+        }
+    }
+
+    #[test]
+    fn args_override_the_config() {
+        let config = config_file(json!({
+            "attributes": {"skill": 20, "role": "tank"},
+            "latencies": {"us-west-2": 40, "eu-west-1": 150},
+        }));
+
+        let player = load_player(
+            "player-1",
+            Some(&config),
+            &["skill=30".parse().unwrap()],
+            &["eu-west-1=120".parse().unwrap()],
+        )
+        .unwrap();
+
+        assert_eq!(player.player_id, "player-1");
+        assert_eq!(player.attributes["skill"], PlayerAttribute::Number(30.0));
+        assert_eq!(
+            player.attributes["role"],
+            PlayerAttribute::String("tank".to_owned())
+        );
+        assert_eq!(
+            player.latency_in_ms,
+            [("us-west-2".to_owned(), 40), ("eu-west-1".to_owned(), 120)].into()
+        );
+    }
+
+    #[test]
+    fn rejects_bad_configs() {
+        let missing = std::env::temp_dir().join(format!("echo-player-{}.json", Uuid::new_v4()));
+        let bad_attribute = config_file(json!({"attributes": {"skill": null}}));
+        let bad_latency = config_file(json!({"latencies": {"us-west-2": "fast"}}));
+
+        for config in [missing, bad_attribute, bad_latency] {
+            assert!(
+                load_player("player-1", Some(&config), &[], &[]).is_err(),
+                "{} loaded",
+                config.display()
+            );
+        }
+
+        let player = load_player("player-1", None::<&Path>, &[], &[]).unwrap();
+        assert!(player.attributes.is_empty());
+        assert!(player.latency_in_ms.is_empty());
+    }
+
+    fn ids(party: &[PlayerDetails]) -> Vec<&str> {
+        party
+            .iter()
+            .map(|player| player.player_id.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn loads_party_members() {
+        let config = config_file(json!({
+            "attributes": {"skill": 20, "role": "dps"},
+            "latencies": {"us-west-2": 40},
+            "party": [
+                {"playerId": "player-1", "attributes": {"role": "tank"}},
+                {"playerId": "player-2", "latencies": {"us-west-2": 60, "eu-west-1": 150}},
+            ],
+        }));
+
+        let party = load_party(
+            &["player-3".to_owned()],
+            Some(&config),
+            &["skill=25".parse().unwrap()],
+            &["us-east-1=90".parse().unwrap()],
+        )
+        .unwrap();
+        assert_eq!(ids(&party), ["player-1", "player-2", "player-3"]);
+
+        let role = |player: &PlayerDetails| player.attributes["role"].clone();
+        assert_eq!(role(&party[0]), PlayerAttribute::String("tank".to_owned()));
+        assert_eq!(role(&party[1]), PlayerAttribute::String("dps".to_owned()));
+        assert_eq!(role(&party[2]), PlayerAttribute::String("dps".to_owned()));
+
+        for player in &party {
+            assert_eq!(player.attributes["skill"], PlayerAttribute::Number(25.0));
+            assert_eq!(player.latency_in_ms["us-east-1"], 90);
+        }
+        assert_eq!(party[0].latency_in_ms["us-west-2"], 40);
+        assert_eq!(party[1].latency_in_ms["us-west-2"], 60);
+        assert_eq!(party[1].latency_in_ms["eu-west-1"], 150);
+        assert!(!party[2].latency_in_ms.contains_key("eu-west-1"));
+    }
+
+    #[test]
+    fn party_of_one_by_default() {
+        let party = load_party(&[], None::<&Path>, &["skill=25".parse().unwrap()], &[]).unwrap();
+        assert_eq!(party.len(), 1);
+        assert!(Uuid::parse_str(&party[0].player_id).is_ok());
+        assert_eq!(party[0].attributes["skill"], PlayerAttribute::Number(25.0));
+
+        let party = load_party(&["player-1".to_owned()], None::<&Path>, &[], &[]).unwrap();
+        assert_eq!(ids(&party), ["player-1"]);
+    }
+
+    #[test]
+    fn rejects_bad_parties() {
+        let duplicate_member = config_file(json!({
+            "party": [{"playerId": "player-1"}, {"playerId": "player-1"}],
+        }));
+        let one_member = config_file(json!({"party": [{"playerId": "player-1"}]}));
+        let no_player_id = config_file(json!({"party": [{"attributes": {"skill": 20}}]}));
+        let bad_attribute = config_file(json!({
+            "party": [{"playerId": "player-1", "attributes": {"skill": null}}],
+        }));
+
+        for (player_ids, config, error) in [
+            (
+                vec![],
+                duplicate_member,
+                "Player player-1 is in the party more than once",
+            ),
+            (
+                vec!["player-1"],
+                one_member,
+                "Player player-1 is in the party more than once",
+            ),
+            (vec![], no_player_id, "Invalid player config"),
+            (
+                vec![],
+                bad_attribute,
+                "Unsupported value for player attribute skill",
+            ),
+        ] {
+            let player_ids = player_ids
+                .into_iter()
+                .map(str::to_owned)
+                .collect::<Vec<_>>();
+            let err = load_party(&player_ids, Some(&config), &[], &[]).unwrap_err();
+            assert!(err.to_string().starts_with(error), "{}", err);
+        }
+
+        let player_ids = ["player-1".to_owned(), "player-1".to_owned()];
+        assert_eq!(
+            load_party(&player_ids, None::<&Path>, &[], &[])
+                .unwrap_err()
+                .to_string(),
+            "Player player-1 is in the party more than once"
+        );
+    }
+}
@@ -0,0 +1,514 @@
+use std::collections::HashMap;
+use std::convert::Infallible;
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use anyhow::bail;
+use hyper::{
+    header::CONTENT_TYPE,
+    service::{make_service_fn, service_fn},
+    Body, Method, Request, Response, Server, StatusCode,
+};
+use serde::{de::DeserializeOwned, Deserialize, Serialize};
+use tokio::{sync::broadcast, time};
+use tracing::{debug, info, warn};
+
+use echo_protocol::{
+    events::{self, GameLiftEvent},
+    placements::PlacementState,
+    tickets::TicketState,
+};
+
+/// How long a ticket request waits for an update by default
+const DEFAULT_WAIT_SECONDS: u64 = 20;
+
+/// Longest a ticket request can wait for an update
+const MAX_WAIT_SECONDS: u64 = 60;
+
+/// An SNS HTTP(S) delivery, or an SNS record forwarded by echo-notifications
+#[derive(Debug, Deserialize)]
+#[serde(rename_all = "PascalCase")]
+struct SnsNotification {
+    #[serde(rename = "Type")]
+    message_type: String,
+    message_id: String,
+    message: String,
+
+    #[serde(default, rename = "SubscribeURL")]
+    subscribe_url: Option<String>,
+}
+
+struct RelayState {
+    tickets: HashMap<String, TicketState>,
+    placements: HashMap<String, PlacementState>,
+
+    /// ids of updated tickets and placements, for requests waiting on them
+    updates: broadcast::Sender<String>,
+}
+
+/// The ticket or placement, if it has changed since the given update time (ms since the epoch)
+fn changed<T: Clone>(
+    states: &HashMap<String, T>,
+    id: &str,
+    after: Option<i64>,
+    updated_at: impl Fn(&T) -> i64,
+) -> Option<T> {
+    states
+        .get(id)
+        .filter(|state| match after {
+            Some(after) => updated_at(state) > after,
+            None => true,
+        })
+        .cloned()
+}
+
+impl RelayState {
+    fn ticket(&self, ticket_id: &str, after: Option<i64>) -> Option<TicketState> {
+        changed(&self.tickets, ticket_id, after, |state| {
+            state.updated_at.timestamp_millis()
+        })
+    }
+
+    fn placement(&self, placement_id: &str, after: Option<i64>) -> Option<PlacementState> {
+        changed(&self.placements, placement_id, after, |state| {
+            state.updated_at.timestamp_millis()
+        })
+    }
+
+    fn update_placement(&mut self, state: PlacementState) {
+        info!("Placement {} is {:?}", state.placement_id, state.status);
+
+        // placements only have one event, so there's nothing newer to keep
+        let placement_id = state.placement_id.clone();
+        self.placements.insert(placement_id.clone(), state);
+
+        self.updates.send(placement_id).ok();
+    }
+
+    fn update(&mut self, state: TicketState) {
+        if let Some(stored) = self.tickets.get(&state.ticket_id) {
+            if !state.supersedes(stored) {
+                debug!("Ticket {} has a newer or finished state", state.ticket_id);
+                return;
+            }
+        }
+
+        info!("Ticket {} is {:?}", state.ticket_id, state.status);
+
+        let ticket_id = state.ticket_id.clone();
+        self.tickets.insert(ticket_id.clone(), state);
+
+        // nobody waiting is fine
+        self.updates.send(ticket_id).ok();
+    }
+}
+
+fn publish(state: &Mutex<RelayState>, body: &[u8]) -> Result<(), String> {
+    let notification: SnsNotification =
+        serde_json::from_slice(body).map_err(|err| format!("Invalid SNS message: {}", err))?;
+
+    match notification.message_type.as_str() {
+        "Notification" => {
+            let event = events::parse_event(&notification.message)
+                .map_err(|err| format!("{} in message {}", err, notification.message_id))?;
+
+            let mut state = state.lock().unwrap();
+            match event {
+                GameLiftEvent::Matchmaking(event) => {
+                    for ticket in TicketState::from_event(&event) {
+                        state.update(ticket);
+                    }
+                }
+                GameLiftEvent::Placement(event) => {
+                    state.update_placement(PlacementState::from_event(&event))
+                }
+            }
+
+            Ok(())
+        }
+        "SubscriptionConfirmation" => {
+            info!(
+                "Confirm the SNS subscription by visiting {}",
+                notification.subscribe_url.as_deref().unwrap_or("(no url)")
+            );
+
+            Ok(())
+        }
+        message_type => Err(format!("Unsupported SNS message type {}", message_type)),
+    }
+}
+
+/// Parses ?after=<ms>&wait=<seconds>
+fn wait_params(query: Option<&str>) -> Result<(Option<i64>, u64), String> {
+    let mut after = None;
+    let mut wait = DEFAULT_WAIT_SECONDS;
+
+    for param in query
+        .unwrap_or_default()
+        .split('&')
+        .filter(|s| !s.is_empty())
+    {
+        match param.split_once('=') {
+            Some(("after", value)) => {
+                after = Some(value.parse().map_err(|_| "Invalid after".to_owned())?)
+            }
+            Some(("wait", value)) => wait = value.parse().map_err(|_| "Invalid wait".to_owned())?,
+            _ => return Err(format!("Unknown parameter {}", param)),
+        }
+    }
+
+    Ok((after, wait.min(MAX_WAIT_SECONDS)))
+}
+
+/// Waits until the ticket or placement changes, or the wait runs out
+async fn wait_for<T>(
+    state: &Mutex<RelayState>,
+    id: &str,
+    after: Option<i64>,
+    wait: u64,
+    get: impl Fn(&RelayState, &str, Option<i64>) -> Option<T>,
+) -> Option<T> {
+    let deadline = time::Instant::now() + time::Duration::from_secs(wait);
+
+    let mut updates = {
+        let state = state.lock().unwrap();
+        if let Some(changed) = get(&state, id, after) {
+            return Some(changed);
+        }
+
+        // subscribed while locked, so no update can slip in between
+        state.updates.subscribe()
+    };
+
+    loop {
+        match time::timeout_at(deadline, updates.recv()).await {
+            Ok(Ok(updated)) if updated != id => continue,
+            // updated, or we missed some updates and have to check
+            Ok(Ok(_)) | Ok(Err(broadcast::error::RecvError::Lagged(_))) => {
+                if let Some(changed) = get(&state.lock().unwrap(), id, after) {
+                    return Some(changed);
+                }
+            }
+            Ok(Err(broadcast::error::RecvError::Closed)) | Err(_) => return None,
+        }
+    }
+}
+
+/// Answers a long poll with the changed ticket or placement, or 204 if nothing changed
+async fn long_poll<T: Serialize>(
+    state: &Mutex<RelayState>,
+    id: &str,
+    query: Option<&str>,
+    get: impl Fn(&RelayState, &str, Option<i64>) -> Option<T>,
+) -> Result<Response<Body>, String> {
+    let (after, wait) = wait_params(query)?;
+
+    match wait_for(state, id, after, wait, get).await {
+        Some(changed) => serde_json::to_vec(&changed)
+            .map(|body| response(StatusCode::OK, body))
+            .map_err(|err| err.to_string()),
+        None => Ok(response(StatusCode::NO_CONTENT, Body::empty())),
+    }
+}
+
+fn response(status: StatusCode, body: impl Into<Body>) -> Response<Body> {
+    Response::builder()
+        .status(status)
+        .header(CONTENT_TYPE, "application/json")
+        .body(body.into())
+        .unwrap()
+}
+
+async fn handle_request(
+    request: Request<Body>,
+    state: Arc<Mutex<RelayState>>,
+) -> Result<Response<Body>, Infallible> {
+    let method = request.method().clone();
+    let path = request.uri().path().to_owned();
+    let query = request.uri().query().map(str::to_owned);
+
+    let resource = path
+        .strip_prefix('/')
+        .and_then(|path| path.split_once('/'))
+        .filter(|(_, id)| !id.is_empty() && !id.contains('/'));
+
+    let res = match (&method, resource) {
+        (&Method::POST, None) if path == "/" || path == "/sns" => {
+            match hyper::body::to_bytes(request.into_body()).await {
+                Ok(body) => publish(&state, &body).map(|_| response(StatusCode::OK, "{}")),
+                Err(err) => Err(err.to_string()),
+            }
+        }
+        (&Method::GET, Some(("tickets", ticket_id))) => {
+            long_poll(&state, ticket_id, query.as_deref(), RelayState::ticket).await
+        }
+        (&Method::GET, Some(("placements", placement_id))) => {
+            long_poll(
+                &state,
+                placement_id,
+                query.as_deref(),
+                RelayState::placement,
+            )
+            .await
+        }
+        _ => Ok(response(StatusCode::NOT_FOUND, "{}")),
+    };
+
+    Ok(res.unwrap_or_else(|err| {
+        warn!("{} {}: {}", path, query.unwrap_or_default(), err);
+
+        response(
+            StatusCode::BAD_REQUEST,
+            serde_json::json!({ "message": err }).to_string(),
+        )
+    }))
+}
+
+/// Relays GameLift notifications to clients waiting on their tickets or placements
+///
+/// SNS messages (or echo-notifications) are POSTed to /sns, clients long-poll
+/// GET /tickets/{ticket id} or /placements/{placement id}?after={ms}&wait={seconds}
+pub async fn run(port: u16) -> anyhow::Result<()> {
+    let (updates, _) = broadcast::channel(64);
+    let state = Arc::new(Mutex::new(RelayState {
+        tickets: HashMap::new(),
+        placements: HashMap::new(),
+        updates,
+    }));
+
+    let addr = SocketAddr::from(([0, 0, 0, 0], port));
+
+    let make_service = make_service_fn(move |_| {
+        let state = state.clone();
+        async move {
+            Ok::<_, Infallible>(service_fn(move |request| {
+                handle_request(request, state.clone())
+            }))
+        }
+    });
+
+    info!("Notification relay listening on {}", addr);
+
+    Server::bind(&addr)
+        .serve(make_service)
+        .with_graceful_shutdown(async {
+            tokio::signal::ctrl_c().await.ok();
+        })
+        .await?;
+
+    info!("Notification relay stopped");
+
+    Ok(())
+}
+
+/// Long-polls a notification relay for ticket updates
+pub struct RelayClient {
+    client: reqwest::Client,
+    endpoint: String,
+}
+
+impl RelayClient {
+    pub fn new(endpoint: impl Into<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            endpoint: endpoint.into().trim_end_matches('/').to_owned(),
+        }
+    }
+
+    async fn wait_for<T: DeserializeOwned>(
+        &self,
+        resource: &str,
+        id: &str,
+        after: Option<i64>,
+    ) -> anyhow::Result<Option<T>> {
+        let mut url = format!(
+            "{}/{}/{}?wait={}",
+            self.endpoint, resource, id, DEFAULT_WAIT_SECONDS
+        );
+        if let Some(after) = after {
+            url.push_str(&format!("&after={}", after));
+        }
+
+        let response = self
+            .client
+            .get(&url)
+            .timeout(time::Duration::from_secs(DEFAULT_WAIT_SECONDS + 10))
+            .send()
+            .await?;
+
+        let status = response.status();
+        if status == reqwest::StatusCode::NO_CONTENT {
+            return Ok(None);
+        }
+        if !status.is_success() {
+            bail!(
+                "Waiting for {} failed ({}): {}",
+                id,
+                status,
+                response.text().await?
+            );
+        }
+
+        Ok(Some(response.json().await?))
+    }
+
+    /// Waits for the ticket to change since the given update, None if it didn't
+    pub async fn wait_for_ticket(
+        &self,
+        ticket_id: &str,
+        after: Option<i64>,
+    ) -> anyhow::Result<Option<TicketState>> {
+        self.wait_for("tickets", ticket_id, after).await
+    }
+
+    /// Waits for the placement to finish, None if it hasn't yet
+    pub async fn wait_for_placement(
+        &self,
+        placement_id: &str,
+    ) -> anyhow::Result<Option<PlacementState>> {
+        self.wait_for("placements", placement_id, None).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::{json, Value};
+
+    use echo_protocol::{placements::PlacementStatus, tickets::TicketStatus};
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    fn event(time: &str, detail: Value) -> String {
+        json!({
+            "version": "0",
+            "id": format!("event-{}", time),
+            "detail-type": "GameLift Matchmaking Event",
+            "source": "aws.gamelift",
+            "time": time,
+            "region": "us-west-2",
+            "resources": [],
+            "detail": detail,
+        })
+        .to_string()
+    }
+
+    fn ticket_event(time: &str, event_type: &str) -> String {
+        event(
+            time,
+            json!({
+                "type": event_type,
+                "tickets": [{
+                    "ticketId": "ticket-1",
+                    "startTime": "2022-06-01T12:00:00.000Z",
+                    "players": [{"playerId": "player-1"}],
+                }],
+                "matchId": "match-1",
+                "acceptanceRequired": false,
+            }),
+        )
+    }
+
+    /// Posts an SNS notification, retrying until the relay is listening
+    async fn publish(endpoint: &str, message: String) {
+        let notification = json!({
+            "Type": "Notification",
+            "MessageId": Uuid::new_v4().to_string(),
+            "Message": message,
+        });
+
+        let client = reqwest::Client::new();
+        let deadline = time::Instant::now() + time::Duration::from_secs(5);
+        loop {
+            match client
+                .post(format!("{}/sns", endpoint))
+                .json(&notification)
+                .send()
+                .await
+            {
+                Ok(response) => {
+                    assert_eq!(response.status(), reqwest::StatusCode::OK);
+                    return;
+                }
+                Err(err) if time::Instant::now() < deadline => {
+                    debug!("Relay not up yet: {}", err);
+                    time::sleep(time::Duration::from_millis(20)).await;
+                }
+                Err(err) => panic!("relay never came up: {}", err),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn waiting_clients_get_updates() {
+        let port = free_port();
+        let relay = tokio::spawn(run(port));
+        let endpoint = format!("http://127.0.0.1:{}", port);
+        let client = RelayClient::new(format!("{}/", endpoint));
+
+        publish(
+            &endpoint,
+            ticket_event("2022-06-01T12:00:01.000Z", "MatchmakingSearching"),
+        )
+        .await;
+        let searching = client.wait_for_ticket("ticket-1", None).await.unwrap();
+        let searching = searching.unwrap();
+        assert_eq!(searching.status, TicketStatus::Searching);
+
+        // waits for something newer than what it has
+        let waiting = tokio::spawn(async move {
+            let after = searching.updated_at.timestamp_millis();
+            client.wait_for_ticket("ticket-1", Some(after)).await
+        });
+        time::sleep(time::Duration::from_millis(100)).await;
+        assert!(!waiting.is_finished());
+
+        publish(
+            &endpoint,
+            ticket_event("2022-06-01T12:00:02.000Z", "PotentialMatchCreated"),
+        )
+        .await;
+        let created = waiting.await.unwrap().unwrap().unwrap();
+        assert_eq!(created.status, TicketStatus::Placing);
+        assert_eq!(created.match_id.as_deref(), Some("match-1"));
+
+        let client = RelayClient::new(&endpoint);
+        let waiting = tokio::spawn(async move { client.wait_for_placement("placement-1").await });
+        publish(
+            &endpoint,
+            event(
+                "2022-06-01T12:00:03.000Z",
+                json!({
+                    "type": "PlacementFulfilled",
+                    "placementId": "placement-1",
+                    "gameSessionArn": "arn:aws:gamelift:us-west-2::gamesession/fleet-1/gsess-1",
+                    "ipAddress": "192.0.2.10",
+                    "port": "7777",
+                    "startTime": "2022-06-01T12:00:02.500Z",
+                }),
+            ),
+        )
+        .await;
+        let placement = waiting.await.unwrap().unwrap().unwrap();
+        assert_eq!(placement.status, PlacementStatus::Fulfilled);
+        assert_eq!(placement.port, Some(7777));
+
+        relay.abort();
+    }
+
+    #[tokio::test]
+    async fn relay_down() {
+        let client = RelayClient::new(format!("http://127.0.0.1:{}", free_port()));
+
+        assert!(client.wait_for_ticket("ticket-1", None).await.is_err());
+        assert!(client.wait_for_placement("placement-1").await.is_err());
+    }
+}
@@ -3,20 +3,33 @@ use std::net::SocketAddr;
 use std::pin::Pin;
 use std::sync::Arc;
 
-use anyhow::bail;
-use aws_gamelift_server_sdk_rs::{
-    log_parameters::LogParameters, process_parameters::ProcessParameters,
-};
 use chrono::Utc;
 use futures_util::FutureExt;
 use tokio::{
-    io::{AsyncReadExt, AsyncWriteExt},
     net::{TcpListener, TcpStream},
-    sync::{mpsc, watch, RwLock},
+    sync::{broadcast, mpsc, watch, RwLock},
+    task::JoinHandle,
     time,
 };
 use tracing::{debug, error, info, warn};
 
+use echo_protocol::Message;
+
+use crate::hosting::{HostingBackend, HostingEvent};
+use crate::util;
+
+/// How long to drain for when no termination time is available
+const DEFAULT_DRAIN_SECONDS: i64 = 30;
+
+/// How long before the termination time to end the session
+const DRAIN_MARGIN_SECONDS: i64 = 5;
+
+/// How often to warn players while draining
+const DRAIN_WARNING_INTERVAL_SECONDS: i64 = 10;
+
+/// How long before the termination time to start warning players every second
+const DRAIN_FINAL_COUNTDOWN_SECONDS: i64 = 5;
+
 type BeginSessionOutput = Pin<Box<dyn future::Future<Output = ()> + Send>>;
 type BeginSession = Box<dyn Fn() -> BeginSessionOutput + Send + Sync>;
 
@@ -67,34 +80,26 @@ impl ServerState {
     }
 }
 
-async fn read_player_session_id(stream: &mut TcpStream) -> anyhow::Result<String> {
-    let len = stream.read_u8().await? as usize;
-
-    let mut buf = vec![0; len];
-
-    let mut t = 0;
-    while t < len {
-        let n = stream.read(&mut buf[t..]).await?;
-        if n == 0 {
-            bail!("Connection closed!");
-        }
-
-        t += n;
-    }
-
-    Ok(std::str::from_utf8(&buf)?.to_string())
+/// Returns the time (in seconds since the epoch) to drain until
+///
+/// Falls back to a default window if no termination time is available
+pub fn drain_deadline(termination_time: Option<i64>) -> i64 {
+    termination_time.unwrap_or_else(|| Utc::now().timestamp() + DEFAULT_DRAIN_SECONDS)
 }
 
 async fn handle_connection(
-    mut stream: TcpStream,
+    stream: TcpStream,
     addr: SocketAddr,
     silent: bool,
     state: Arc<RwLock<ServerState>>,
+    mut notifications: broadcast::Receiver<Message>,
 ) -> anyhow::Result<()> {
+    let (mut reader, mut writer) = stream.into_split();
+
     // TODO: read player id from stream
     let player_id = "N/A";
 
-    let player_session_id = match read_player_session_id(&mut stream).await {
+    let player_session_id = match echo_protocol::read_player_session_id(&mut reader).await {
         Ok(player_session_id) => player_session_id,
         Err(err) => {
             info!("Connection from {} error: {}", addr, err);
@@ -102,6 +107,12 @@ async fn handle_connection(
         }
     };
 
+    // an empty handshake is a health probe
+    if player_session_id.is_empty() {
+        debug!("Health probe from {}", addr);
+        return Ok(());
+    }
+
     info!("Accepted player {} ({})", player_id, player_session_id);
     {
         let mut state = state.write().await;
@@ -111,36 +122,68 @@ async fn handle_connection(
         state.last_update_time = Utc::now().timestamp();
     }
 
-    let mut buf = [0; 1024];
-    loop {
-        let n = stream.read(&mut buf).await?;
-        if n == 0 {
-            info!("Connection from {} closed", addr);
+    // replies and server notifications are both written from their own task
+    let (reply_sender, mut reply_receiver) = mpsc::unbounded_channel();
+    tokio::spawn(async move {
+        loop {
+            let message = tokio::select! {
+                message = reply_receiver.recv() => match message {
+                    Some(message) => message,
+                    None => break,
+                },
+                message = notifications.recv() => match message {
+                    Ok(message) => message,
+                    Err(broadcast::error::RecvError::Lagged(_)) => continue,
+                    Err(broadcast::error::RecvError::Closed) => break,
+                },
+            };
+
+            if let Err(err) = message.write(&mut writer).await {
+                info!("Connection to {} write error: {}", addr, err);
+                break;
+            }
+        }
+    });
 
-            {
-                let mut state = state.write().await;
-                (state.callbacks.remove_player_session)(player_session_id.clone()).await;
+    let res = loop {
+        match Message::read(&mut reader).await {
+            Ok(Some(Message::Echo(message))) => {
+                if !silent {
+                    info!("Read from {}: {}", addr, message);
+                }
 
-                state.player_count -= 1;
-                state.last_update_time = Utc::now().timestamp();
+                if reply_sender.send(Message::Echo(message)).is_err() {
+                    break Ok(());
+                }
             }
-            info!("Removed player {} ({})", player_id, player_session_id);
-
-            return Ok(());
+            Ok(Some(message)) => {
+                warn!("Unexpected message from {}: {:?}", addr, message);
+            }
+            Ok(None) => {
+                info!("Connection from {} closed", addr);
+                break Ok(());
+            }
+            Err(err) => break Err(err),
         }
+    };
 
-        if !silent {
-            info!("Read from {}: {}", addr, std::str::from_utf8(&buf[0..n])?);
-        }
+    {
+        let mut state = state.write().await;
+        (state.callbacks.remove_player_session)(player_session_id.clone()).await;
 
-        stream.write_all(&buf[0..n]).await?;
+        state.player_count -= 1;
+        state.last_update_time = Utc::now().timestamp();
     }
+    info!("Removed player {} ({})", player_id, player_session_id);
+
+    res
 }
 
 pub async fn run(
     addr: impl AsRef<str>,
     silent: bool,
     mut shutdown: watch::Receiver<bool>,
+    mut drain: watch::Receiver<Option<i64>>,
     callbacks: ServerCallbacks,
     timeout: Option<u64>,
 ) -> anyhow::Result<()> {
@@ -151,21 +194,33 @@ pub async fn run(
         ..Default::default()
     }));
 
+    let (notifications, _) = broadcast::channel(16);
+
     info!("Listening on {}", addr.as_ref());
     let listener = TcpListener::bind(addr.as_ref()).await?;
 
     info!("Starting session ...");
     (state.read().await.callbacks.begin_session)().await;
 
-    loop {
-        let mut timer = time::interval(time::Duration::from_secs(timeout.unwrap_or(300)));
+    let mut timer = time::interval(time::Duration::from_secs(timeout.unwrap_or(300)));
+    let mut countdown = time::interval(time::Duration::from_secs(1));
+
+    let mut draining_until = None;
+    let mut last_warning = 0;
 
+    loop {
         tokio::select! {
-            res = listener.accept() => {
+            res = listener.accept(), if draining_until.is_none() => {
                 let (stream, addr) = res?;
 
                 info!("New connection from {}", addr);
-                tokio::spawn(handle_connection(stream, addr, silent, state.clone()));
+                tokio::spawn(handle_connection(
+                    stream,
+                    addr,
+                    silent,
+                    state.clone(),
+                    notifications.subscribe(),
+                ));
 
             },
             _ = timer.tick() => {
@@ -176,6 +231,37 @@ pub async fn run(
                     return Ok(());
                 }
             }
+            _ = countdown.tick(), if draining_until.is_some() => {
+                let deadline: i64 = draining_until.unwrap();
+
+                let now = Utc::now().timestamp();
+                let remaining = deadline - now;
+
+                let state = state.read().await;
+                if state.player_count == 0 || remaining <= DRAIN_MARGIN_SECONDS {
+                    info!("Session drained, exiting ...");
+                    (state.callbacks.end_session)().await;
+                    return Ok(());
+                }
+
+                if now - last_warning >= DRAIN_WARNING_INTERVAL_SECONDS
+                    || remaining <= DRAIN_MARGIN_SECONDS + DRAIN_FINAL_COUNTDOWN_SECONDS
+                {
+                    // no subscribers just means nobody is connected
+                    let _ = notifications.send(Message::ShutdownWarning(remaining as u64));
+                    last_warning = now;
+                }
+            }
+            Ok(()) = drain.changed(), if draining_until.is_none() => {
+                let deadline = *drain.borrow();
+                if let Some(deadline) = deadline {
+                    info!(
+                        "Draining, terminating in {} seconds ...",
+                        deadline - Utc::now().timestamp()
+                    );
+                    draining_until = Some(deadline);
+                }
+            }
             _ = shutdown.changed() => {
                 let shutdown = shutdown.borrow();
                 if *shutdown {
@@ -187,132 +273,649 @@ pub async fn run(
     }
 }
 
-pub async fn run_gamelift(port: u16) -> anyhow::Result<()> {
-    let mut api = aws_gamelift_server_sdk_rs::api::Api::default();
-    api.init_sdk().await?;
-
-    let (terminate_sender, mut terminate_receiver) = mpsc::unbounded_channel();
-    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
-
-    let api = Arc::new(RwLock::new(api));
-
-    api.write()
-        .await
-        .process_ready(ProcessParameters {
-            on_start_game_session: Box::new({
-                let api = api.clone();
-                move |game_session| {
-                    info!("Starting game session: {:?}", game_session);
-
-                    let callbacks = ServerCallbacks {
-                        begin_session: Box::new({
-                            let api = api.clone();
-                            move || {
-                                let api = api.clone();
-                                async move {
-                                    if let Err(err) =
-                                        api.write().await.activate_game_session().await
-                                    {
-                                        error!("Failed to begin session: {}", err);
-                                    }
-                                }
-                                .boxed()
-                            }
-                        }),
-                        end_session: Box::new({
-                            let api = api.clone();
-                            move || {
-                                let api = api.clone();
-                                async move {
-                                    if let Err(err) = api.write().await.process_ending().await {
-                                        error!("Failed to end session: {}", err);
-                                    }
-                                }
-                                .boxed()
-                            }
-                        }),
-                        accept_player_session: Box::new({
-                            let api = api.clone();
-                            move |player_session_id| {
-                                let api = api.clone();
-                                async move {
-                                    if let Err(err) = api
-                                        .write()
-                                        .await
-                                        .accept_player_session(player_session_id)
-                                        .await
-                                    {
-                                        error!("Player session accept error: {}", err);
-                                    }
-                                }
-                                .boxed()
-                            }
-                        }),
-                        remove_player_session: Box::new({
-                            let api = api.clone();
-                            move |player_session_id| {
-                                let api = api.clone();
-                                async move {
-                                    if let Err(err) = api
-                                        .write()
-                                        .await
-                                        .remove_player_session(player_session_id)
-                                        .await
-                                    {
-                                        error!("Player session remove error: {}", err);
-                                    }
-                                }
-                                .boxed()
-                            }
-                        }),
-                    };
-
-                    // spawn the server process
-                    tokio::spawn(run(
-                        format!("0.0.0.0:{}", port),
-                        false,
-                        shutdown_receiver.clone(),
-                        callbacks,
-                        Some(60),
-                    ));
-
-                    info!("Waiting for session ...");
-
-                    future::ready(()).boxed()
+fn hosted_callbacks(backend: Arc<dyn HostingBackend>) -> ServerCallbacks {
+    ServerCallbacks {
+        begin_session: Box::new({
+            let backend = backend.clone();
+            move || {
+                let backend = backend.clone();
+                async move {
+                    if let Err(err) = backend.activate_session().await {
+                        error!("Failed to begin session: {}", err);
+                    }
                 }
-            }),
-            on_update_game_session: Box::new(|update_game_session| {
-                warn!("Update game session: {:?}", update_game_session);
+                .boxed()
+            }
+        }),
+        end_session: Box::new({
+            let backend = backend.clone();
+            move || {
+                let backend = backend.clone();
+                async move {
+                    if let Err(err) = backend.process_ending().await {
+                        error!("Failed to end session: {}", err);
+                    }
+                }
+                .boxed()
+            }
+        }),
+        accept_player_session: Box::new({
+            let backend = backend.clone();
+            move |player_session_id| {
+                let backend = backend.clone();
+                async move {
+                    if let Err(err) = backend.accept_player_session(player_session_id).await {
+                        error!("Player session accept error: {}", err);
+                    }
+                }
+                .boxed()
+            }
+        }),
+        remove_player_session: Box::new(move |player_session_id| {
+            let backend = backend.clone();
+            async move {
+                if let Err(err) = backend.remove_player_session(player_session_id).await {
+                    error!("Player session remove error: {}", err);
+                }
+            }
+            .boxed()
+        }),
+    }
+}
 
-                future::ready(()).boxed()
-            }),
-            on_process_terminate: Box::new(move || {
-                info!("Process terminating ...");
+/// Runs the server under a hosting backend, sessions are started as the backend requests them
+pub async fn run_hosted(
+    backend: Arc<dyn HostingBackend>,
+    host: impl AsRef<str>,
+    port: u16,
+    silent: bool,
+    session_timeout: Option<u64>,
+    log_dir: impl Into<String>,
+) -> anyhow::Result<()> {
+    let (event_sender, mut event_receiver) = mpsc::unbounded_channel();
+    let (shutdown_sender, shutdown_receiver) = watch::channel(false);
+    let (drain_sender, drain_receiver) = watch::channel(None);
 
-                shutdown_sender.send(true).unwrap();
-                terminate_sender.send(true).unwrap();
+    info!("Starting {} hosting ...", backend.name());
 
-                future::ready(()).boxed()
-            }),
-            on_health_check: Box::new(|| {
+    backend
+        .ready(
+            port,
+            vec![log_dir.into()],
+            event_sender,
+            Arc::new(|| {
                 async move {
                     debug!("health check");
                     true
                 }
                 .boxed()
             }),
-            port: port as i32,
-            log_parameters: LogParameters {
-                log_paths: vec!["logs".to_string()],
-            },
-        })
+        )
         .await?;
 
     info!("Waiting for game session ...");
 
-    terminate_receiver.recv().await;
+    let mut session: Option<JoinHandle<anyhow::Result<()>>> = None;
+    let mut backend_closed = false;
+
+    let terminate = util::wait_for_terminate();
+    tokio::pin!(terminate);
+
+    loop {
+        tokio::select! {
+            event = event_receiver.recv(), if !backend_closed => {
+                match event {
+                    Some(HostingEvent::StartSession(session_info)) => {
+                        if session.as_ref().is_some_and(|session| !session.is_finished()) {
+                            warn!("Session already running, ignoring {:?}", session_info);
+                            continue;
+                        }
+
+                        // the last session ended by itself, which the backend has already been told
+                        if let Some(ended) = session.take() {
+                            if let Err(err) = ended.await? {
+                                warn!("Last session failed: {}", err);
+                            }
+                        }
+
+                        info!("Starting game session: {:?}", session_info);
+
+                        // spawn the server process
+                        session = Some(tokio::spawn(run(
+                            format!("{}:{}", host.as_ref(), port),
+                            silent,
+                            shutdown_receiver.clone(),
+                            drain_receiver.clone(),
+                            hosted_callbacks(backend.clone()),
+                            session_timeout,
+                        )));
+
+                        info!("Waiting for session ...");
+                    }
+                    Some(HostingEvent::UpdateSession { description }) => {
+                        warn!("Update game session: {}", description);
+                    }
+                    Some(HostingEvent::Terminate) => {
+                        info!("Process terminating ...");
+                        break;
+                    }
+                    // not a terminate request, the process keeps running until it gets one
+                    None => {
+                        warn!("Hosting backend stopped sending events");
+                        backend_closed = true;
+                    }
+                }
+            }
+            res = &mut terminate => {
+                res?;
+
+                info!("Received SIGTERM, terminating ...");
+                break;
+            }
+        }
+    }
+
+    let termination_time = match backend.termination_time().await {
+        Ok(termination_time) => termination_time,
+        Err(err) => {
+            warn!("Failed to get termination time: {}", err);
+            None
+        }
+    };
+
+    let deadline = drain_deadline(termination_time);
+    drain_sender.send(Some(deadline))?;
+
+    match session {
+        Some(mut session) => {
+            // the session ends itself before the deadline, this is just a backstop
+            let remaining = (deadline - Utc::now().timestamp()).max(0) as u64;
+            match time::timeout(time::Duration::from_secs(remaining), &mut session).await {
+                Ok(res) => res??,
+                Err(_) => {
+                    warn!("Session failed to drain, shutting down ...");
+                    shutdown_sender.send(true)?;
+
+                    session.await??;
+                    backend.process_ending().await?;
+                }
+            }
+        }
+        None => {
+            backend.process_ending().await?;
+        }
+    }
 
     info!("Process terminated!");
 
     Ok(())
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use aws_gamelift_server_sdk_rs::entity::{GameSession, UpdateGameSession, UpdateReason};
+
+    use crate::hosting::{
+        fake::{FakeBackend, HostingCall},
+        fake_gamelift::{FakeGameLiftApi, GameLiftCall},
+        gamelift::GameLiftBackend,
+        standalone::StandaloneBackend,
+        SessionInfo,
+    };
+
+    /// Picks a free local port, the listener is dropped so the server can bind it
+    fn free_port() -> u16 {
+        std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port()
+    }
+
+    #[tokio::test]
+    async fn drain_counts_down_then_ends_the_session() {
+        let port = free_port();
+        let (_shutdown_sender, shutdown) = watch::channel(false);
+        let (drain_sender, drain) = watch::channel(None);
+        let (ended_sender, ended) = watch::channel(false);
+        let server = tokio::spawn(run(
+            format!("127.0.0.1:{}", port),
+            true,
+            shutdown,
+            drain,
+            ServerCallbacks {
+                end_session: Box::new(move || {
+                    ended_sender.send_replace(true);
+                    future::ready(()).boxed()
+                }),
+                ..Default::default()
+            },
+            None,
+        ));
+
+        let mut stream = loop {
+            match TcpStream::connect(("127.0.0.1", port)).await {
+                Ok(stream) => break stream,
+                Err(_) => time::sleep(time::Duration::from_millis(10)).await,
+            }
+        };
+        echo_protocol::write_player_session_id(&mut stream, "player-session-1")
+            .await
+            .unwrap();
+
+        // the echo means the player has been counted
+        Message::Echo("hello".to_owned())
+            .write(&mut stream)
+            .await
+            .unwrap();
+        assert_eq!(
+            Message::read(&mut stream).await.unwrap(),
+            Some(Message::Echo("hello".to_owned()))
+        );
+
+        // already in the final countdown, so warned every second
+        let countdown = DRAIN_MARGIN_SECONDS + 3;
+        drain_sender
+            .send(Some(Utc::now().timestamp() + countdown))
+            .unwrap();
+
+        let mut warnings = Vec::new();
+        loop {
+            let message = time::timeout(time::Duration::from_secs(10), Message::read(&mut stream))
+                .await
+                .expect("the session didn't end")
+                .unwrap();
+            match message {
+                Some(Message::ShutdownWarning(remaining)) => warnings.push(remaining),
+                Some(message) => panic!("unexpected message {:?}", message),
+                // the player stayed, so the session ends at the margin
+                None => break,
+            }
+        }
+
+        assert!(warnings.len() >= 2, "{:?}", warnings);
+        assert!(warnings[0] <= countdown as u64, "{:?}", warnings);
+        assert!(
+            warnings.windows(2).all(|pair| pair[1] < pair[0]),
+            "{:?}",
+            warnings
+        );
+        assert!(*warnings.last().unwrap() > DRAIN_MARGIN_SECONDS as u64);
+
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+        assert!(*ended.borrow());
+    }
+
+    #[tokio::test]
+    async fn drain_without_players_ends_straight_away() {
+        let port = free_port();
+        let (_shutdown_sender, shutdown) = watch::channel(false);
+        let (drain_sender, drain) = watch::channel(None);
+        let server = tokio::spawn(run(
+            format!("127.0.0.1:{}", port),
+            true,
+            shutdown,
+            drain,
+            ServerCallbacks::default(),
+            None,
+        ));
+
+        drain_sender.send(Some(drain_deadline(None))).unwrap();
+
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    fn spawn_hosted(
+        backend: Arc<dyn HostingBackend>,
+        port: u16,
+        session_timeout: Option<u64>,
+    ) -> JoinHandle<anyhow::Result<()>> {
+        let log_dir = std::env::temp_dir().to_string_lossy().into_owned();
+        tokio::spawn(run_hosted(
+            backend,
+            "127.0.0.1",
+            port,
+            true,
+            session_timeout,
+            log_dir,
+        ))
+    }
+
+    async fn connect(port: u16, player_session_id: &str) -> TcpStream {
+        let mut stream = TcpStream::connect(("127.0.0.1", port)).await.unwrap();
+        echo_protocol::write_player_session_id(&mut stream, player_session_id)
+            .await
+            .unwrap();
+
+        stream
+    }
+
+    async fn wait_for_call(backend: &FakeBackend, call: HostingCall) {
+        time::timeout(
+            time::Duration::from_secs(5),
+            backend.wait_for_call(|made| *made == call),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("no {:?} call in {:?}", call, backend.calls()))
+        .unwrap();
+    }
+
+    async fn wait_for_gamelift_call(api: &FakeGameLiftApi, call: GameLiftCall) {
+        time::timeout(
+            time::Duration::from_secs(5),
+            api.wait_for_call(|made| *made == call),
+        )
+        .await
+        .unwrap_or_else(|_| panic!("no {:?} call in {:?}", call, api.calls()))
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn hosted_session_lifecycle() {
+        let port = free_port();
+        let backend = Arc::new(FakeBackend::default());
+        let server = spawn_hosted(backend.clone(), port, None);
+
+        wait_for_call(&backend, HostingCall::Ready(port)).await;
+        assert!(backend.check_health().await.unwrap());
+
+        backend
+            .start_session(SessionInfo {
+                session_id: "session-1".to_owned(),
+                max_players: Some(2),
+            })
+            .unwrap();
+        wait_for_call(&backend, HostingCall::ActivateSession).await;
+
+        let mut stream = connect(port, "player-session-1").await;
+        Message::Echo("hello".to_owned())
+            .write(&mut stream)
+            .await
+            .unwrap();
+        assert_eq!(
+            Message::read(&mut stream).await.unwrap(),
+            Some(Message::Echo("hello".to_owned()))
+        );
+        wait_for_call(
+            &backend,
+            HostingCall::AcceptPlayerSession("player-session-1".to_owned()),
+        )
+        .await;
+
+        // players are warned and the session ends once they leave
+        backend
+            .terminate(Some(Utc::now().timestamp() + 30))
+            .unwrap();
+        match Message::read(&mut stream).await.unwrap() {
+            Some(Message::ShutdownWarning(remaining)) => assert!(remaining <= 30),
+            message => panic!("expected a shutdown warning, got {:?}", message),
+        }
+        drop(stream);
+
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                HostingCall::Ready(port),
+                HostingCall::ActivateSession,
+                HostingCall::AcceptPlayerSession("player-session-1".to_owned()),
+                HostingCall::TerminationTime,
+                HostingCall::RemovePlayerSession("player-session-1".to_owned()),
+                HostingCall::ProcessEnding,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hosted_second_session_ignored() {
+        let port = free_port();
+        let backend = Arc::new(FakeBackend::default());
+        let server = spawn_hosted(backend.clone(), port, None);
+
+        wait_for_call(&backend, HostingCall::Ready(port)).await;
+        for session_id in ["session-1", "session-2"] {
+            backend
+                .start_session(SessionInfo {
+                    session_id: session_id.to_owned(),
+                    max_players: None,
+                })
+                .unwrap();
+        }
+        wait_for_call(&backend, HostingCall::ActivateSession).await;
+
+        // nobody connected, so the session drains straight away
+        backend.terminate(None).unwrap();
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        let calls = backend.calls();
+        assert_eq!(
+            calls
+                .iter()
+                .filter(|call| **call == HostingCall::ActivateSession)
+                .count(),
+            1
+        );
+        assert_eq!(calls.last(), Some(&HostingCall::ProcessEnding));
+    }
+
+    #[tokio::test]
+    async fn hosted_sessions_one_after_another() {
+        let port = free_port();
+        let backend = Arc::new(FakeBackend::default());
+        // sessions nobody joins end after a second
+        let server = spawn_hosted(backend.clone(), port, Some(1));
+
+        wait_for_call(&backend, HostingCall::Ready(port)).await;
+        for (ended, session_id) in ["session-1", "session-2"].into_iter().enumerate() {
+            backend
+                .start_session(SessionInfo {
+                    session_id: session_id.to_owned(),
+                    max_players: None,
+                })
+                .unwrap();
+
+            // the session ends itself with ProcessEnding
+            let started = time::Instant::now();
+            while backend
+                .calls()
+                .iter()
+                .filter(|call| **call == HostingCall::ProcessEnding)
+                .count()
+                <= ended
+            {
+                assert!(
+                    started.elapsed() < time::Duration::from_secs(5),
+                    "{} didn't end: {:?}",
+                    session_id,
+                    backend.calls()
+                );
+                time::sleep(time::Duration::from_millis(50)).await;
+            }
+        }
+
+        backend.terminate(None).unwrap();
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                HostingCall::Ready(port),
+                HostingCall::ActivateSession,
+                HostingCall::ProcessEnding,
+                HostingCall::ActivateSession,
+                HostingCall::ProcessEnding,
+                // the last session already ended the process
+                HostingCall::TerminationTime,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn hosted_terminate_without_session() {
+        let port = free_port();
+        let backend = Arc::new(FakeBackend::default());
+        let server = spawn_hosted(backend.clone(), port, None);
+
+        wait_for_call(&backend, HostingCall::Ready(port)).await;
+        backend.terminate(None).unwrap();
+
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            backend.calls(),
+            vec![
+                HostingCall::Ready(port),
+                HostingCall::TerminationTime,
+                HostingCall::ProcessEnding,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn standalone_keeps_running() {
+        let port = free_port();
+        let server = spawn_hosted(Arc::new(StandaloneBackend::default()), port, None);
+
+        // the session outlives the backend's ready call
+        time::sleep(time::Duration::from_secs(2)).await;
+        assert!(!server.is_finished());
+
+        let mut stream = connect(port, "player-session-1").await;
+        Message::Echo("hello".to_owned())
+            .write(&mut stream)
+            .await
+            .unwrap();
+        assert_eq!(
+            Message::read(&mut stream).await.unwrap(),
+            Some(Message::Echo("hello".to_owned()))
+        );
+
+        server.abort();
+    }
+
+    #[tokio::test]
+    async fn gamelift_session_lifecycle() {
+        let port = free_port();
+        let api = Arc::new(FakeGameLiftApi::default());
+        let server = spawn_hosted(Arc::new(GameLiftBackend::with_api(api.clone())), port, None);
+
+        wait_for_gamelift_call(&api, GameLiftCall::ProcessReady(port as i32)).await;
+        assert!(api.health_check().await.unwrap());
+
+        let game_session = GameSession {
+            game_session_id: Some("game-session-1".to_owned()),
+            max_players: 2,
+            port: port as i32,
+            ..Default::default()
+        };
+        api.start_game_session(game_session.clone()).await.unwrap();
+        wait_for_gamelift_call(&api, GameLiftCall::ActivateGameSession).await;
+
+        let mut stream = connect(port, "player-session-1").await;
+        Message::Echo("hello".to_owned())
+            .write(&mut stream)
+            .await
+            .unwrap();
+        assert_eq!(
+            Message::read(&mut stream).await.unwrap(),
+            Some(Message::Echo("hello".to_owned()))
+        );
+        wait_for_gamelift_call(
+            &api,
+            GameLiftCall::AcceptPlayerSession("player-session-1".to_owned()),
+        )
+        .await;
+
+        // updates are only logged, the session keeps running
+        api.update_game_session(UpdateGameSession {
+            game_session: Some(game_session),
+            update_reason: UpdateReason::MatchmakingDataUpdated,
+            backfill_ticket_id: String::new(),
+        })
+        .await
+        .unwrap();
+
+        api.terminate_process(Some(Utc::now().timestamp() + 30))
+            .await
+            .unwrap();
+        match Message::read(&mut stream).await.unwrap() {
+            Some(Message::ShutdownWarning(remaining)) => assert!(remaining <= 30),
+            message => panic!("expected a shutdown warning, got {:?}", message),
+        }
+        drop(stream);
+
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            api.calls(),
+            vec![
+                GameLiftCall::ProcessReady(port as i32),
+                GameLiftCall::ActivateGameSession,
+                GameLiftCall::AcceptPlayerSession("player-session-1".to_owned()),
+                GameLiftCall::GetTerminationTime,
+                GameLiftCall::RemovePlayerSession("player-session-1".to_owned()),
+                GameLiftCall::ProcessEnding,
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn gamelift_session_without_id() {
+        let port = free_port();
+        let api = Arc::new(FakeGameLiftApi::default());
+        let server = spawn_hosted(Arc::new(GameLiftBackend::with_api(api.clone())), port, None);
+
+        wait_for_gamelift_call(&api, GameLiftCall::ProcessReady(port as i32)).await;
+        api.start_game_session(GameSession::default())
+            .await
+            .unwrap();
+
+        // no termination time either, the default drain window is used
+        api.terminate_process(None).await.unwrap();
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+
+        assert_eq!(
+            api.calls(),
+            vec![
+                GameLiftCall::ProcessReady(port as i32),
+                GameLiftCall::GetTerminationTime,
+                GameLiftCall::ProcessEnding,
+            ]
+        );
+    }
+}
@@ -0,0 +1,223 @@
+use std::path::PathBuf;
+
+use anyhow::anyhow;
+use serde::{Deserialize, Serialize};
+
+/// A matchmaking ticket that hasn't finished yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingTicket {
+    pub ticket_id: String,
+    pub player_ids: Vec<String>,
+    pub region: String,
+}
+
+/// A game session placement that hasn't finished yet
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PendingPlacement {
+    pub placement_id: String,
+    pub player_id: String,
+    pub region: String,
+}
+
+/// Client requests that are still in flight, so they can be resumed after a restart
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ClientState {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub ticket: Option<PendingTicket>,
+
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub placement: Option<PendingPlacement>,
+}
+
+/// Client state persisted as JSON
+pub struct StateFile {
+    path: PathBuf,
+}
+
+impl StateFile {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self { path: path.into() }
+    }
+
+    /// Loads the state, a missing file is an empty state
+    pub fn load(&self) -> anyhow::Result<ClientState> {
+        let state = match std::fs::read_to_string(&self.path) {
+            Ok(state) => state,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(ClientState::default())
+            }
+            Err(err) => return Err(anyhow!("Failed to read {}: {}", self.path.display(), err)),
+        };
+
+        serde_json::from_str(&state)
+            .map_err(|err| anyhow!("Invalid client state {}: {}", self.path.display(), err))
+    }
+
+    fn save(&self, state: &ClientState) -> anyhow::Result<()> {
+        if *state == ClientState::default() {
+            return match std::fs::remove_file(&self.path) {
+                Err(err) if err.kind() != std::io::ErrorKind::NotFound => {
+                    Err(anyhow!("Failed to remove {}: {}", self.path.display(), err))
+                }
+                _ => Ok(()),
+            };
+        }
+
+        // write then rename, so a crash can't leave a partial file behind
+        let tmp_path = self.path.with_extension("tmp");
+        std::fs::write(&tmp_path, serde_json::to_string_pretty(state)?)
+            .map_err(|err| anyhow!("Failed to write {}: {}", tmp_path.display(), err))?;
+        std::fs::rename(&tmp_path, &self.path)
+            .map_err(|err| anyhow!("Failed to write {}: {}", self.path.display(), err))?;
+
+        Ok(())
+    }
+
+    /// Loads the state, applies the update and saves it
+    pub fn update<F>(&self, f: F) -> anyhow::Result<()>
+    where
+        F: FnOnce(&mut ClientState),
+    {
+        let mut state = self.load()?;
+        f(&mut state);
+        self.save(&state)
+    }
+
+    pub fn pending_ticket(&self) -> anyhow::Result<PendingTicket> {
+        self.load()?
+            .ticket
+            .ok_or_else(|| anyhow!("No pending matchmaking ticket in {}", self.path.display()))
+    }
+
+    pub fn pending_placement(&self) -> anyhow::Result<PendingPlacement> {
+        self.load()?
+            .placement
+            .ok_or_else(|| anyhow!("No pending placement in {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use uuid::Uuid;
+
+    use super::*;
+
+    fn state_file() -> StateFile {
+        StateFile::new(std::env::temp_dir().join(format!("echo-state-{}.json", Uuid::new_v4())))
+    }
+
+    fn ticket() -> PendingTicket {
+        PendingTicket {
+            ticket_id: "ticket-1".to_owned(),
+            player_ids: vec!["player-1".to_owned(), "player-2".to_owned()],
+            region: "us-west-2".to_owned(),
+        }
+    }
+
+    fn placement() -> PendingPlacement {
+        PendingPlacement {
+            placement_id: "placement-1".to_owned(),
+            player_id: "player-1".to_owned(),
+            region: "us-west-2".to_owned(),
+        }
+    }
+
+    #[test]
+    fn round_trips() {
+        let file = state_file();
+
+        file.update(|state| state.ticket = Some(ticket())).unwrap();
+        file.update(|state| state.placement = Some(placement()))
+            .unwrap();
+        assert_eq!(file.pending_ticket().unwrap(), ticket());
+        assert_eq!(file.pending_placement().unwrap(), placement());
+        assert!(!file.path.with_extension("tmp").exists());
+
+        // a fresh handle on the same file sees the same state
+        let reopened = StateFile::new(&file.path);
+        assert_eq!(
+            reopened.load().unwrap(),
+            ClientState {
+                ticket: Some(ticket()),
+                placement: Some(placement()),
+            }
+        );
+
+        file.update(|state| state.ticket = None).unwrap();
+        assert!(file.pending_ticket().is_err());
+        assert_eq!(file.pending_placement().unwrap(), placement());
+
+        // nothing pending, nothing left behind
+        file.update(|state| state.placement = None).unwrap();
+        assert!(!file.path.exists());
+    }
+
+    #[test]
+    fn missing_file_is_empty() {
+        let file = state_file();
+
+        assert_eq!(file.load().unwrap(), ClientState::default());
+        assert_eq!(
+            file.pending_ticket().unwrap_err().to_string(),
+            format!("No pending matchmaking ticket in {}", file.path.display())
+        );
+        assert!(file.pending_placement().is_err());
+
+        file.update(|_| ()).unwrap();
+        assert!(!file.path.exists());
+    }
+
+    #[test]
+    fn stale_files() {
+        let file = state_file();
+
+        // left behind by a crash mid write
+        let tmp_path = file.path.with_extension("tmp");
+        std::fs::write(&tmp_path, "{\"ticket\": ").unwrap();
+        assert_eq!(file.load().unwrap(), ClientState::default());
+        file.update(|state| state.ticket = Some(ticket())).unwrap();
+        assert!(!tmp_path.exists());
+        assert_eq!(file.pending_ticket().unwrap(), ticket());
+
+        // written by another version
+        std::fs::write(
+            &file.path,
+            r#"{"ticket": {"ticketId": "ticket-1", "playerIds": ["player-1", "player-2"],
+                "region": "us-west-2", "attempts": 3}, "lastRun": "yesterday"}"#,
+        )
+        .unwrap();
+        assert_eq!(file.pending_ticket().unwrap(), ticket());
+
+        std::fs::remove_file(&file.path).unwrap();
+    }
+
+    #[test]
+    fn corrupt_files() {
+        let file = state_file();
+
+        for corrupt in [
+            "{\"ticket\": ",
+            "\"ticket-1\"",
+            "{\"ticket\": {\"ticketId\": 1}}",
+        ] {
+            std::fs::write(&file.path, corrupt).unwrap();
+
+            let err = file.load().unwrap_err();
+            assert!(
+                err.to_string()
+                    .starts_with(&format!("Invalid client state {}", file.path.display())),
+                "{}",
+                err
+            );
+            assert!(file.pending_placement().is_err());
+
+            // left alone rather than overwritten
+            assert!(file.update(|state| state.ticket = Some(ticket())).is_err());
+            assert_eq!(std::fs::read_to_string(&file.path).unwrap(), corrupt);
+        }
+
+        std::fs::remove_file(&file.path).unwrap();
+    }
+}
@@ -0,0 +1,416 @@
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
+use std::str::FromStr;
+
+use anyhow::bail;
+use futures_util::future::join_all;
+use tokio::{
+    net::TcpStream,
+    process::{Child, Command},
+    time,
+};
+use tracing::{info, warn};
+
+use crate::util;
+
+/// How often to check on the children
+const MONITOR_INTERVAL: time::Duration = time::Duration::from_secs(1);
+
+/// How often to log the aggregate health of the children
+const HEALTH_INTERVAL: time::Duration = time::Duration::from_secs(10);
+
+/// How long to wait on a health probe
+const HEALTH_TIMEOUT: time::Duration = time::Duration::from_secs(1);
+
+/// Restart backoff starts here and doubles with each consecutive crash
+const RESTART_BACKOFF_MIN: time::Duration = time::Duration::from_secs(1);
+const RESTART_BACKOFF_MAX: time::Duration = time::Duration::from_secs(60);
+
+/// Children that run this long are considered stable and have their backoff reset
+const STABLE_RUNTIME: time::Duration = time::Duration::from_secs(60);
+
+/// How long to wait for children to exit on shutdown
+const SHUTDOWN_TIMEOUT: time::Duration = time::Duration::from_secs(60);
+
+/// The server mode supervised children run as
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ChildMode {
+    GameLift,
+    Dedicated,
+}
+
+impl ChildMode {
+    fn subcommand(&self) -> &'static str {
+        match self {
+            Self::GameLift => "gamelift",
+            Self::Dedicated => "dedicated",
+        }
+    }
+}
+
+impl FromStr for ChildMode {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "gamelift" => Ok(Self::GameLift),
+            "dedicated" => Ok(Self::Dedicated),
+            _ => Err(format!("invalid child mode: {}", s)),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum ChildHealth {
+    /// Running and accepting connections
+    Active,
+
+    /// Running but not (yet) accepting connections
+    Idle,
+
+    /// Waiting to be restarted
+    Restarting,
+}
+
+struct SupervisedChild {
+    port: u16,
+    log_dir: PathBuf,
+
+    process: Option<Child>,
+    started: time::Instant,
+
+    restarts: u32,
+    next_start: time::Instant,
+}
+
+impl SupervisedChild {
+    fn new(port: u16, log_dir: PathBuf) -> Self {
+        let now = time::Instant::now();
+        Self {
+            port,
+            log_dir,
+            process: None,
+            started: now,
+            restarts: 0,
+            next_start: now,
+        }
+    }
+
+    fn start(&mut self, mode: ChildMode) -> anyhow::Result<()> {
+        std::fs::create_dir_all(&self.log_dir)?;
+
+        info!(
+            "Starting {} child on port {} (logs: {}) ...",
+            mode.subcommand(),
+            self.port,
+            self.log_dir.display()
+        );
+
+        let process = Command::new(std::env::current_exe()?)
+            .arg("--log-dir")
+            .arg(&self.log_dir)
+            .arg(mode.subcommand())
+            .arg("--port")
+            .arg(self.port.to_string())
+            .stdin(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()?;
+
+        self.process = Some(process);
+        self.started = time::Instant::now();
+
+        Ok(())
+    }
+
+    /// Checks if the child has exited and schedules a restart if it has
+    fn check_exited(&mut self) -> anyhow::Result<()> {
+        let process = match self.process.as_mut() {
+            Some(process) => process,
+            None => return Ok(()),
+        };
+
+        let status = match process.try_wait()? {
+            Some(status) => status,
+            None => return Ok(()),
+        };
+
+        let now = time::Instant::now();
+        if now.duration_since(self.started) >= STABLE_RUNTIME {
+            self.restarts = 0;
+        }
+
+        let backoff = restart_backoff(self.restarts);
+        warn!(
+            "Child on port {} exited ({}), restarting in {:?} ...",
+            self.port, status, backoff
+        );
+
+        self.process = None;
+        self.restarts += 1;
+        self.next_start = now + backoff;
+
+        Ok(())
+    }
+
+    async fn health(&self) -> ChildHealth {
+        if self.process.is_none() {
+            return ChildHealth::Restarting;
+        }
+
+        match time::timeout(HEALTH_TIMEOUT, probe(self.port)).await {
+            Ok(Ok(())) => ChildHealth::Active,
+            _ => ChildHealth::Idle,
+        }
+    }
+
+    fn terminate(&mut self) {
+        let process = match self.process.as_mut() {
+            Some(process) => process,
+            None => return,
+        };
+
+        if let Err(err) = signal_terminate(process) {
+            warn!("Failed to terminate child on port {}: {}", self.port, err);
+        }
+    }
+
+    async fn wait(&mut self) {
+        let process = match self.process.as_mut() {
+            Some(process) => process,
+            None => return,
+        };
+
+        match time::timeout(SHUTDOWN_TIMEOUT, process.wait()).await {
+            Ok(Ok(status)) => info!("Child on port {} exited ({})", self.port, status),
+            Ok(Err(err)) => warn!("Failed to wait on child on port {}: {}", self.port, err),
+            Err(_) => {
+                warn!("Child on port {} failed to exit, killing ...", self.port);
+                process.kill().await.ok();
+            }
+        }
+
+        self.process = None;
+    }
+}
+
+fn restart_backoff(restarts: u32) -> time::Duration {
+    RESTART_BACKOFF_MIN
+        .saturating_mul(2_u32.saturating_pow(restarts))
+        .min(RESTART_BACKOFF_MAX)
+}
+
+/// Connects to a child and sends an empty handshake, which servers treat as a health probe
+async fn probe(port: u16) -> anyhow::Result<()> {
+    let mut stream = TcpStream::connect(format!("127.0.0.1:{}", port)).await?;
+    echo_protocol::write_player_session_id(&mut stream, "").await?;
+
+    Ok(())
+}
+
+#[cfg(unix)]
+fn signal_terminate(process: &mut Child) -> anyhow::Result<()> {
+    let pid = match process.id() {
+        Some(pid) => pid,
+        None => return Ok(()),
+    };
+
+    // SIGTERM lets the child drain its session
+    if unsafe { libc::kill(pid as libc::pid_t, libc::SIGTERM) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+
+    Ok(())
+}
+
+#[cfg(not(unix))]
+fn signal_terminate(process: &mut Child) -> anyhow::Result<()> {
+    process.start_kill()?;
+
+    Ok(())
+}
+
+fn log_health(children: &[SupervisedChild], health: &[ChildHealth]) {
+    let count = |state| health.iter().filter(|h| **h == state).count();
+
+    info!(
+        "Health: {} children, {} active, {} idle, {} restarting",
+        children.len(),
+        count(ChildHealth::Active),
+        count(ChildHealth::Idle),
+        count(ChildHealth::Restarting)
+    );
+
+    for (child, health) in children.iter().zip(health) {
+        if *health == ChildHealth::Restarting {
+            warn!(
+                "Child on port {} is restarting (restarts: {})",
+                child.port, child.restarts
+            );
+        }
+    }
+}
+
+pub async fn run(
+    mode: ChildMode,
+    count: u16,
+    base_port: u16,
+    log_dir: impl AsRef<Path>,
+) -> anyhow::Result<()> {
+    if count == 0 {
+        bail!("Child count must be greater than 0");
+    }
+
+    let last_port = match base_port.checked_add(count - 1) {
+        Some(last_port) => last_port,
+        None => bail!("Port range overflows: {} + {}", base_port, count),
+    };
+
+    info!(
+        "Supervising {} {} children on ports {}-{} ...",
+        count,
+        mode.subcommand(),
+        base_port,
+        last_port
+    );
+
+    let mut children = (base_port..=last_port)
+        .map(|port| SupervisedChild::new(port, log_dir.as_ref().join(port.to_string())))
+        .collect::<Vec<_>>();
+
+    let mut monitor = time::interval(MONITOR_INTERVAL);
+    let mut health = time::interval(HEALTH_INTERVAL);
+
+    let terminate = util::wait_for_terminate();
+    tokio::pin!(terminate);
+
+    let interrupt = tokio::signal::ctrl_c();
+    tokio::pin!(interrupt);
+
+    loop {
+        tokio::select! {
+            _ = monitor.tick() => {
+                let now = time::Instant::now();
+                for child in children.iter_mut() {
+                    child.check_exited()?;
+
+                    if child.process.is_none() && now >= child.next_start {
+                        if let Err(err) = child.start(mode) {
+                            let backoff = restart_backoff(child.restarts);
+                            warn!(
+                                "Failed to start child on port {}: {}, retrying in {:?} ...",
+                                child.port, err, backoff
+                            );
+
+                            child.restarts += 1;
+                            child.next_start = now + backoff;
+                        }
+                    }
+                }
+            }
+            _ = health.tick() => {
+                // probed together, so hung children only hold things up for one timeout
+                let states = join_all(children.iter().map(SupervisedChild::health)).await;
+
+                log_health(&children, &states);
+            }
+            res = &mut terminate => {
+                res?;
+
+                info!("Received SIGTERM, stopping children ...");
+                break;
+            }
+            res = &mut interrupt => {
+                res?;
+
+                info!("Received Ctrl-C, stopping children ...");
+                break;
+            }
+        }
+    }
+
+    for child in children.iter_mut() {
+        child.terminate();
+    }
+
+    for child in children.iter_mut() {
+        child.wait().await;
+    }
+
+    info!("All children stopped!");
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tokio::sync::watch;
+
+    use super::*;
+    use crate::server;
+
+    #[test]
+    fn restart_backoff_doubles_up_to_the_max() {
+        for (restarts, seconds) in [
+            (0, 1),
+            (1, 2),
+            (2, 4),
+            (5, 32),
+            (6, 60),
+            (7, 60),
+            (u32::MAX, 60),
+        ] {
+            assert_eq!(
+                restart_backoff(restarts),
+                time::Duration::from_secs(seconds),
+                "after {} restarts",
+                restarts
+            );
+        }
+    }
+
+    #[tokio::test]
+    async fn probe_checks_the_server_accepts_connections() {
+        let port = std::net::TcpListener::bind("127.0.0.1:0")
+            .unwrap()
+            .local_addr()
+            .unwrap()
+            .port();
+
+        // nothing listening yet
+        assert!(probe(port).await.is_err());
+
+        let (shutdown_sender, shutdown) = watch::channel(false);
+        let (_drain_sender, drain) = watch::channel(None);
+        let server = tokio::spawn(server::run(
+            format!("127.0.0.1:{}", port),
+            true,
+            shutdown,
+            drain,
+            server::ServerCallbacks::default(),
+            None,
+        ));
+
+        let started = time::Instant::now();
+        while probe(port).await.is_err() {
+            assert!(
+                started.elapsed() < time::Duration::from_secs(5),
+                "server never came up"
+            );
+            time::sleep(time::Duration::from_millis(10)).await;
+        }
+
+        shutdown_sender.send(true).unwrap();
+        time::timeout(time::Duration::from_secs(5), server)
+            .await
+            .unwrap()
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn stopped_children_are_restarting() {
+        let child = SupervisedChild::new(1, PathBuf::from("unused"));
+
+        assert_eq!(child.health().await, ChildHealth::Restarting);
+    }
+}
@@ -1,14 +1,63 @@
+use std::future;
+use std::sync::OnceLock;
+
 use tokio::sync::watch;
 
-pub async fn wait_for_signal(mut receiver: watch::Receiver<bool>) -> anyhow::Result<()> {
-    loop {
-        receiver.changed().await?;
-        if *receiver.borrow() {
-            break;
-        }
+/// Set once Ctrl-C is pressed, see listen_for_interrupt()
+static INTERRUPT: OnceLock<watch::Receiver<bool>> = OnceLock::new();
 
-        tokio::task::yield_now().await;
-    }
+/// Waits for SIGTERM (or Ctrl-C where SIGTERM isn't available)
+#[cfg(unix)]
+pub async fn wait_for_terminate() -> anyhow::Result<()> {
+    use tokio::signal::unix::{signal, SignalKind};
+
+    signal(SignalKind::terminate())?.recv().await;
+
+    Ok(())
+}
+
+/// Waits for SIGTERM (or Ctrl-C where SIGTERM isn't available)
+#[cfg(not(unix))]
+pub async fn wait_for_terminate() -> anyhow::Result<()> {
+    tokio::signal::ctrl_c().await?;
 
     Ok(())
 }
+
+/// Starts listening for Ctrl-C for the rest of the process
+///
+/// Waiting on ctrl_c() only sees presses made during that wait, this remembers every press
+pub fn listen_for_interrupt() {
+    let (sender, receiver) = watch::channel(false);
+    if INTERRUPT.set(receiver).is_err() {
+        return;
+    }
+
+    tokio::spawn(async move {
+        if tokio::signal::ctrl_c().await.is_ok() {
+            sender.send_replace(true);
+        }
+
+        // hold on to the sender, so receivers don't see it close
+        future::pending::<()>().await;
+    });
+}
+
+/// Ctrl-C as seen by listen_for_interrupt(), never set if nothing is listening
+pub fn interrupt() -> watch::Receiver<bool> {
+    INTERRUPT
+        .get()
+        .cloned()
+        .unwrap_or_else(|| watch::channel(false).1)
+}
+
+/// Waits until the interrupt is set, which may already have happened
+pub async fn wait_for_interrupt(mut interrupt: watch::Receiver<bool>) {
+    if interrupt
+        .wait_for(|interrupted| *interrupted)
+        .await
+        .is_err()
+    {
+        future::pending::<()>().await;
+    }
+}